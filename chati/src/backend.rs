@@ -0,0 +1,427 @@
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::chatgpt::{ChatGPT, Unavailable};
+use crate::completions::CompletionsBackend;
+use crate::config::{BackendConfig, WebConfig};
+use crate::intercept::{Interceptor, NetworkEvent, TargetSelector};
+use crate::openai::{self, MessageInfo};
+use crate::ratelimit::{self, RateLimiter};
+use crate::replay::{Recorder, ReplayBackend};
+use crate::supervisor::{Relay, Supervisor};
+use crate::trace::StepDebugger;
+use crate::transcript::Transcript;
+
+use tracing::{debug, error};
+
+/// What the assistant says back, piece by piece.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// a message of the answer begins
+    Message(MessageInfo),
+    /// a chunk of the answer
+    Words(String),
+    /// the answer is complete
+    End,
+    /// the assistant failed to answer; the message should be said again
+    Nothing,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BackendError {
+    #[error("webdriver: {0}")]
+    WebDriver(#[from] fantoccini::error::CmdError),
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("no conversation, call new_conversation first")]
+    NoConversation,
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the backend can not reopen conversations")]
+    CannotReopen,
+    #[error("the assistant reached its limit of messages, try again {}", try_again(.retry_after))]
+    Tired {
+        /// how long until it takes messages again, when known
+        retry_after: Option<Duration>,
+    },
+    #[error("logged out of the assistant")]
+    LoggedOut,
+    #[error("the browser session is lost")]
+    SessionLost,
+    #[error("a message is stuck sending")]
+    Stuck,
+    #[error("launch the browser: {0}")]
+    Launch(#[from] fantoccini::error::NewSessionError),
+}
+
+impl From<Unavailable> for BackendError {
+    fn from(unavailable: Unavailable) -> Self {
+        match unavailable {
+            Unavailable::Tired => BackendError::Tired { retry_after: None },
+            Unavailable::LoggedOut => BackendError::LoggedOut,
+            Unavailable::Lost => BackendError::SessionLost,
+            Unavailable::Stuck => BackendError::Stuck,
+        }
+    }
+}
+
+fn try_again(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(retry_after) => format!("in {}", ratelimit::clock(*retry_after)),
+        None => "later".to_string(),
+    }
+}
+
+/// Something able to hold a conversation with an assistant.
+///
+/// `isaid` only delivers the message. The answer arrives later through the
+/// channel handed to `new_conversation`, ended by either [`Reply::End`] or
+/// [`Reply::Nothing`].
+#[async_trait]
+pub trait ChatBackend: Send {
+    async fn new_conversation(
+        &mut self,
+        auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError>;
+
+    /// Go on with the conversation of `transcript`, as `new_conversation`
+    /// starts one.
+    async fn reopen_conversation(
+        &mut self,
+        _transcript: &Transcript,
+        _auto_login: bool,
+        _replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        Err(BackendError::CannotReopen)
+    }
+
+    /// Set the instructions the assistant follows for the rest of the
+    /// conversation. `false` when the backend has no such thing.
+    async fn set_system(&mut self, _prompt: &str) -> Result<bool, BackendError> {
+        Ok(false)
+    }
+
+    /// forget the last message said and its answer, before saying it again
+    async fn forget_last_exchange(&mut self) -> Result<(), BackendError> {
+        Ok(())
+    }
+
+    async fn isaid(&mut self, said: &str) -> Result<(), BackendError>;
+
+    async fn end(self: Box<Self>) -> Result<(), BackendError>;
+}
+
+/// create the backend described by `config`
+pub async fn connect(config: &BackendConfig) -> Result<Box<dyn ChatBackend>, BackendError> {
+    match config {
+        BackendConfig::Web(web) => Ok(Box::new(WebBackend::new(web).await?)),
+        BackendConfig::Completions(completions) => {
+            Ok(Box::new(CompletionsBackend::new(completions)?))
+        }
+        BackendConfig::Replay(replay) => Ok(Box::new(ReplayBackend::open(&replay.path)?)),
+    }
+}
+
+/// chatgpt.com driven by webdriver, with answers captured through cdp
+pub struct WebBackend {
+    gpt: ChatGPT,
+    supervisor: Supervisor,
+    debugging_port: u16,
+    record: Option<PathBuf>,
+    /// listens to the tab from the first conversation on
+    listening: Option<JoinHandle<()>>,
+}
+
+impl WebBackend {
+    pub async fn new(config: &WebConfig) -> Result<Self, BackendError> {
+        let mut gpt = ChatGPT::new(&config.browser).await?;
+        gpt.fail_fast(config.fail_fast);
+        if let Some(step_debug) = &config.step_debug {
+            gpt.step_debug(Some(Arc::new(StepDebugger::new(step_debug)?)));
+        }
+        let supervisor = Supervisor::new(
+            Relay::new(),
+            config.initial_prompts.clone(),
+            config.max_recoveries,
+            RateLimiter::new(&config.rate_limit, &config.browser),
+        );
+        Ok(WebBackend {
+            gpt,
+            supervisor,
+            debugging_port: config.browser.debugging_port,
+            record: config.record.clone(),
+            listening: None,
+        })
+    }
+
+    /// Forward the answers to `replies`. The tab is listened to once the
+    /// returned flag is set, that is when the page is open.
+    fn listen(&mut self, replies: mpsc::UnboundedSender<Reply>) -> Arc<AtomicBool> {
+        let relay = self.supervisor.relay().clone();
+        relay.connect(replies);
+        let flag_tx = Arc::new(AtomicBool::new(false));
+        if self.listening.is_some() {
+            return flag_tx;
+        }
+        let flag_rx = Arc::clone(&flag_tx);
+
+        let debugging_port = self.debugging_port;
+        let record = self.record.clone();
+        self.listening = Some(tokio::task::spawn(async move {
+            loop {
+                if flag_rx.load(Ordering::Acquire) {
+                    debug!("Flag is set, task can proceed");
+                    break;
+                } else {
+                    debug!("Flag is not set, checking again...");
+                    // To prevent busy-waiting, you can sleep for a short duration
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+            }
+
+            let mut recorder = match record.map(Recorder::create).transpose() {
+                Ok(recorder) => recorder,
+                Err(error) => {
+                    error!("create the recording: {error}");
+                    None
+                }
+            };
+            let events = Interceptor::new(debugging_port)
+                .target(TargetSelector::Url("https://chatgpt.com/".to_string()))
+                .stream(|url| url == openai::CONVERSATION_URL)
+                .watch(|url| url == openai::SENTINEL_URL)
+                .listen()
+                .inspect(move |event| {
+                    if let Some(recorder) = recorder.as_mut() {
+                        if let Err(error) = recorder.record(event) {
+                            error!("record {event:?}: {error}");
+                        }
+                    }
+                });
+            relay.run(events).await;
+        }));
+        flag_tx
+    }
+}
+
+#[async_trait]
+impl ChatBackend for WebBackend {
+    async fn new_conversation(
+        &mut self,
+        auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        let flag_tx = self.listen(replies);
+        self.supervisor.forget_system();
+        if auto_login {
+            self.gpt.new_session(flag_tx).await?;
+        } else {
+            self.gpt.wait_for_chatbox(flag_tx).await?;
+        }
+        self.supervisor.prime(&mut self.gpt).await
+    }
+
+    async fn reopen_conversation(
+        &mut self,
+        transcript: &Transcript,
+        _auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        let id = transcript
+            .conversation_id
+            .as_deref()
+            .ok_or(BackendError::CannotReopen)?;
+        let flag_tx = self.listen(replies);
+        self.supervisor.forget_system();
+        Ok(self.gpt.open_conversation(id, flag_tx).await?)
+    }
+
+    /// said as a message, and again whenever the browser is started again
+    async fn set_system(&mut self, prompt: &str) -> Result<bool, BackendError> {
+        self.supervisor.set_system(&mut self.gpt, prompt).await?;
+        Ok(true)
+    }
+
+    async fn isaid(&mut self, said: &str) -> Result<(), BackendError> {
+        self.supervisor.say(&mut self.gpt, said).await
+    }
+
+    async fn end(mut self: Box<Self>) -> Result<(), BackendError> {
+        if let Some(listening) = self.listening.take() {
+            listening.abort();
+        }
+        Ok(self.gpt.close().await?)
+    }
+}
+
+/// Dropped without [`ChatBackend::end`], as by a panic, the tab is not
+/// listened to any more.
+impl Drop for WebBackend {
+    fn drop(&mut self) {
+        if let Some(listening) = self.listening.take() {
+            listening.abort();
+        }
+    }
+}
+
+/// Decode the conversation streams among `events` into `replies` until
+/// either of them ends. Every response is decoded on its own, so that one cut
+/// off or interleaved leaves the others whole.
+pub async fn forward_replies(
+    mut events: impl Stream<Item = NetworkEvent> + Unpin,
+    replies: mpsc::UnboundedSender<Reply>,
+) {
+    // It could send two or more None in a run when respond to one chat message.
+    // The Situation is: when you send your first message, chatgpt.com could
+    // respond with an http 403 error. Then, you click the button "重新生成",
+    // chatgpt.com responds again with an http 403 error. This redundant message
+    // will result in sending the same user message again. Hence, `has_said_none`
+    // variable ensures at most one deliverary semantics.
+    let mut has_said_none = false;
+    let mut decoders: HashMap<String, openai::AssistantDecoder> = HashMap::new();
+    // the responses whose message ended, what comes after repeats the end
+    let mut ended = HashSet::new();
+    let mut message_id = None;
+    loop {
+        let event = tokio::select! {
+            event = events.next() => event,
+            // nobody waits for the answers any more
+            _ = replies.closed() => None,
+        };
+        match event {
+            Some(NetworkEvent::Response {
+                request_id, status, ..
+            }) => {
+                decoders.remove(&request_id);
+                // another answer begins, the ended ones are over
+                ended.clear();
+                if status / 100 != 2 && !has_said_none {
+                    has_said_none = true;
+                    if let Err(error) = replies.send(Reply::Nothing) {
+                        error!("send response data to inner channel: {error:#?}");
+                    }
+                }
+            }
+            Some(NetworkEvent::Data { request_id, data }) => {
+                has_said_none = false;
+                if ended.contains(&request_id) {
+                    continue;
+                }
+                let decoder = decoders.entry(request_id.clone()).or_default();
+                let mut finished = false;
+                decoder.feed(&data, |info, stream_msg, ended| {
+                    if info.message_id != message_id {
+                        message_id = info.message_id.clone();
+                        if let Err(error) = replies.send(Reply::Message(info.clone())) {
+                            error!("send response data to inner channel: {error:#?}");
+                        }
+                    }
+                    if let Err(error) = replies.send(Reply::Words(stream_msg.to_string())) {
+                        error!("send response data to inner channel: {error:#?}");
+                    }
+                    if ended {
+                        finished = true;
+                        if let Err(error) = replies.send(Reply::End) {
+                            error!("send response data to inner channel: {error:#?}");
+                        }
+                    }
+                });
+                if finished {
+                    decoders.remove(&request_id);
+                    ended.insert(request_id);
+                }
+            }
+            // the responses in flight are abandoned
+            Some(NetworkEvent::Detached { .. } | NetworkEvent::Navigated { .. }) => {
+                decoders.clear();
+                ended.clear();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai::tests::message_event;
+
+    fn response(request_id: &str) -> NetworkEvent {
+        NetworkEvent::Response {
+            request_id: request_id.to_string(),
+            url: openai::CONVERSATION_URL.to_string(),
+            status: 200,
+        }
+    }
+
+    fn data(request_id: &str, data: &[u8]) -> NetworkEvent {
+        NetworkEvent::Data {
+            request_id: request_id.to_string(),
+            data: data.to_vec(),
+        }
+    }
+
+    async fn forward(events: Vec<NetworkEvent>) -> Vec<Reply> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        forward_replies(futures::stream::iter(events), tx).await;
+        let mut replies = Vec::new();
+        while let Some(reply) = rx.recv().await {
+            if !matches!(reply, Reply::Message(_)) {
+                replies.push(reply);
+            }
+        }
+        replies
+    }
+
+    #[tokio::test]
+    async fn test_forward_replies() {
+        let words = |words: &str| Reply::Words(words.to_string());
+
+        // a response cut in the middle of a line leaves the next one whole
+        let cut = message_event("m1", "一", "in_progress").into_bytes();
+        let events = vec![
+            response("r1"),
+            data("r1", &cut[..cut.len() / 2]),
+            NetworkEvent::Detached {
+                reason: "closed".to_string(),
+            },
+            response("r2"),
+            data(
+                "r2",
+                message_event("m2", "二", "finished_successfully").as_bytes(),
+            ),
+            data(
+                "r2",
+                message_event("m2", "二", "finished_successfully").as_bytes(),
+            ),
+        ];
+        assert_eq!(forward(events).await, [words("二"), Reply::End]);
+
+        // interleaved responses are not mixed up
+        let first = message_event("m1", "一", "finished_successfully").into_bytes();
+        let second = message_event("m2", "二", "in_progress").into_bytes();
+        let (first_head, first_tail) = first.split_at(first.len() / 2);
+        let (second_head, second_tail) = second.split_at(second.len() / 2);
+        let events = vec![
+            response("r1"),
+            response("r2"),
+            data("r1", first_head),
+            data("r2", second_head),
+            data("r1", first_tail),
+            data("r2", second_tail),
+        ];
+        assert_eq!(
+            forward(events).await,
+            [words("一"), Reply::End, words("二")]
+        );
+    }
+}
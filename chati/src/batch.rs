@@ -0,0 +1,225 @@
+use glob::Pattern;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use metacomment::language;
+
+/// Which files of the inputs to translate
+#[derive(Debug, Default)]
+pub struct FileSelector {
+    /// when not empty, a file has to match one of them
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+}
+
+impl FileSelector {
+    pub fn matches(&self, path: &Path) -> bool {
+        let matches = |pattern: &Pattern| {
+            pattern.matches_path(path)
+                || path
+                    .file_name()
+                    .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// Expand files, directories and glob patterns into the files to
+    /// translate, sorted. Inside directories, only files of a known language
+    /// are taken. Inputs which can not be read are returned as failures.
+    pub fn collect(&self, inputs: &[String]) -> (Vec<PathBuf>, Vec<(String, String)>) {
+        let mut files = Vec::new();
+        let mut failures = Vec::new();
+        for input in inputs {
+            let paths = if Path::new(input).exists() {
+                vec![PathBuf::from(input)]
+            } else {
+                match glob::glob(input) {
+                    Ok(paths) => paths.filter_map(Result::ok).collect(),
+                    Err(error) => {
+                        failures.push((input.clone(), error.to_string()));
+                        continue;
+                    }
+                }
+            };
+            if paths.is_empty() {
+                failures.push((input.clone(), "no such file".to_string()));
+            }
+            for path in paths {
+                if path.is_dir() {
+                    if let Err(error) = self.walk(&path, &mut files) {
+                        failures.push((path.display().to_string(), error.to_string()));
+                    }
+                } else if self.matches(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        files.dedup();
+        (files, failures)
+    }
+
+    fn walk(&self, dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                self.walk(&path, files)?;
+            } else if language::by_path(&path).is_some() && self.matches(&path) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the translation file of `code_file` goes, mirroring its path under `out_dir`.
+pub fn output_path(out_dir: &Path, code_file: &Path) -> PathBuf {
+    let mut path = out_dir.to_path_buf();
+    for component in code_file.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            // keep the mirror inside `out_dir`
+            Component::ParentDir => path.push("__"),
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => {}
+        }
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".jsonl");
+    path.set_file_name(name);
+    path
+}
+
+/// How far a batch went and how long the rest may take
+pub struct Progress {
+    pub files: usize,
+    pub files_done: usize,
+    pub comments: usize,
+    pub comments_done: usize,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn new(files: usize, comments: usize) -> Self {
+        Progress {
+            files,
+            files_done: 0,
+            comments,
+            comments_done: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// the time left if the rest goes as fast as what is done
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        if self.comments_done == 0 {
+            return None;
+        }
+        let left = self.comments.saturating_sub(self.comments_done) as u32;
+        Some(elapsed / self.comments_done as u32 * left)
+    }
+
+    pub fn report(&self) -> String {
+        let eta = match self.eta(self.started.elapsed()) {
+            Some(eta) => {
+                let secs = eta.as_secs();
+                format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            }
+            None => "-".to_string(),
+        };
+        format!(
+            "files {}/{}, comments {}/{}, ETA {eta}",
+            self.files_done, self.files, self.comments_done, self.comments
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let root = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in [
+            "a.c",
+            "b.h",
+            "README",
+            "sub/c.rs",
+            "sub/c_test.rs",
+            ".git/d.c",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let selector = FileSelector {
+            include: vec![],
+            exclude: vec![Pattern::new("*_test.rs").unwrap()],
+        };
+        let inputs = vec![
+            root.display().to_string(),
+            root.join("*.c").display().to_string(),
+            root.join("nothing.c").display().to_string(),
+        ];
+        let (files, failures) = selector.collect(&inputs);
+        let names = files
+            .iter()
+            .map(|file| file.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("a.c"),
+                PathBuf::from("b.h"),
+                PathBuf::from("sub/c.rs")
+            ]
+        );
+        assert_eq!(failures.len(), 1);
+
+        let selector = FileSelector {
+            include: vec![Pattern::new("**/sub/*").unwrap()],
+            exclude: vec![],
+        };
+        let (files, _) = selector.collect(&inputs[..1]);
+        assert_eq!(files.len(), 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_output_path() {
+        let out = Path::new("translated");
+        assert_eq!(
+            output_path(out, Path::new("src/backend/brin.c")),
+            PathBuf::from("translated/src/backend/brin.c.jsonl")
+        );
+        assert_eq!(
+            output_path(out, Path::new("/usr/src/a.c")),
+            PathBuf::from("translated/usr/src/a.c.jsonl")
+        );
+        assert_eq!(
+            output_path(out, Path::new("../a.c")),
+            PathBuf::from("translated/__/a.c.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_eta() {
+        let mut progress = Progress::new(2, 10);
+        assert_eq!(progress.eta(Duration::from_secs(10)), None);
+        progress.comments_done = 4;
+        assert_eq!(
+            progress.eta(Duration::from_secs(20)),
+            Some(Duration::from_secs(30))
+        );
+    }
+}
@@ -1,97 +1,378 @@
 // block code comment translator
 
-use chati::{chati::Chati, comment_extractor::CommentExtractor};
-use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
+use chati::{
+    backend::BackendError,
+    batch::{output_path, FileSelector, Progress},
+    chati::Chati,
+    comment_extractor::{CommentExtractor, CommentOfC},
+    config::Config,
+    glossary::Glossary,
+    journal::Journal,
+    memory::TranslationMemory,
+    pool::SessionPool,
+    ratelimit,
+    validator::ReplyValidator,
+};
+use futures::StreamExt;
+use metacomment::language;
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc, Mutex};
 use tokio::io::AsyncWriteExt;
 
+/// where the translation files go, one per code file mirroring its path;
+/// merger reads them, and a restarted run continues from them
+const OUTPUT_DIR: &str = "translated";
+
+/// how many times a translation breaking the rules is asked again
+const MAX_CORRECTIONS: usize = 2;
+
+/// the comments of one code file left to translate
+struct Job {
+    file: PathBuf,
+    validator: ReplyValidator,
+    journal: Journal,
+    pending: Vec<CommentOfC>,
+}
+
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <code_file>", args[0]);
+    let mut args = std::env::args().collect::<Vec<String>>();
+    let program = args.remove(0);
+    let mut out_dir = PathBuf::from(OUTPUT_DIR);
+    let mut sessions = 1;
+    let mut selector = FileSelector::default();
+    let mut inputs = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let option = matches!(
+            arg.as_str(),
+            "--out" | "--sessions" | "--include" | "--exclude"
+        );
+        if !option {
+            inputs.push(arg);
+            continue;
+        }
+        let Some(value) = args.next() else {
+            eprintln!("{arg} needs a value");
+            std::process::exit(1);
+        };
+        match arg.as_str() {
+            "--out" => out_dir = PathBuf::from(value),
+            "--sessions" => match value.parse::<usize>() {
+                Ok(n) if n > 0 => sessions = n,
+                _ => {
+                    eprintln!("--sessions {value}: not a number of sessions");
+                    std::process::exit(1);
+                }
+            },
+            _ => {
+                let pattern = glob::Pattern::new(&value).unwrap_or_else(|error| {
+                    eprintln!("{arg} {value}: {error}");
+                    std::process::exit(1);
+                });
+                if arg == "--include" {
+                    selector.include.push(pattern);
+                } else {
+                    selector.exclude.push(pattern);
+                }
+            }
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!(
+            "Usage: {program} [--out <dir>] [--sessions <n>] [--include <glob>]... [--exclude <glob>]... <file|dir|glob>..."
+        );
         std::process::exit(1);
     }
 
-    let code_file = std::fs::File::open(&args[1]).unwrap();
+    let config = Config::load().unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+    chati::trace::init(&config.log);
+    let (files, failures) = selector.collect(&inputs);
     tokio::runtime::Runtime::new()
         .unwrap()
-        .block_on(block_code_comment_translator(code_file));
+        .block_on(block_code_comment_translator(
+            &config, files, &out_dir, sessions, failures,
+        ));
 }
 
-async fn block_code_comment_translator(code_file: std::fs::File) {
-    let mut ci = Chati::new().await;
-    ci.new_converstation(false).await;
-
-    let command = r#"Suppose you are a specialized code comment translator.
-Translate code comments I will provide to you coming from database project in following conversations into Chinese.
-You need obey strictly the following rules:
+async fn block_code_comment_translator(
+    config: &Config,
+    files: Vec<PathBuf>,
+    out_dir: &Path,
+    sessions: usize,
+    mut failures: Vec<(String, String)>,
+) {
+    let glossary = Glossary::load(config.glossary.as_deref()).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+    // comments translated by an earlier run on another version of the code
+    let version = glossary.version();
+    let mut memory =
+        TranslationMemory::open(TranslationMemory::DEFAULT_PATH).expect("open translation memory");
 
-* The Chinese text should be placed in an independent comment of which style is the same as the original.
-* You should not output the original comment.
-* For some code-related names, say data structure name, function name, variable name, you should not translate it.
-* For some English terms which may have different meaning in regular English context, you should also not translate it.
-* The word in all upper case letters, you should not translate it.
-* Keep the original whitespaces at the head of every line.
-* The output should be in a c code block.
-* Wrap the line at around the column position 80.
-* Add one space between Chinese text and English text.
+    let file_count = files.len();
+    let mut jobs = Vec::new();
+    let mut done_before = 0;
+    for file in files {
+        match prepare_job(&file, out_dir, &mut memory, &version) {
+            Ok(job) => {
+                done_before += job.journal.len();
+                if !job.pending.is_empty() {
+                    jobs.push(job);
+                }
+            }
+            Err(error) => failures.push((file.display().to_string(), error.to_string())),
+        }
+    }
+    let total = jobs.iter().map(|job| job.pending.len()).sum::<usize>();
+    println!(
+        "{file_count} files, {done_before} comments translated before, {total} comments remaining"
+    );
+    println!("translation memory: {}", memory.stats());
 
-And remember the following translation rules:
-* 'cursor' should be translated as '游标'
-* 'shard' should be translated as '分片'
-* 'helper' should be translated as '辅助'
+    let mut flagged = 0;
+    if total > 0 {
+        let mut pool = SessionPool::start(&config.backend, sessions)
+            .await
+            .expect("connect to chat backend");
+        for ci in pool.sessions_mut() {
+            ci.new_converstation(false)
+                .await
+                .expect("start a new conversation");
 
-And do not translate the word/words below within a sentence:
-  * placement
-  * colocation,
-  * colocation id
-  * colocation group
+            // told again whenever the browser is started again
+            let told = ci
+                .set_system(&glossary.prompt())
+                .await
+                .expect("tell the translation rules");
+            if !told {
+                ensure_responded(ci, &glossary.prompt()).await;
+            }
+        }
 
-The final translation should preserve the structure and meaning of the original comment in Chinese."#;
+        // the comments go to whichever session is idle, their translations
+        // come back in the order of the files and of the comments in them,
+        // so that the journals are written as a single session would
+        let glossary = Arc::new(glossary);
+        let validators = Arc::new(
+            jobs.iter()
+                .map(|job| job.validator.clone())
+                .collect::<Vec<_>>(),
+        );
+        let mut remaining = jobs.iter().map(|job| job.pending.len()).collect::<Vec<_>>();
+        let comments = jobs
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(index, job)| {
+                std::mem::take(&mut job.pending)
+                    .into_iter()
+                    .map(move |com| (index, com))
+            })
+            .collect::<Vec<_>>();
+        // to tell which comment is lost with a session
+        let lines = comments
+            .iter()
+            .map(|(index, com)| (*index, com.line))
+            .collect::<Vec<_>>();
+        let mut translations = std::pin::pin!(pool.run(
+            comments,
+            move |mut ci, (index, com): (usize, CommentOfC)| {
+                let glossary = Arc::clone(&glossary);
+                let validators = Arc::clone(&validators);
+                async move {
+                    let (translated, violations) =
+                        translate(&mut ci, &com.content, &glossary, &validators[index]).await;
+                    (ci, (index, com, translated, violations))
+                }
+            }
+        ));
 
-    ensure_responded(&mut ci, &command, false).await;
+        let mut progress = Progress::new(jobs.len(), total);
+        let mut translating = None;
+        let mut broken = vec![false; jobs.len()];
+        // a file with a comment lost is not done either
+        let mut incomplete = vec![false; jobs.len()];
+        while let Some(result) = translations.next().await {
+            let (index, com, translated, violations) = match result {
+                Ok(translation) => translation,
+                Err(lost) => {
+                    let (index, line) = lines[lost.seq];
+                    let file = jobs[index].file.display().to_string();
+                    failures.push((file, format!("comment at line {line}: {lost}")));
+                    remaining[index] -= 1;
+                    incomplete[index] = true;
+                    continue;
+                }
+            };
+            let job = &mut jobs[index];
+            if translating != Some(index) {
+                translating = Some(index);
+                println!("TRANSLATING {}", job.file.display());
+            }
+            remaining[index] -= 1;
+            if broken[index] {
+                continue;
+            }
+            // take it anyway, but let a human check
+            for violation in &violations {
+                println!("FLAGGED {}:{}: {violation}", job.file.display(), com.line);
+            }
+            if violations.is_empty() {
+                if let Err(error) = memory.put(&com.content, &version, &translated) {
+                    println!("translation memory: {error}");
+                }
+            } else {
+                flagged += 1;
+            }
+            if let Err(error) = job.journal.record(&com, &translated) {
+                failures.push((job.file.display().to_string(), error.to_string()));
+                broken[index] = true;
+                continue;
+            }
+            progress.comments_done += 1;
+            // a file whose journal broke is never done
+            if remaining[index] == 0 && !incomplete[index] {
+                progress.files_done += 1;
+            }
+            println!("PROGRESS {}", progress.report());
+        }
+    }
 
-    let ce = CommentExtractor::new(code_file);
+    let summary = summary(&failures, flagged);
+    println!("{summary}");
+    if let Err(error) = std::fs::create_dir_all(out_dir)
+        .and_then(|_| std::fs::write(out_dir.join("summary.txt"), &summary))
+    {
+        eprintln!("write the summary to {}: {error}", out_dir.display());
+    }
+    println!("DONE");
+}
 
-    for com in ce {
-        ensure_responded(&mut ci, &com.content, true).await;
+/// open the translation file of `file` and take what is translated already
+fn prepare_job(
+    file: &Path,
+    out_dir: &Path,
+    memory: &mut TranslationMemory,
+    version: &str,
+) -> std::io::Result<Job> {
+    let language = language::detect(None, file).expect("a language always");
+    let journal_path = output_path(out_dir, file);
+    if let Some(dir) = journal_path.parent() {
+        std::fs::create_dir_all(dir)?;
     }
+    let mut journal = Journal::open(&journal_path)?;
+    let comments = CommentExtractor::with_language(std::fs::File::open(file)?, language)
+        .filter(|com| journal.get(com).is_none())
+        .collect::<Vec<_>>();
+    let mut pending = Vec::new();
+    for com in comments {
+        match memory.get(&com.content, version) {
+            Ok(Some(translated)) => journal.record(&com, &translated)?,
+            Ok(None) => pending.push(com),
+            Err(error) => {
+                println!("translation memory: {error}");
+                pending.push(com);
+            }
+        }
+    }
+    Ok(Job {
+        file: file.to_path_buf(),
+        validator: ReplyValidator::new(language),
+        journal,
+        pending,
+    })
+}
 
-    println!("DONE");
-    ci.end().await;
+/// Translate `comment`, asking again while the reply breaks the rules.
+/// Return the translation and the rules it still breaks.
+async fn translate(
+    ci: &mut Chati,
+    comment: &str,
+    glossary: &Glossary,
+    validator: &ReplyValidator,
+) -> (String, Vec<String>) {
+    let mut reply = ensure_responded(ci, &validator.request(comment)).await;
+    let mut corrections = 0;
+    loop {
+        let (translated, mut violations) = match validator.validate(comment, &reply) {
+            Ok(block) => (block.to_string(), Vec::new()),
+            Err(problems) => (
+                reply.clone(),
+                problems.iter().map(ToString::to_string).collect(),
+            ),
+        };
+        violations.extend(
+            glossary
+                .verify(comment, &translated)
+                .iter()
+                .map(ToString::to_string),
+        );
+        if violations.is_empty() || corrections == MAX_CORRECTIONS {
+            return (translated, violations);
+        }
+        corrections += 1;
+        let correction = violations
+            .iter()
+            .map(|violation| format!("* {violation}\n"))
+            .collect::<String>();
+        let correction = format!(
+            "Your translation breaks the rules below, please translate the comment again.\n{correction}"
+        );
+        reply = ensure_responded(ci, &correction).await;
+    }
 }
 
-async fn append_to_file(words: &str) {
-    let mut file = tokio::fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open("translated.txt")
-        .await
-        .expect("append to translated.txt");
-    let _ = file.write_all(words.as_bytes()).await;
+fn summary(failures: &[(String, String)], flagged: usize) -> String {
+    let mut summary = format!(
+        "{} failures, {flagged} translations flagged\n",
+        failures.len()
+    );
+    for (file, error) in failures {
+        summary.push_str(&format!("FAILED {file}: {error}\n"));
+    }
+    summary
 }
 
-async fn ensure_responded(ci: &mut Chati, isaid: &str, remember_he_said: bool) {
+/// say `isaid` until he responds, and return what he said
+async fn ensure_responded(ci: &mut Chati, isaid: &str) -> String {
     loop {
-        chati::util::pause().await;
         println!("I SAID: {isaid}");
         tokio::io::stdout().flush().await.unwrap();
 
-        ci.isaid(isaid).await;
+        match ci.isaid(isaid).await {
+            Ok(()) => {}
+            // the queue waits for the limit to reset
+            Err(BackendError::Tired {
+                retry_after: Some(retry_after),
+            }) => {
+                println!("rate limited for {}", ratelimit::clock(retry_after));
+                ratelimit::countdown(retry_after).await;
+                continue;
+            }
+            Err(error) => {
+                println!("failed to say: {error}. I will repeat my said");
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+        }
 
         print!("HE SAID: ");
         tokio::io::stdout().flush().await.unwrap();
 
         let repeat = Arc::new(AtomicBool::new(false));
+        let he_said = Arc::new(Mutex::new(String::new()));
         ci.hesaid(|words| {
             let repeat = Arc::clone(&repeat);
+            let he_said = Arc::clone(&he_said);
             async move {
                 match words {
                     Some(words) => {
                         // print!("{words}");
                         // let _ = tokio::io::stdout().flush().await;
-                        if remember_he_said {
-                            append_to_file(&words).await;
-                        }
+                        he_said.lock().unwrap().push_str(&words);
                     }
                     None => {
                         println!("he said nothing. I will repeat my said");
@@ -105,8 +386,8 @@ async fn ensure_responded(ci: &mut Chati, isaid: &str, remember_he_said: bool) {
         if !repeat.load(Ordering::Relaxed) {
             // println!();
             // tokio::io::stdout().flush().await.unwrap();
-            append_to_file("\n").await;
-            break;
+            let he_said = he_said.lock().unwrap().clone();
+            return he_said;
         }
     }
 }
@@ -1,32 +1,130 @@
+use chati::backend::BackendError;
 use chati::chati::Chati;
+use chati::config::Config;
+use chati::ratelimit;
+use chati::repl::{self, Command, InputHelper, MarkdownHighlighter};
+use chati::transcript::TranscriptStore;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use std::io::Write;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use env_logger;
-use log::{debug, info};
-use std::io::Write;
+use tracing::{debug, info};
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    let config = Config::load().unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+    chati::trace::init(&config.log);
+    let reopen = match std::env::args().skip(1).collect::<Vec<_>>().as_slice() {
+        [] => None,
+        [flag, id] if flag == "--reopen" => Some(id.clone()),
+        _ => {
+            eprintln!("Usage: chati [--reopen <conversation id>]");
+            std::process::exit(1);
+        }
+    };
 
-    let mut ci = Chati::new().await;
+    let mut ci = Chati::new(&config.backend)
+        .await
+        .expect("connect to chat backend");
+    let transcripts = config
+        .transcripts
+        .unwrap_or_else(|| TranscriptStore::DEFAULT_DIR.into());
+    ci.keep_transcripts(TranscriptStore::new(transcripts));
 
-    ci.new_converstation(true).await;
+    match reopen {
+        Some(id) => ci.reopen(&id, true).await.expect("reopen the conversation"),
+        None => ci
+            .new_converstation(true)
+            .await
+            .expect("start a new conversation"),
+    }
+    info!("transcript {}", ci.transcript().id);
 
-    loop {
-        print!(">>> ");
-        std::io::stdout().flush().unwrap();
+    let mut editor = Editor::<InputHelper, DefaultHistory>::new().expect("line editor");
+    editor.set_helper(Some(InputHelper));
+    let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(".chati_history"));
+    if let Some(history) = &history {
+        // there is none the first time
+        let _ = editor.load_history(history);
+    }
 
-        let mut i_said = String::new();
-        std::io::stdin().read_line(&mut i_said).unwrap();
+    loop {
+        let input = match editor.readline(">>> ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                println!("failed to read: {error}");
+                break;
+            }
+        };
+        if input.trim() == "" {
+            continue;
+        }
+        let _ = editor.add_history_entry(input.as_str());
 
-        if i_said.trim() == "\\q" {
-            break;
+        let command = match Command::parse(&input) {
+            None => {
+                ensure_responded(&mut ci, &repl::message(&input)).await;
+                continue;
+            }
+            Some(Ok(command)) => command,
+            Some(Err(error)) => {
+                println!("{error}");
+                continue;
+            }
+        };
+        match command {
+            Command::Quit => break,
+            Command::Help => println!("{}", repl::HELP),
+            Command::New => match ci.new_converstation(false).await {
+                Ok(()) => info!("transcript {}", ci.transcript().id),
+                Err(error) => println!("failed to start a new conversation: {error}"),
+            },
+            Command::Retry => match ci.forget_last_exchange().await {
+                Ok(Some(said)) => ensure_responded(&mut ci, &said).await,
+                Ok(None) => println!("nothing said yet"),
+                Err(error) => println!("failed to forget: {error}"),
+            },
+            Command::Save(path) => {
+                let transcript = ci.transcript();
+                let text = match path.extension() {
+                    Some(ext) if ext == "md" => transcript.to_markdown(),
+                    _ => transcript.to_json(),
+                };
+                match std::fs::write(&path, text) {
+                    Ok(()) => println!("saved to {}", path.display()),
+                    Err(error) => println!("failed to save {}: {error}", path.display()),
+                }
+            }
+            Command::Load(path) => match std::fs::read_to_string(&path) {
+                Ok(prompt) => ensure_responded(&mut ci, &prompt).await,
+                Err(error) => println!("failed to load {}: {error}", path.display()),
+            },
+            Command::System(prompt) => match ci.set_system(&prompt).await {
+                Ok(true) => println!("instructions set"),
+                // the web backend has no such thing
+                Ok(false) => ensure_responded(&mut ci, &prompt).await,
+                Err(error) => println!("failed to set instructions: {error}"),
+            },
+            Command::History => {
+                for turn in &ci.transcript().turns {
+                    println!("[{:?}] {}", turn.role, turn.text.trim_end());
+                }
+            }
         }
-        if i_said.trim() == "" {
-            continue;
+    }
+
+    if let Some(history) = &history {
+        if let Err(error) = editor.save_history(history) {
+            debug!("save history: {error}");
         }
-        ensure_responded(&mut ci, &i_said).await;
     }
 
     println!("\nbye!");
@@ -36,16 +134,32 @@ async fn main() {
 
 async fn ensure_responded(ci: &mut Chati, isaid: &str) {
     loop {
-        // chati::util::pause_force().await;
         debug!("I SAID: {isaid}");
 
-        ci.isaid(isaid).await;
+        match ci.isaid(isaid).await {
+            Ok(()) => {}
+            Err(
+                error @ BackendError::Tired {
+                    retry_after: Some(retry_after),
+                },
+            ) => {
+                println!("{error}");
+                ratelimit::countdown(retry_after).await;
+                continue;
+            }
+            Err(error) => {
+                println!("failed to say: {error}");
+                break;
+            }
+        }
 
         debug!("HE SAID: ");
 
         let repeat = Arc::new(AtomicBool::new(false));
+        let mut highlighter = MarkdownHighlighter::new();
         ci.hesaid(|words| {
             let repeat = Arc::clone(&repeat);
+            let words = words.map(|words| highlighter.push(&words));
             async move {
                 match words {
                     Some(words) => {
@@ -62,7 +176,7 @@ async fn ensure_responded(ci: &mut Chati, isaid: &str) {
         .await;
 
         if !repeat.load(Ordering::Relaxed) {
-            println!();
+            println!("{}", highlighter.finish());
             std::io::stdout().flush().unwrap();
             break;
         }
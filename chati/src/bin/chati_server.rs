@@ -0,0 +1,40 @@
+use chati::chati::Chati;
+use chati::config::{BackendConfig, Config};
+use chati::server;
+use chati::transcript::TranscriptStore;
+
+use tracing::info;
+
+/// Usage: chati_server [<address>]
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    chati::trace::init(&config.log);
+    let listen = match std::env::args().skip(1).collect::<Vec<_>>().as_slice() {
+        [] => config.server.listen.clone(),
+        [listen] => listen.clone(),
+        _ => {
+            eprintln!("Usage: chati_server [<address>]");
+            std::process::exit(1);
+        }
+    };
+
+    // the clients are told about a tired or logged out page, instead of
+    // waiting for it
+    let mut backend = config.backend.clone();
+    if let BackendConfig::Web(web) = &mut backend {
+        web.fail_fast = true;
+    }
+    let mut ci = Chati::new(&backend).await?;
+    let transcripts = config
+        .transcripts
+        .clone()
+        .unwrap_or_else(|| TranscriptStore::DEFAULT_DIR.into());
+    ci.keep_transcripts(TranscriptStore::new(transcripts));
+    ci.new_converstation(true).await?;
+
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    info!("serve http://{listen}/v1/chat/completions");
+    axum::serve(listener, server::router(ci, &config.server)).await?;
+    Ok(())
+}
@@ -1,58 +1,104 @@
 use chati::comment_extractor::CommentExtractor;
-use chati::util::merge_comments;
+use chati::journal;
+use chati::merge::{match_translations, merge, translated_comment};
+use chati::util::MergeMode;
+use metacomment::cli::take_option;
+use metacomment::language;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
+    let mut args = std::env::args().collect::<Vec<String>>();
+    let lang = take_option(&mut args, "--lang");
+    let mode = match take_option(&mut args, "--mode") {
+        Some(name) => MergeMode::by_name(&name).unwrap_or_else(|| {
+            let known: Vec<_> = MergeMode::ALL.iter().map(|mode| mode.name()).collect();
+            eprintln!(
+                "unknown merge mode {name}, expect one of {}",
+                known.join(",")
+            );
+            std::process::exit(1);
+        }),
+        None => MergeMode::default(),
+    };
     if args.len() != 4 {
         eprintln!(
-            "Usage: {} <code_file> <comment_file> <merged_file>",
+            "Usage: {} [--lang <language>] [--mode <replace|above|below|adjacent>] <code_file> <translation_file> <merged_file>",
             args[0]
         );
         std::process::exit(1);
     }
 
     let code_filename = &args[1];
-    let comm_filename = &args[2];
+    let tran_filename = &args[2];
     let merg_filename = &args[3];
 
+    let language = language::detect(lang.as_deref(), code_filename).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+    let syntax = language.syntax();
+
     let code_file = File::open(code_filename).expect("open {code_filename}");
-    let comm_file = File::open(comm_filename).expect("open {comm_filename}");
+    let entries = journal::read(tran_filename).unwrap_or_else(|error| {
+        eprintln!("read {tran_filename}: {error}");
+        std::process::exit(1);
+    });
     let mut merg_file = std::fs::OpenOptions::new()
         .append(true)
         .create(true)
         .open(merg_filename)
         .expect("open {merg_filename}");
 
-    let mut ce1 = CommentExtractor::new(code_file);
-    let mut ce2 = CommentExtractor::new(comm_file);
+    let comments = CommentExtractor::with_language(code_file, language).collect::<Vec<_>>();
+    let matching = match_translations(comments, entries);
 
+    let mut merged_count = 0;
+    // comments kept as they are, with the reason
+    let mut skipped = Vec::new();
     let mut out_offset = 0;
-    loop {
-        let com1 = ce1.next();
-        if com1.is_none() {
-            break;
-        }
-        let com1 = com1.unwrap();
-        let com2 = ce2.next().expect("the same count comments");
-
-        let merged = merge_comments(&com2.content, &com1.content);
+    for (com, entry) in &matching.matched {
+        let Some(translated) = translated_comment(&entry.translated, language) else {
+            skipped.push((com.line, "no comment in the translation".to_string()));
+            continue;
+        };
+        let merged = match merge(&com.content, &translated, &syntax, mode) {
+            Ok(merged) => merged,
+            Err(error) => {
+                skipped.push((com.line, error.to_string()));
+                continue;
+            }
+        };
 
-        copy_rest(
-            out_offset,
-            com1.offset as u64,
-            code_filename,
-            &mut merg_file,
-        );
-        // com1.end is not inclusive
-        out_offset = com1.end as u64;
+        copy_rest(out_offset, com.offset as u64, code_filename, &mut merg_file);
+        // com.end is not inclusive
+        out_offset = com.end as u64;
 
         merg_file
             .write_all(merged.as_bytes())
             .expect("write to {merg_filename}");
+        merged_count += 1;
     }
     copy_rest(out_offset, u64::MAX, code_filename, &mut merg_file);
+
+    for (line, reason) in &skipped {
+        eprintln!("skipped comment at line {line}: {reason}");
+    }
+    for com in &matching.unmatched {
+        eprintln!("untranslated comment at line {}", com.line);
+    }
+    for entry in &matching.stale {
+        eprintln!(
+            "stale translation of offset {}, the comment is changed or gone",
+            entry.offset
+        );
+    }
+    println!(
+        "{merged_count} comments merged, {} skipped, {} untranslated, {} stale translations",
+        skipped.len(),
+        matching.unmatched.len(),
+        matching.stale.len()
+    );
 }
 
 /// read content from `infilename` at offset `start` until `end` and append the content to `outfile`
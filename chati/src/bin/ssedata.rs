@@ -1,17 +1,44 @@
+use futures::StreamExt;
 use std::io::Write;
 
+use chati::config::{BackendConfig, Config};
+use chati::intercept::{Interceptor, NetworkEvent, TargetSelector};
 use chati::openai;
-use chati::util;
+use chati::replay::Recorder;
 
+/// Usage: ssedata [--record <file>]
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    util::listen_webpage_stream_data(
-        9222,
-        "https://chatgpt.com/",
-        0,
-        "https://chatgpt.com/backend-anon/conversation",
-        |data| match data {
-            Some(data) => openai::assistant_sse(data, |stream_msg, ended| {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut recorder = match args.as_slice() {
+        [] => None,
+        [flag, path] if flag == "--record" => Some(Recorder::create(path)?),
+        _ => {
+            eprintln!("Usage: ssedata [--record <file>]");
+            std::process::exit(1);
+        }
+    };
+
+    // the port of the web backend, when chati.toml has one
+    let debugging_port = match Config::load()?.backend {
+        BackendConfig::Web(web) => web.browser.debugging_port,
+        _ => 9222,
+    };
+    let mut decoder = openai::AssistantDecoder::new();
+    let mut events = Interceptor::new(debugging_port)
+        .target(TargetSelector::Url("https://chatgpt.com/".to_string()))
+        .stream(|url| url == openai::CONVERSATION_URL)
+        .watch(|url| url == openai::SENTINEL_URL)
+        .listen();
+    while let Some(event) = events.next().await {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(&event)?;
+        }
+        match event {
+            NetworkEvent::Response { status, .. } if status / 100 != 2 => {
+                println!("nothing said");
+            }
+            NetworkEvent::Data { data, .. } => decoder.feed(&data, |_, stream_msg, ended| {
                 print!("{}", stream_msg);
                 if let Err(e) = std::io::stdout().flush() {
                     eprintln!("Failed to flush stdout: {}", e);
@@ -20,11 +47,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!();
                 }
             }),
-            None => {
-                println!("nothing said");
-            }
-        },
-    )
-    .await?;
+            _ => {}
+        }
+    }
     Ok(())
 }
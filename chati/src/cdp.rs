@@ -0,0 +1,577 @@
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+use tracing::{debug, error};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CdpError {
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("websocket: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("no target matches {0}")]
+    NoTarget(String),
+    #[error("{method}: {message} ({code})")]
+    Protocol {
+        method: &'static str,
+        code: i64,
+        message: String,
+    },
+    #[error("the connection is closed")]
+    Closed,
+}
+
+/// A page, worker or the like listed by `http://localhost:<port>/json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetInfo {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub url: String,
+    /// missing when some other client is attached already
+    pub web_socket_debugger_url: Option<String>,
+}
+
+/// the targets of the browser listening on the remote debugging `port`
+pub async fn targets(port: u16) -> Result<Vec<TargetInfo>, CdpError> {
+    let url = format!("http://localhost:{port}/json");
+    Ok(reqwest::get(url).await?.json().await?)
+}
+
+/// A method of the protocol with its parameters
+pub trait Command: Serialize {
+    const METHOD: &'static str;
+    type Response: DeserializeOwned;
+}
+
+/// A response without anything in it
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Empty {}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Network/#method-enable
+#[derive(Serialize, Debug, Default)]
+pub struct NetworkEnable {}
+
+impl Command for NetworkEnable {
+    const METHOD: &'static str = "Network.enable";
+    type Response = Empty;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Network/#method-streamResourceContent
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamResourceContent {
+    pub request_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamResourceContentResponse {
+    /// base64 of what is received before streaming is enabled
+    pub buffered_data: String,
+}
+
+impl Command for StreamResourceContent {
+    const METHOD: &'static str = "Network.streamResourceContent";
+    type Response = StreamResourceContentResponse;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Runtime/#method-enable
+#[derive(Serialize, Debug, Default)]
+pub struct RuntimeEnable {}
+
+impl Command for RuntimeEnable {
+    const METHOD: &'static str = "Runtime.enable";
+    type Response = Empty;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Page/#method-enable
+#[derive(Serialize, Debug, Default)]
+pub struct PageEnable {}
+
+impl Command for PageEnable {
+    const METHOD: &'static str = "Page.enable";
+    type Response = Empty;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Fetch/#method-enable
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchEnable {
+    /// pause the requests matching one of them
+    pub patterns: Vec<RequestPattern>,
+    /// tell [`Event::AuthRequired`] rather than ask the user
+    pub handle_auth_requests: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPattern {
+    /// wildcards `*` and `?` are allowed
+    pub url_pattern: String,
+}
+
+impl Command for FetchEnable {
+    const METHOD: &'static str = "Fetch.enable";
+    type Response = Empty;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Fetch/#method-continueRequest
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchContinueRequest {
+    pub request_id: String,
+}
+
+impl Command for FetchContinueRequest {
+    const METHOD: &'static str = "Fetch.continueRequest";
+    type Response = Empty;
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Fetch/#method-continueWithAuth
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchContinueWithAuth {
+    pub request_id: String,
+    pub auth_challenge_response: AuthChallengeResponse,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AuthChallengeResponse {
+    /// `Default`, `CancelAuth` or `ProvideCredentials`
+    pub response: &'static str,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Command for FetchContinueWithAuth {
+    const METHOD: &'static str = "Fetch.continueWithAuth";
+    type Response = Empty;
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub url: String,
+    pub status: u16,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Network/#event-responseReceived
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseReceived {
+    pub request_id: String,
+    pub response: Response,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Network/#event-dataReceived
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataReceived {
+    pub request_id: String,
+    pub data_length: u64,
+    /// base64, only when the content of the request is streamed
+    pub data: Option<String>,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Runtime/#event-consoleAPICalled
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsoleApiCalled {
+    /// `log`, `error` and so on
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub args: Vec<Value>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    pub id: String,
+    /// none for the main frame of the tab
+    pub parent_id: Option<String>,
+    pub url: String,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Page/#event-frameNavigated
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FrameNavigated {
+    pub frame: Frame,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Fetch/#event-requestPaused
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPaused {
+    /// not the one of the Network domain
+    pub request_id: String,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Fetch/#event-authRequired
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthRequired {
+    pub request_id: String,
+}
+
+/// https://chromedevtools.github.io/devtools-protocol/tot/Inspector/#event-detached
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Detached {
+    /// such as `target_closed` or `replaced_with_devtools`
+    pub reason: String,
+}
+
+/// Something the browser tells without being asked
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    ResponseReceived(ResponseReceived),
+    DataReceived(DataReceived),
+    ConsoleApiCalled(ConsoleApiCalled),
+    FrameNavigated(FrameNavigated),
+    RequestPaused(RequestPaused),
+    AuthRequired(AuthRequired),
+    Detached(Detached),
+    /// an event without a type here, or with params of an unexpected shape
+    Other {
+        method: String,
+        params: Value,
+    },
+}
+
+impl Event {
+    fn parse(method: &str, params: Value) -> Event {
+        fn typed<T: DeserializeOwned>(params: &Value, wrap: fn(T) -> Event) -> Option<Event> {
+            serde_json::from_value(params.clone()).ok().map(wrap)
+        }
+        let event = match method {
+            "Network.responseReceived" => typed(&params, Event::ResponseReceived),
+            "Network.dataReceived" => typed(&params, Event::DataReceived),
+            "Runtime.consoleAPICalled" => typed(&params, Event::ConsoleApiCalled),
+            "Page.frameNavigated" => typed(&params, Event::FrameNavigated),
+            "Fetch.requestPaused" => typed(&params, Event::RequestPaused),
+            "Fetch.authRequired" => typed(&params, Event::AuthRequired),
+            "Inspector.detached" => typed(&params, Event::Detached),
+            _ => None,
+        };
+        event.unwrap_or_else(|| Event::Other {
+            method: method.to_string(),
+            params,
+        })
+    }
+}
+
+type Pending = HashMap<u64, oneshot::Sender<Result<Value, (i64, String)>>>;
+
+/// The shared part of a client and its reading task
+#[derive(Default)]
+struct Shared {
+    pending: Pending,
+    subscribers: Vec<mpsc::UnboundedSender<Event>>,
+    closed: bool,
+}
+
+/// A connection to one target speaking the Chrome DevTools Protocol.
+///
+/// Commands are correlated to their responses by id, so any number of them
+/// may be in flight. Events go to every subscriber. Dropping the client
+/// closes the connection.
+pub struct CdpClient {
+    next_id: AtomicU64,
+    outgoing: mpsc::UnboundedSender<Message>,
+    shared: Arc<Mutex<Shared>>,
+    reader: JoinHandle<()>,
+}
+
+impl CdpClient {
+    /// connect to the websocket debugger url of a target
+    pub async fn connect(url: &str) -> Result<Self, CdpError> {
+        debug!("connect to cdp target {url}");
+        let (ws_stream, _) = connect_async(url).await?;
+        let (mut sink, mut stream) = ws_stream.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                if let Err(error) = sink.send(message).await {
+                    error!("send to cdp target: {error}");
+                    break;
+                }
+            }
+        });
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let reader_shared = Arc::clone(&shared);
+        let reader = tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                match message {
+                    Ok(Message::Text(text)) => dispatch(&reader_shared, &text),
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => {}
+                    Err(error) => {
+                        error!("receive from cdp target: {error}");
+                        break;
+                    }
+                }
+            }
+            // fail what waits for a response and end the event streams
+            let mut shared = reader_shared.lock().unwrap();
+            shared.closed = true;
+            shared.pending.clear();
+            shared.subscribers.clear();
+        });
+
+        Ok(CdpClient {
+            next_id: AtomicU64::new(1),
+            outgoing,
+            shared,
+            reader,
+        })
+    }
+
+    /// connect to the first target of the browser on `port` which `pred` accepts
+    pub async fn connect_target(
+        port: u16,
+        pred: impl Fn(&TargetInfo) -> bool,
+    ) -> Result<Self, CdpError> {
+        let url = targets(port)
+            .await?
+            .into_iter()
+            .filter(|target| pred(target))
+            .find_map(|target| target.web_socket_debugger_url)
+            .ok_or_else(|| CdpError::NoTarget(format!("on port {port}")))?;
+        Self::connect(&url).await
+    }
+
+    /// send `command` and wait for its response
+    pub async fn send<C: Command>(&self, command: &C) -> Result<C::Response, CdpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.closed {
+                return Err(CdpError::Closed);
+            }
+            shared.pending.insert(id, tx);
+        }
+        let message = json!({
+            "id": id,
+            "method": C::METHOD,
+            "params": command,
+        });
+        self.outgoing
+            .send(Message::Text(message.to_string()))
+            .map_err(|_| CdpError::Closed)?;
+        match rx.await.map_err(|_| CdpError::Closed)? {
+            Ok(result) => Ok(serde_json::from_value(result)?),
+            Err((code, message)) => Err(CdpError::Protocol {
+                method: C::METHOD,
+                code,
+                message,
+            }),
+        }
+    }
+
+    /// Every event received from now on. The stream ends with the connection.
+    pub fn events(&self) -> mpsc::UnboundedReceiver<Event> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.closed {
+            shared.subscribers.push(tx);
+        }
+        rx
+    }
+}
+
+impl Drop for CdpClient {
+    fn drop(&mut self) {
+        // the writer sends it and ends with the channel, the reader would
+        // keep its half open until the browser hangs up
+        let _ = self.outgoing.send(Message::Close(None));
+        self.reader.abort();
+    }
+}
+
+/// hand a message of the target to whom it is for
+fn dispatch(shared: &Mutex<Shared>, text: &str) {
+    let message = match serde_json::from_str::<Value>(text) {
+        Ok(message) => message,
+        Err(error) => {
+            error!("not json from cdp target: {text}: {error}");
+            return;
+        }
+    };
+    let mut shared = shared.lock().unwrap();
+    if let Some(id) = message["id"].as_u64() {
+        let Some(tx) = shared.pending.remove(&id) else {
+            debug!("response to nobody: {text}");
+            return;
+        };
+        let result = match message.get("error") {
+            Some(error) => Err((
+                error["code"].as_i64().unwrap_or_default(),
+                error["message"].as_str().unwrap_or_default().to_string(),
+            )),
+            None => Ok(message.get("result").cloned().unwrap_or(json!({}))),
+        };
+        let _ = tx.send(result);
+    } else if let Some(method) = message["method"].as_str() {
+        let event = Event::parse(method, message["params"].clone());
+        shared
+            .subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// A websocket server answering every command with `respond`, then
+    /// telling the events of a session after the first command. A session
+    /// is one connection, which is hung up on at a `"close"` in its events.
+    pub(crate) async fn cdp_server(
+        respond: fn(&str, &Value) -> Value,
+        sessions: Vec<Vec<Value>>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for events in sessions {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+                let mut events = Some(events);
+                'session: while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let command: Value = serde_json::from_str(&text).unwrap();
+                    let method = command["method"].as_str().unwrap();
+                    let mut response = respond(method, &command["params"]);
+                    response["id"] = command["id"].clone();
+                    ws.send(Message::Text(response.to_string())).await.unwrap();
+                    for event in events.take().unwrap_or_default() {
+                        if event == "close" {
+                            ws.close(None).await.unwrap();
+                            break 'session;
+                        }
+                        ws.send(Message::Text(event.to_string())).await.unwrap();
+                    }
+                }
+            }
+        });
+        format!("ws://{addr}/devtools/page/1")
+    }
+
+    pub(crate) fn respond(method: &str, params: &Value) -> Value {
+        match method {
+            "Network.enable" | "Runtime.enable" | "Page.enable" => json!({"result": {}}),
+            "Network.streamResourceContent" if params["requestId"] == "r1" => {
+                json!({"result": {"bufferedData": "aGk="}})
+            }
+            _ => json!({"error": {"code": -32000, "message": "No resource with given id"}}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_and_events() {
+        let events = vec![
+            json!({"method": "Network.responseReceived", "params": {
+                "requestId": "r1", "response": {"url": "https://chatgpt.com/", "status": 200}}}),
+            json!({"method": "Network.dataReceived", "params": {
+                "requestId": "r1", "dataLength": 2, "data": "aGk="}}),
+            json!({"method": "Runtime.consoleAPICalled", "params": {
+                "type": "log", "args": [{"type": "string", "value": "hello"}]}}),
+            json!({"method": "Page.frameNavigated", "params": {
+                "frame": {"id": "f1", "url": "https://chatgpt.com/"}}}),
+            json!({"method": "Page.loadEventFired", "params": {"timestamp": 1.5}}),
+        ];
+        let url = cdp_server(respond, vec![events]).await;
+        let client = CdpClient::connect(&url).await.unwrap();
+        let mut events = client.events();
+
+        client.send(&NetworkEnable {}).await.unwrap();
+        let response = client
+            .send(&StreamResourceContent {
+                request_id: "r1".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.buffered_data, "aGk=");
+        let error = client
+            .send(&StreamResourceContent {
+                request_id: "r2".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CdpError::Protocol { code: -32000, .. }));
+
+        let mut received = Vec::new();
+        for _ in 0..5 {
+            received.push(events.recv().await.unwrap());
+        }
+        assert!(
+            matches!(&received[0], Event::ResponseReceived(event) if event.response.status == 200)
+        );
+        assert!(
+            matches!(&received[1], Event::DataReceived(event) if event.data.as_deref() == Some("aGk="))
+        );
+        assert!(matches!(&received[2], Event::ConsoleApiCalled(event) if event.kind == "log"));
+        assert!(
+            matches!(&received[3], Event::FrameNavigated(event) if event.frame.parent_id.is_none())
+        );
+        assert!(
+            matches!(&received[4], Event::Other { method, .. } if method == "Page.loadEventFired")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            // hang up on the first command
+            let _ = ws.next().await;
+            ws.close(None).await.unwrap();
+        });
+        let client = CdpClient::connect(&format!("ws://{addr}/")).await.unwrap();
+        let mut events = client.events();
+        let error = client.send(&RuntimeEnable {}).await.unwrap_err();
+        assert!(matches!(error, CdpError::Closed));
+        assert!(events.recv().await.is_none());
+        assert!(matches!(
+            client.send(&RuntimeEnable {}).await,
+            Err(CdpError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            ws.next().await
+        });
+        let client = CdpClient::connect(&format!("ws://{addr}/")).await.unwrap();
+        let mut events = client.events();
+        drop(client);
+        assert!(matches!(
+            server.await.unwrap(),
+            Some(Ok(Message::Close(_))) | None
+        ));
+        assert!(events.recv().await.is_none());
+    }
+}
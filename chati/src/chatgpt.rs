@@ -1,158 +1,202 @@
 #![allow(dead_code)]
 
+use async_trait::async_trait;
 use fantoccini::actions::{InputSource, MouseActions, PointerAction, MOUSE_BUTTON_LEFT};
+use fantoccini::error::NewSessionError;
 use fantoccini::{client::*, elements::*, ClientBuilder, Locator};
-use serde_json::json;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio;
-use log::{debug, info, error};
-
-use crate::util;
-
-enum WebState {
-    ChatReady,
-    LoggingIn,
-    LoginTip,
-    Tired,
-    NeedReopen,
-    /// send button is spinning
-    MsgSending,
-    Talking,
-}
+use tracing::{debug, error, info};
+
+use crate::browser::{self, BrowserConfig};
+use crate::page::{PageDriver, PageError, PageMachine, PageState};
+use crate::ratelimit;
+use crate::trace::StepDebugger;
+
+pub use crate::page::Unavailable;
+
+/// the answers of the conversation, the limit notices among them
+const ASSISTANT_MESSAGES: &str = "div[data-message-author-role=\"assistant\"]";
 
 pub struct ChatGPT {
     client: Client,
-    /// when the page is stucking in sending user message, we need remember how long have we waited
-    /// for. If the wait time exceeds a limit, we need refresh page.
-    sending_sleep: u64,
-    // /// When we refresh the page, we need send these initial prompts to tell chatgpt what we want
-    // /// do. Responses(or assistant messages) will be discarded.
-    // initial_prompts: Vec<String>,
+    browser: BrowserConfig,
+    /// logs in to the proxy of `browser`
+    proxy_auth: Option<tokio::task::JoinHandle<()>>,
+    /// give up rather than wait when the page is tired or logged out
+    fail_fast: bool,
+    /// keeps the page at every step
+    steps: Option<Arc<StepDebugger>>,
+    /// the webdriver session or its window is gone, start another one
+    lost: AtomicBool,
+    /// see [`PageMachine::answered`]
+    answered: Option<usize>,
 }
 
 impl ChatGPT {
-    pub async fn new(/* initial_prompts: Vec<String>*/) -> Self {
-        let mut proxy_server = std::env::var("http_proxy").unwrap_or("".to_string());
-        let mut args = json!({
-            "args": [
-                // "--headless",
-                // "--disable-gpu",
-                "--no-sandbox",
-                "--disable-dev-shm-usage",
-                "--disable-blink-features=AutomationControlled",
-                "--disable-features=InterestCohort",
-                "--disable-features=BrowsingTopics",
-                // start a remote port for CDP protocol
-                "--remote-debugging-port=9222",
-                "user-agent=Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36",
-                "disable-infobars",
-            ],
-            "excludeSwitches": ["enable-automation"]
-        });
-        if !proxy_server.is_empty() {
-            if proxy_server.starts_with("http://") {
-                proxy_server = proxy_server[7..].to_string();
-            }
-            args["args"].as_array_mut().unwrap().push(json! {
-                format!("--proxy-server={}", proxy_server)
-            });
-        }
+    /// start a browser through the chromedriver of `browser`, which has to
+    /// be running already
+    pub async fn new(browser: &BrowserConfig) -> Result<Self, NewSessionError> {
+        let args = browser.chrome_options();
         info!("args: {:#?}", args);
         // Define the Chrome capabilities
         let mut caps = serde_json::map::Map::new();
         caps.insert("goog:chromeOptions".to_string(), args);
 
-        // start chromedriver at port 9515 before launching this program
         let client = ClientBuilder::native()
             .capabilities(caps)
-            .connect("http://localhost:9515")
-            .await
-            .expect("connect to http://localhost:9515");
+            .connect(&browser.driver_url)
+            .await?;
 
-        ChatGPT {
+        let proxy_auth = match browser.proxy() {
+            Ok(Some(proxy)) if proxy.needs_auth() => Some(tokio::spawn(
+                browser::authenticate_proxy(browser.debugging_port, proxy),
+            )),
+            _ => None,
+        };
+
+        Ok(ChatGPT {
             client,
-            sending_sleep: 0,
-            // initial_prompts,
-        }
+            browser: browser.clone(),
+            proxy_auth,
+            fail_fast: false,
+            steps: None,
+            lost: AtomicBool::new(false),
+            answered: None,
+        })
     }
 
-    pub async fn new_session(&mut self, session_opened: Arc<AtomicBool>) {
-        if let Err(error) = self.client.goto("https://chatgpt.com/").await {
-            panic!("go to https://chatgpt.com: {error:#?}");
-        }
-        session_opened.store(true, Ordering::Release);
-        loop {
-            debug!("try to login in...");
-            if self.get_chatbox(5).await.is_none() && self.open_chatbox().await.is_none() {
-                continue;
-            }
-            break;
-        }
+    /// Close what is left of the browser and start another one. The new one
+    /// has to log in again, unless its profile keeps the login.
+    pub async fn restart(&mut self) -> Result<(), NewSessionError> {
+        let browser = self.browser.clone();
+        self.restart_with(&browser).await
     }
 
-    pub async fn wait_for_chatbox(&self, session_opened: Arc<AtomicBool>) {
-        if let Err(error) = self.client.goto("https://chatgpt.com/").await {
-            panic!("go to https://chatgpt.com: {error:#?}");
+    /// start another browser launched from `browser`, such as another
+    /// profile when this one is rate limited
+    pub async fn restart_with(&mut self, browser: &BrowserConfig) -> Result<(), NewSessionError> {
+        if let Some(proxy_auth) = self.proxy_auth.take() {
+            proxy_auth.abort();
         }
-        session_opened.store(true, Ordering::Release);
-        loop {
-            if self.get_chatbox(1).await.is_some() {
-                break;
-            }
-            debug!("waiting for chatbox available...");
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        if let Err(error) = self.client.clone().close().await {
+            debug!("close the lost session: {error:#?}");
         }
+        let (fail_fast, steps) = (self.fail_fast, self.steps.take());
+        *self = ChatGPT::new(browser).await?;
+        self.fail_fast = fail_fast;
+        self.steps = steps;
+        Ok(())
     }
 
-    #[allow(unreachable_code)]
-    pub async fn send_my_said(&mut self, said: &str) {
-        loop {
-            debug!("1. get_chatbox");
-            let mut chatbox = self.get_chatbox(5).await;
-            if chatbox.is_none() {
-                // util::pause();
-                debug!("2. open_chatbox");
-                chatbox = self.open_chatbox().await;
-                if chatbox.is_none() {
-                    continue;
-                }
-            }
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
 
-            while WebState::is_talking(&self.client).await {
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            }
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    /// what the page told when it became [`Unavailable::Tired`]
+    pub async fn limit_message(&self) -> Option<String> {
+        limit_message(&self.client).await
+    }
 
-            // wait until user the message could be sent to openai
-            #[allow(unused_assignments)]
-            let mut msg_sent = false;
-            // `set_user_msg` and `send_user_msg` bost must be in the same loop
-            loop {
-                debug!("ready to set user message immediately...");
-                let _ = util::pause().await;
-                self.set_user_msg(said).await;
-
-                debug!("4. send_user_msg");
-                if self.send_user_msg().await {
-                    debug!("5. message sent..");
-                    msg_sent = true;
-                    break;
-                } else {
-                    debug!("6. open_chatbox");
-                    let _ = self.open_chatbox().await;
-                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                }
+    /// Return [`Unavailable::LoggedOut`] instead of waiting for somebody to
+    /// log in.
+    pub fn fail_fast(&mut self, fail_fast: bool) {
+        self.fail_fast = fail_fast;
+    }
+
+    /// keep a screenshot and the DOM of the page at every step
+    pub fn step_debug(&mut self, steps: Option<Arc<StepDebugger>>) {
+        self.steps = steps;
+    }
+
+    async fn keep_step(&self, name: &str) {
+        let Some(steps) = &self.steps else {
+            return;
+        };
+        let screenshot = match self.client.screenshot().await {
+            Ok(screenshot) => Some(screenshot),
+            Err(error) => {
+                debug!("screenshot of {name}: {error}");
+                None
             }
-            if msg_sent {
-                break;
+        };
+        let dom = match self.client.source().await {
+            Ok(dom) => Some(dom),
+            Err(error) => {
+                debug!("source of {name}: {error}");
+                None
             }
-
-            // take a break
-            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        };
+        if let Err(error) = steps
+            .step(name, screenshot.as_deref(), dom.as_deref())
+            .await
+        {
+            error!("keep step {name} in {}: {error}", steps.dir().display());
         }
     }
 
+    pub async fn new_session(
+        &mut self,
+        session_opened: Arc<AtomicBool>,
+    ) -> Result<(), Unavailable> {
+        self.goto("https://chatgpt.com/").await?;
+        session_opened.store(true, Ordering::Release);
+        debug!("try to login in...");
+        PageMachine::new(self.fail_fast).open(self).await
+    }
+
+    pub async fn wait_for_chatbox(
+        &mut self,
+        session_opened: Arc<AtomicBool>,
+    ) -> Result<(), Unavailable> {
+        self.wait_for_chatbox_at("https://chatgpt.com/", session_opened)
+            .await
+    }
+
+    /// go back to the conversation `id` and wait for its chatbox
+    pub async fn open_conversation(
+        &mut self,
+        id: &str,
+        session_opened: Arc<AtomicBool>,
+    ) -> Result<(), Unavailable> {
+        let url = format!("https://chatgpt.com/c/{id}");
+        self.wait_for_chatbox_at(&url, session_opened).await
+    }
+
+    /// somebody at the browser logs in when it is needed
+    async fn wait_for_chatbox_at(
+        &mut self,
+        url: &str,
+        session_opened: Arc<AtomicBool>,
+    ) -> Result<(), Unavailable> {
+        self.goto(url).await?;
+        session_opened.store(true, Ordering::Release);
+        debug!("waiting for chatbox available...");
+        PageMachine::new(self.fail_fast)
+            .manual_login()
+            .open(self)
+            .await
+    }
+
+    /// the browser can not go anywhere when it is lost or not started
+    async fn goto(&mut self, url: &str) -> Result<(), Unavailable> {
+        // the answers are counted again on another page
+        self.answered = None;
+        self.client.goto(url).await.map_err(|error| {
+            error!("go to {url}: {error:#?}");
+            self.lost.store(true, Ordering::Release);
+            Unavailable::Lost
+        })
+    }
+
+    pub async fn send_my_said(&mut self, said: &str) -> Result<(), Unavailable> {
+        let mut machine = PageMachine::new(self.fail_fast).with_answered(self.answered);
+        let sent = machine.send(self, said).await;
+        self.answered = machine.answered();
+        sent
+    }
+
     async fn set_user_msg(&self, msg: &str) {
         let msg = msg.replace("\n", "\\n");
         let msg = msg.replace("\t", "\\t");
@@ -177,7 +221,6 @@ impl ChatGPT {
         if let Some(chatbox) = self.get_chatbox(1).await {
             if let Err(error) = chatbox.send_keys(" ").await {
                 error!("send_keys: {error:#?}");
-                // util::pause_force().await;
                 return false;
             }
         }
@@ -187,12 +230,11 @@ impl ChatGPT {
             Some(send_btn) => send_btn,
             None => return false,
         };
-        if !send_btn.is_enabled().await.unwrap() {
+        if !send_btn.is_enabled().await.unwrap_or(false) {
             return false;
         }
 
-        // println!("Are yre ready to click?");
-        let _ = util::pause().await;
+        self.keep_step("click-send").await;
 
         let mouse_click = MouseActions::new("click send button".to_string())
             .then(PointerAction::MoveToElement {
@@ -215,66 +257,6 @@ impl ChatGPT {
         }
     }
 
-    // TODO: refreshing a page means we need do more things.
-    // Such as,
-    //  * resend initial command prompts
-    async fn restart_session(&mut self, doit: bool) {
-        if doit {
-            if let Err(error) = self.client.refresh().await {
-                panic!("refresh page: {error:#?}");
-            }
-        }
-    }
-
-    async fn open_chatbox(&mut self) -> Option<Element> {
-        let webstate = WebState::get(&self.client).await;
-        match webstate {
-            WebState::MsgSending => {
-                debug!("1.6 msg sending...");
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                self.sending_sleep += 1;
-                if self.sending_sleep > 10 {
-                    self.sending_sleep = 0;
-                    self.restart_session(false).await;
-                }
-            }
-            _ => {
-                self.sending_sleep = 0;
-            }
-        }
-        match webstate {
-            WebState::LoggingIn => {
-                debug!("1.1 logging in...");
-                self.bypass_cloudfare().await.ok()
-            }
-            WebState::LoginTip => {
-                debug!("1.2 logging tip...");
-                let _ = WebState::close_login_tip(&self.client).await;
-                self.get_chatbox(1).await
-            }
-            WebState::Tired => {
-                debug!("1.3 tired, sleep 10 minutes...");
-                tokio::time::sleep(std::time::Duration::from_secs(600)).await;
-                self.get_chatbox(1).await
-            }
-            WebState::NeedReopen => {
-                debug!("1.4 reopen...");
-                let _ = WebState::reopen_chatbox(&self.client).await;
-                let cb = self.get_chatbox(1).await;
-                if cb.is_none() {
-                    self.restart_session(true).await;
-                }
-                cb
-            }
-            WebState::ChatReady => {
-                debug!("1.5 chat ready...");
-                self.get_chatbox(1).await
-            }
-            WebState::Talking => self.get_chatbox(1).await,
-            _ => None,
-        }
-    }
-
     async fn resort_to_rescue_page(&self) -> Result<(), fantoccini::error::CmdError> {
         let client = &self.client;
 
@@ -325,7 +307,7 @@ impl ChatGPT {
         loop {
             // find a parent element with an id
             let checkbox = checkbox.find(Locator::XPath(&path)).await?;
-            if !checkbox.attr("id").await?.is_none() {
+            if checkbox.attr("id").await?.is_some() {
                 button = checkbox;
                 break;
             }
@@ -347,18 +329,14 @@ impl ChatGPT {
             error!("get #prompt-textarea: {:#?}", error);
             use fantoccini::error::CmdError;
             match error {
-                CmdError::Lost(_) => {
-                    std::process::exit(0);
-                }
-                CmdError::NoSuchWindow(_) => {
-                    std::process::exit(0);
+                CmdError::Lost(_) | CmdError::NoSuchWindow(_) => {
+                    self.lost.store(true, Ordering::Release);
                 }
                 _ => {}
             }
-            // util::pause_force().await;
             return None;
         }
-        return elm.ok();
+        elm.ok()
     }
 
     // Idea from https://github.com/ultrafunkamsterdam/undetected-chromedriver/issues/73#issuecomment-748487642
@@ -414,9 +392,100 @@ impl ChatGPT {
         Ok(self.get_chatbox(1).await.unwrap())
     }
 
-    pub async fn close(self) -> Result<(), fantoccini::error::CmdError> {
+    pub async fn close(&mut self) -> Result<(), fantoccini::error::CmdError> {
+        if let Some(proxy_auth) = self.proxy_auth.take() {
+            proxy_auth.abort();
+        }
         // Close the browser
-        self.client.close().await
+        self.client.clone().close().await
+    }
+}
+
+/// Dropped without [`ChatGPT::close`], as by a panic, the browser closes
+/// with the last handle of the webdriver session.
+impl Drop for ChatGPT {
+    fn drop(&mut self) {
+        if let Some(proxy_auth) = self.proxy_auth.take() {
+            proxy_auth.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl PageDriver for ChatGPT {
+    async fn state(&mut self) -> Result<PageState, PageError> {
+        if self.is_lost() {
+            return Err(PageError::Lost);
+        }
+        if let Err(error) = self.client.window().await {
+            let error = PageError::from(error);
+            if matches!(error, PageError::Lost) {
+                self.lost.store(true, Ordering::Release);
+            }
+            return Err(error);
+        }
+        let client = &self.client;
+        let state = if is_logging_in(client).await {
+            PageState::LoggingIn
+        } else if is_login_tip(client).await {
+            PageState::LoginTip
+        } else if is_tired(client).await {
+            PageState::Tired
+        } else if need_reopen(client).await {
+            PageState::NeedReopen
+        } else if is_msg_sending(client).await {
+            PageState::Sending
+        } else if is_talking(client).await {
+            PageState::Talking
+        } else {
+            PageState::Ready
+        };
+        Ok(state)
+    }
+
+    async fn close_login_tip(&mut self) -> Result<(), PageError> {
+        Ok(close_login_tip(&self.client).await?)
+    }
+
+    async fn log_in(&mut self) -> Result<(), PageError> {
+        self.bypass_cloudfare().await?;
+        Ok(())
+    }
+
+    async fn regenerate(&mut self) -> Result<(), PageError> {
+        Ok(reopen_chatbox(&self.client).await?)
+    }
+
+    // What the conversation was told before is still there after a refresh.
+    // A browser started again is told the initial prompts by the supervisor.
+    async fn refresh(&mut self) -> Result<(), PageError> {
+        Ok(self.client.refresh().await?)
+    }
+
+    async fn type_message(&mut self, said: &str) -> Result<(), PageError> {
+        self.keep_step("type-message").await;
+        self.set_user_msg(said).await;
+        Ok(())
+    }
+
+    async fn step(&mut self, name: &str) {
+        self.keep_step(name).await;
+    }
+
+    async fn click_send(&mut self) -> Result<bool, PageError> {
+        let sent = self.send_user_msg().await;
+        if !sent && self.is_lost() {
+            return Err(PageError::Lost);
+        }
+        Ok(sent)
+    }
+
+    async fn answers(&mut self) -> Result<usize, PageError> {
+        let answers = self
+            .client
+            .find_all(Locator::Css(ASSISTANT_MESSAGES))
+            .await?;
+        Ok(answers.len())
     }
 }
 
@@ -429,7 +498,7 @@ async fn get_send_btn(client: &Client) -> Option<Element> {
 
     if btn.is_err() {
         let btns = client.find_all(Locator::Css("button[data-testid]")).await;
-        if let Err(_) = btns {
+        if btns.is_err() {
             error!("could not get any button[data-testid]...sleep a while...");
             return None;
         }
@@ -451,117 +520,87 @@ async fn get_send_btn(client: &Client) -> Option<Element> {
         }
         btn = Ok(btns.remove(btns.len() - 1 - index));
     }
-    return Some(btn.unwrap());
+    Some(btn.unwrap())
 }
 
-impl WebState {
-    async fn get(client: &Client) -> Self {
-        if WebState::is_logging_in(client).await {
-            WebState::LoggingIn
-        } else if WebState::is_login_tip(client).await {
-            WebState::LoginTip
-        } else if WebState::is_tired(client).await {
-            WebState::Tired
-        } else if WebState::need_reopen(client).await {
-            WebState::NeedReopen
-        } else if WebState::is_msg_sending(client).await {
-            WebState::MsgSending
-        } else {
-            WebState::ChatReady
+async fn is_msg_sending(client: &Client) -> bool {
+    let btn = get_send_btn(client).await;
+    match btn {
+        Some(btn) => {
+            !btn.is_enabled().await.unwrap_or(false)
+                && btn.find(Locator::Css("svg.animate-spin")).await.is_ok()
         }
+        None => false,
     }
+}
 
-    async fn is_msg_sending(client: &Client) -> bool {
-        let btn = get_send_btn(client).await;
-        match btn {
-            Some(btn) => {
-                !btn.is_enabled().await.unwrap()
-                    && btn.find(Locator::Css("svg.animate-spin")).await.is_ok()
-            }
-            None => false,
-        }
-    }
+async fn is_login_tip(client: &Client) -> bool {
+    client
+        .find(Locator::Css("div[role=\"dialog\"]"))
+        .await
+        .is_ok()
+}
 
-    async fn is_login_tip(client: &Client) -> bool {
-        client
-            .find(Locator::Css("div[role=\"dialog\"]"))
-            .await
-            .is_ok()
-    }
+async fn close_login_tip(client: &Client) -> Result<(), fantoccini::error::CmdError> {
+    let dialog = client.find(Locator::Css("div[role=\"dialog\"]")).await?;
+    // 保持注销状态
+    let link = dialog.find(Locator::Css("div > div > a")).await?;
+    link.click().await
+}
 
-    async fn close_login_tip(client: &Client) -> Result<(), fantoccini::error::CmdError> {
-        let dialog = client
-            .find(Locator::Css("div[role=\"dialog\"]"))
-            .await
-            .expect("not in LoginTip state");
-        let link = dialog
-            .find(Locator::Css("div > div > a"))
-            .await
-            .expect("保持注销状态");
-        link.click().await
-    }
+async fn is_tired(client: &Client) -> bool {
+    limit_message(client).await.is_some()
+}
 
-    async fn is_tired(client: &Client) -> bool {
-        match WebState::last_assistant_message(&client).await {
-            Ok(opts) => {
-                if opts.is_none() {
-                    return false;
-                }
-                opts.unwrap()
-                    == "You've reached our limit of messages per hour. Please try again later."
-            }
-            Err(_) => false,
-        }
+/// the last answer when it tells the limit of messages is reached
+async fn limit_message(client: &Client) -> Option<String> {
+    match last_assistant_message(client).await {
+        Ok(Some(message)) if ratelimit::is_limit_message(&message) => Some(message),
+        _ => None,
     }
+}
 
-    async fn last_assistant_message(
-        client: &Client,
-    ) -> Result<Option<String>, fantoccini::error::CmdError> {
-        match client
-            .find_all(Locator::Css("div[data-message-author-role=\"assistant\"]"))
-            .await?
-            .pop()
-            .map(|element| async move { element.text().await })
-        {
-            Some(fut) => fut.await.map(|s| Some(s)),
-            None => Ok(None),
-        }
+async fn last_assistant_message(
+    client: &Client,
+) -> Result<Option<String>, fantoccini::error::CmdError> {
+    match client
+        .find_all(Locator::Css(ASSISTANT_MESSAGES))
+        .await?
+        .pop()
+        .map(|element| async move { element.text().await })
+    {
+        Some(fut) => fut.await.map(Some),
+        None => Ok(None),
     }
+}
 
-    /// this function should not be invoked on WebState::LoginTip state
-    async fn need_reopen(client: &Client) -> bool {
-        debug_assert_eq!(WebState::is_login_tip(&client).await, false);
-        client.find(Locator::Css("#prompt-textarea")).await.is_err()
-    }
+/// this function should not be invoked on LoginTip state
+async fn need_reopen(client: &Client) -> bool {
+    debug_assert!(!is_login_tip(client).await);
+    client.find(Locator::Css("#prompt-textarea")).await.is_err()
+}
 
-    /// find reversely the first button having text content "重新生成"
-    async fn reopen_chatbox(client: &Client) -> Result<(), fantoccini::error::CmdError> {
-        for btn in client
-            .find_all(Locator::Css("button"))
-            .await
-            .unwrap()
-            .iter()
-            .rev()
-        {
-            if btn.text().await.unwrap() == "重新生成" {
-                btn.click().await?
-            }
+/// find reversely the first button having text content "重新生成"
+async fn reopen_chatbox(client: &Client) -> Result<(), fantoccini::error::CmdError> {
+    for btn in client.find_all(Locator::Css("button")).await?.iter().rev() {
+        if btn.text().await? == "重新生成" {
+            btn.click().await?
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    async fn is_logging_in(client: &Client) -> bool {
-        client.find(Locator::Css("#challenge-form")).await.is_ok()
-    }
+async fn is_logging_in(client: &Client) -> bool {
+    client.find(Locator::Css("#challenge-form")).await.is_ok()
+}
 
-    async fn is_talking(client: &Client) -> bool {
-        let btn = get_send_btn(client).await;
-        match btn {
-            Some(btn) => {
-                btn.is_enabled().await.unwrap_or(false)
-                    && btn.find(Locator::Css("svg > rect")).await.is_ok()
-            }
-            None => false,
+async fn is_talking(client: &Client) -> bool {
+    let btn = get_send_btn(client).await;
+    match btn {
+        Some(btn) => {
+            btn.is_enabled().await.unwrap_or(false)
+                && btn.find(Locator::Css("svg > rect")).await.is_ok()
         }
+        None => false,
     }
 }
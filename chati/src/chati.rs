@@ -1,123 +1,137 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
 use tokio::sync::mpsc;
 
-use crate::chatgpt::ChatGPT;
-use crate::openai;
-use crate::util;
+use crate::backend::{self, BackendError, ChatBackend, Reply};
+use crate::config::BackendConfig;
+use crate::openai::MessageInfo;
+use crate::transcript::{Role, Transcript, TranscriptStore, Turn};
 
-use log::{debug, error};
+use tracing::error;
 
 pub struct Chati {
-    gpt: ChatGPT,
+    backend: Box<dyn ChatBackend>,
     // he could say nothing
-    he_said_tx: mpsc::UnboundedSender<Option<String>>,
-    he_said_rx: mpsc::UnboundedReceiver<Option<String>>,
+    he_said_tx: mpsc::UnboundedSender<Reply>,
+    he_said_rx: mpsc::UnboundedReceiver<Reply>,
+    transcript: Transcript,
+    /// where the transcript is saved after every answer
+    store: Option<TranscriptStore>,
 }
 
 impl Chati {
-    const WORDS_ENDED: &'static str = "\n\n\n\n\n\n";
+    pub async fn new(config: &BackendConfig) -> Result<Self, BackendError> {
+        Ok(Chati::with_backend(backend::connect(config).await?))
+    }
 
-    pub async fn new() -> Self {
-        let gpt = ChatGPT::new().await;
+    pub fn with_backend(backend: Box<dyn ChatBackend>) -> Self {
         let (he_said_tx, he_said_rx) = mpsc::unbounded_channel();
         Chati {
-            gpt,
+            backend,
             he_said_tx,
             he_said_rx,
+            transcript: Transcript::new(),
+            store: None,
         }
     }
 
-    pub async fn new_converstation(&mut self, auto_login: bool) {
-        let flag = Arc::new(AtomicBool::new(false));
-        let flag_tx = Arc::clone(&flag);
-        let flag_rx = Arc::clone(&flag);
+    /// save the transcript into `store` after every answer
+    pub fn keep_transcripts(&mut self, store: TranscriptStore) {
+        self.store = Some(store);
+    }
 
-        let he_said_tx = self.he_said_tx.clone();
-        tokio::task::spawn(async move {
-            loop {
-                if flag_rx.load(Ordering::Acquire) {
-                    debug!("Flag is set, task can proceed");
-                    break;
-                } else {
-                    debug!("Flag is not set, checking again...");
-                    // To prevent busy-waiting, you can sleep for a short duration
-                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                }
-            }
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
 
-            let he_said_tx = he_said_tx.clone();
-            // It could send two or more None in a run when respond to one chat message.
-            // The Situation is: when you send your first message, chatgpt.com could
-            // respond with an http 403 error. Then, you click the button "重新生成",
-            // chatgpt.com responds again with an http 403 error. This redundant message
-            // will result in sending the same user message again. Hence, `has_said_none`
-            // variable ensures at most one deliverary semantics.
-            let mut has_said_none = false;
-            if let Err(error) = util::listen_webpage_stream_data(
-                9222,
-                "https://chatgpt.com/",
-                0,
-                "https://chatgpt.com/backend-anon/conversation",
-                |data| match data {
-                    Some(data) => {
-                        has_said_none = false;
-                        openai::assistant_sse(data, |stream_msg, ended| {
-                            let words = stream_msg.to_string();
-                            if let Err(error) = he_said_tx.send(Some(words)) {
-                                error!("send response data to inner channel: {error:#?}");
-                            }
-                            if ended {
-                                if let Err(error) =
-                                    he_said_tx.send(Some(Self::WORDS_ENDED.to_string()))
-                                {
-                                    error!("send response data to inner channel: {error:#?}");
-                                }
-                            }
-                        });
-                    }
-                    None => {
-                        if !has_said_none {
-                            has_said_none = true;
-                            if let Err(error) = he_said_tx.send(None) {
-                                error!("send response data to inner channel: {error:#?}");
-                            }
-                        }
-                    }
-                },
-            )
+    pub async fn new_converstation(&mut self, auto_login: bool) -> Result<(), BackendError> {
+        self.transcript = Transcript::new();
+        self.backend
+            .new_conversation(auto_login, self.he_said_tx.clone())
             .await
-            {
-                error!("error on listen_webpage_stream_data: {error:#?}");
-            }
-        });
+    }
+
+    /// Go on with the conversation kept as `id`, or of the conversation id
+    /// `id`, in the store of [`Chati::keep_transcripts`] or `conversations`.
+    pub async fn reopen(&mut self, id: &str, auto_login: bool) -> Result<(), BackendError> {
+        let store = self
+            .store
+            .clone()
+            .unwrap_or_else(|| TranscriptStore::new(TranscriptStore::DEFAULT_DIR));
+        let transcript = store.load(id)?;
+        self.backend
+            .reopen_conversation(&transcript, auto_login, self.he_said_tx.clone())
+            .await?;
+        self.transcript = transcript;
+        Ok(())
+    }
 
-        if auto_login {
-            self.gpt.new_session(flag_tx).await;
-        } else {
-            self.gpt.wait_for_chatbox(flag_tx).await;
+    /// Set the instructions of the rest of the conversation. `false` when
+    /// the backend has no such thing, say it as a message then.
+    pub async fn set_system(&mut self, prompt: &str) -> Result<bool, BackendError> {
+        let set = self.backend.set_system(prompt).await?;
+        if set {
+            self.transcript.turns.push(Turn::system(prompt));
         }
+        Ok(set)
+    }
+
+    /// Forget the last exchange and return what was said in it, so that it
+    /// can be said again.
+    pub async fn forget_last_exchange(&mut self) -> Result<Option<String>, BackendError> {
+        let turns = &mut self.transcript.turns;
+        let Some(last_said) = turns.iter().rposition(|turn| turn.role == Role::User) else {
+            return Ok(None);
+        };
+        self.backend.forget_last_exchange().await?;
+        let said = turns.remove(last_said).text;
+        turns.truncate(last_said);
+        Ok(Some(said))
     }
 
-    pub async fn isaid(&mut self, said: &str) {
-        self.gpt.send_my_said(said).await;
+    #[tracing::instrument(
+        name = "isaid",
+        skip_all,
+        fields(transcript = %self.transcript.id, turn = self.transcript.turns.len())
+    )]
+    pub async fn isaid(&mut self, said: &str) -> Result<(), BackendError> {
+        self.backend.isaid(said).await?;
+        self.transcript.turns.push(Turn::user(said));
+        Ok(())
     }
 
+    /// Pass what he said to `out` until he finishes. `out` receives `None`
+    /// when he said nothing and the message should be said again.
+    #[tracing::instrument(
+        name = "hesaid",
+        skip_all,
+        fields(transcript = %self.transcript.id, turn = self.transcript.turns.len())
+    )]
     pub async fn hesaid<F, Fut>(&mut self, mut out: F)
     where
         F: FnMut(Option<String>) -> Fut,
         Fut: futures::Future<Output = ()>,
     {
+        let mut answer = String::new();
+        let mut info = MessageInfo::default();
         while let Some(words) = self.he_said_rx.recv().await {
             match words {
-                Some(words) => {
-                    if words == Self::WORDS_ENDED {
-                        out(Some("\n".to_string()));
-                        break;
-                    }
+                Reply::Message(message) => {
+                    self.transcript.learn(&message);
+                    info = message;
+                }
+                Reply::Words(words) => {
+                    answer.push_str(&words);
                     out(Some(words)).await;
                 }
-                None => {
+                Reply::End => {
+                    self.transcript.turns.push(Turn::assistant(&answer, &info));
+                    self.save_transcript();
+                    break;
+                }
+                Reply::Nothing => {
+                    // the message is to be said again
+                    if self.transcript.turns.last().map(|turn| turn.role) == Some(Role::User) {
+                        self.transcript.turns.pop();
+                    }
                     out(None).await;
                     break;
                 }
@@ -125,9 +139,63 @@ impl Chati {
         }
     }
 
+    fn save_transcript(&self) {
+        if let Some(store) = &self.store {
+            if let Err(error) = store.save(&self.transcript) {
+                error!("save transcript {}: {error}", self.transcript.id);
+            }
+        }
+    }
+
     pub async fn end(self) {
-        if let Err(error) = self.gpt.close().await {
-            error!("chatgpt.close: {error:#?}");
+        if let Err(error) = self.backend.end().await {
+            error!("chat backend end: {error:#?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplayBackend;
+
+    fn replayed() -> Chati {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay/retry_403.jsonl");
+        Chati::with_backend(Box::new(ReplayBackend::open(path).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_transcript() {
+        let dir = std::env::temp_dir().join(format!("chati-transcripts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut ci = replayed();
+        ci.keep_transcripts(TranscriptStore::new(&dir));
+        ci.new_converstation(false).await.unwrap();
+        for _ in 0..2 {
+            ci.isaid("translate").await.unwrap();
+            ci.hesaid(|_| async {}).await;
         }
+        let transcript = ci.transcript().clone();
+        // the said nothing is not kept
+        assert_eq!(transcript.turns.len(), 2);
+        assert_eq!(transcript.turns[0].role, Role::User);
+        assert_eq!(transcript.turns[1].text, "```c\n/* 游标 */\n```");
+        assert_eq!(transcript.turns[1].message_id.as_deref(), Some("m1"));
+        assert_eq!(transcript.turns[1].parent_id.as_deref(), Some("u1"));
+        assert_eq!(transcript.model_slug(), Some("gpt-4o-mini"));
+        assert_eq!(transcript.conversation_id.as_deref(), Some("c1"));
+
+        let mut ci = replayed();
+        ci.keep_transcripts(TranscriptStore::new(&dir));
+        ci.reopen("c1", false).await.unwrap();
+        assert_eq!(ci.transcript(), &transcript);
+        assert!(matches!(
+            ci.reopen("c2", false).await,
+            Err(BackendError::Io(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,438 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::backend::{BackendError, ChatBackend, Reply};
+use crate::config::CompletionsConfig;
+use crate::openai::{MessageInfo, SseDecoder};
+use crate::transcript::{Role, Transcript};
+
+use tracing::{debug, error};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatChunk {
+    id: Option<String>,
+    model: Option<String>,
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkChoice {
+    delta: Delta,
+}
+
+#[derive(Deserialize, Debug)]
+struct Delta {
+    content: Option<String>,
+}
+
+/// A client of an OpenAI-compatible `/chat/completions` endpoint.
+///
+/// The whole conversation is sent with every request and the answer is read
+/// as server-sent events.
+pub struct CompletionsBackend {
+    client: reqwest::Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    messages: Arc<Mutex<Vec<ChatMessage>>>,
+    replies: Option<mpsc::UnboundedSender<Reply>>,
+    /// the request still streaming its answer
+    pending: Option<JoinHandle<()>>,
+}
+
+impl CompletionsBackend {
+    pub fn new(config: &CompletionsConfig) -> Result<Self, BackendError> {
+        let api_key = config
+            .api_key_env
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok());
+        Ok(CompletionsBackend {
+            client: reqwest::Client::builder().build()?,
+            url: format!("{}/chat/completions", config.base_url.trim_end_matches('/')),
+            model: config.model.clone(),
+            api_key,
+            messages: Arc::new(Mutex::new(Vec::new())),
+            replies: None,
+            pending: None,
+        })
+    }
+
+    async fn wait_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            if let Err(error) = pending.await {
+                error!("streaming chat completion: {error:#?}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for CompletionsBackend {
+    async fn new_conversation(
+        &mut self,
+        _auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        self.wait_pending().await;
+        self.messages.lock().unwrap().clear();
+        self.replies = Some(replies);
+        Ok(())
+    }
+
+    async fn reopen_conversation(
+        &mut self,
+        transcript: &Transcript,
+        _auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        self.wait_pending().await;
+        *self.messages.lock().unwrap() = transcript
+            .turns
+            .iter()
+            .map(|turn| ChatMessage {
+                role: match turn.role {
+                    Role::System => "system",
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                }
+                .to_string(),
+                content: turn.text.clone(),
+            })
+            .collect();
+        self.replies = Some(replies);
+        Ok(())
+    }
+
+    async fn set_system(&mut self, prompt: &str) -> Result<bool, BackendError> {
+        self.wait_pending().await;
+        let system = ChatMessage {
+            role: "system".to_string(),
+            content: prompt.to_string(),
+        };
+        let mut messages = self.messages.lock().unwrap();
+        match messages.first_mut() {
+            Some(first) if first.role == "system" => *first = system,
+            _ => messages.insert(0, system),
+        }
+        Ok(true)
+    }
+
+    async fn forget_last_exchange(&mut self) -> Result<(), BackendError> {
+        self.wait_pending().await;
+        let mut messages = self.messages.lock().unwrap();
+        if messages
+            .last()
+            .is_some_and(|message| message.role == "assistant")
+        {
+            messages.pop();
+        }
+        if messages
+            .last()
+            .is_some_and(|message| message.role == "user")
+        {
+            messages.pop();
+        }
+        Ok(())
+    }
+
+    async fn isaid(&mut self, said: &str) -> Result<(), BackendError> {
+        self.wait_pending().await;
+        let replies = self.replies.clone().ok_or(BackendError::NoConversation)?;
+
+        let body = {
+            let mut messages = self.messages.lock().unwrap();
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: said.to_string(),
+            });
+            serde_json::to_vec(&ChatRequest {
+                model: &self.model,
+                messages: &messages,
+                stream: true,
+            })
+            .expect("serialize chat request")
+        };
+
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(error) => {
+                self.messages.lock().unwrap().pop();
+                return Err(error.into());
+            }
+        };
+
+        let messages = Arc::clone(&self.messages);
+        self.pending = Some(tokio::task::spawn(async move {
+            match stream_answer(response, &replies).await {
+                Some(answer) => {
+                    messages.lock().unwrap().push(ChatMessage {
+                        role: "assistant".to_string(),
+                        content: answer,
+                    });
+                    let _ = replies.send(Reply::End);
+                }
+                None => {
+                    // the user message will be said again
+                    messages.lock().unwrap().pop();
+                    let _ = replies.send(Reply::Nothing);
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    async fn end(mut self: Box<Self>) -> Result<(), BackendError> {
+        self.wait_pending().await;
+        Ok(())
+    }
+}
+
+/// forward every delta of the answer to `replies` and return the whole answer
+async fn stream_answer(
+    response: reqwest::Response,
+    replies: &mpsc::UnboundedSender<Reply>,
+) -> Option<String> {
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        error!("chat completion responded {status}: {text}");
+        return None;
+    }
+
+    let mut answer = String::new();
+    let mut message_told = false;
+    let mut decoder = SseDecoder::new();
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                error!("reading chat completion: {error:#?}");
+                return None;
+            }
+        };
+        for event in decoder.feed(&chunk) {
+            if event.data == "[DONE]" {
+                return Some(answer);
+            }
+            match serde_json::from_str::<ChatChunk>(&event.data) {
+                Ok(chunk) => {
+                    if !message_told && chunk.id.is_some() {
+                        message_told = true;
+                        let info = MessageInfo {
+                            message_id: chunk.id,
+                            model_slug: chunk.model,
+                            ..MessageInfo::default()
+                        };
+                        let _ = replies.send(Reply::Message(info));
+                    }
+                    for choice in chunk.choices {
+                        if let Some(words) = choice.delta.content {
+                            answer.push_str(&words);
+                            if replies.send(Reply::Words(words)).is_err() {
+                                debug!("nobody listens to the answer");
+                            }
+                        }
+                    }
+                }
+                Err(error) => {
+                    error!(
+                        "when parsing json text as chat chunk: {} {error:#?}",
+                        event.data
+                    );
+                }
+            }
+        }
+    }
+    Some(answer)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chati::Chati;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `responses` one per connection and hand back every request body.
+    pub(crate) async fn mock_server(
+        responses: Vec<String>,
+    ) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(pos) = text.find("\r\n\r\n") {
+                        let length = text[..pos]
+                            .lines()
+                            .find_map(|line| {
+                                line.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|len| len.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= pos + 4 + length {
+                            tx.send(text[pos + 4..].to_string()).unwrap();
+                            break;
+                        }
+                    }
+                }
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+        (format!("http://{addr}/v1"), rx)
+    }
+
+    pub(crate) fn sse_response(words: &[&str]) -> String {
+        let mut body = String::new();
+        for words in words {
+            let chunk = serde_json::json!({"choices": [{"delta": {"content": words}}]});
+            body.push_str(&format!("data: {chunk}\n\n"));
+        }
+        body.push_str("data: [DONE]\n\n");
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{body}"
+        )
+    }
+
+    fn config(base_url: String) -> CompletionsConfig {
+        CompletionsConfig {
+            base_url,
+            model: "mock".to_string(),
+            api_key_env: None,
+        }
+    }
+
+    async fn hesaid(ci: &mut Chati) -> Option<String> {
+        let said = Arc::new(Mutex::new(Some(String::new())));
+        ci.hesaid(|words| {
+            let said = Arc::clone(&said);
+            async move {
+                let mut said = said.lock().unwrap();
+                match words {
+                    Some(words) => said.as_mut().unwrap().push_str(&words),
+                    None => *said = None,
+                }
+            }
+        })
+        .await;
+        let said = said.lock().unwrap().clone();
+        said
+    }
+
+    #[tokio::test]
+    async fn test_stream_conversation() {
+        let (base_url, mut requests) = mock_server(vec![
+            sse_response(&["Hello", ", ", "world"]),
+            sse_response(&["again"]),
+        ])
+        .await;
+        let backend = CompletionsBackend::new(&config(base_url)).unwrap();
+        let mut ci = Chati::with_backend(Box::new(backend));
+        ci.new_converstation(false).await.unwrap();
+
+        ci.isaid("hi").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, Some("Hello, world".to_string()));
+        let request: serde_json::Value =
+            serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert_eq!(request["stream"], true);
+        assert_eq!(request["messages"].as_array().unwrap().len(), 1);
+
+        ci.isaid("once more").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, Some("again".to_string()));
+        let request: serde_json::Value =
+            serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"], "Hello, world");
+
+        ci.end().await;
+    }
+
+    #[tokio::test]
+    async fn test_error_status_says_nothing() {
+        let (base_url, _requests) = mock_server(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+            sse_response(&["ok"]),
+        ])
+        .await;
+        let backend = CompletionsBackend::new(&config(base_url)).unwrap();
+        let mut ci = Chati::with_backend(Box::new(backend));
+        ci.new_converstation(false).await.unwrap();
+
+        ci.isaid("hi").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, None);
+
+        ci.isaid("hi").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, Some("ok".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_system_and_retry() {
+        let (base_url, mut requests) =
+            mock_server(vec![sse_response(&["Bonjour"]), sse_response(&["Salut"])]).await;
+        let backend = CompletionsBackend::new(&config(base_url)).unwrap();
+        let mut ci = Chati::with_backend(Box::new(backend));
+        ci.new_converstation(false).await.unwrap();
+        assert!(ci.set_system("Answer in French.").await.unwrap());
+
+        ci.isaid("hi").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, Some("Bonjour".to_string()));
+        requests.recv().await.unwrap();
+
+        assert_eq!(
+            ci.forget_last_exchange().await.unwrap(),
+            Some("hi".to_string())
+        );
+        ci.isaid("hi").await.unwrap();
+        assert_eq!(hesaid(&mut ci).await, Some("Salut".to_string()));
+        let request: serde_json::Value =
+            serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"], "hi");
+
+        let roles = ci.transcript().turns.iter().map(|turn| turn.role);
+        assert_eq!(
+            roles.collect::<Vec<_>>(),
+            [Role::System, Role::User, Role::Assistant]
+        );
+    }
+}
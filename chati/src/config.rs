@@ -0,0 +1,220 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::browser::{BrowserConfig, BrowserConfigError};
+use crate::ratelimit::RateLimitConfig;
+use crate::trace::{LogConfig, StepDebugConfig};
+
+/// Settings shared by the executables of this crate.
+///
+/// It is read from the toml file named by the `CHATI_CONFIG` environment
+/// variable, or from `chati.toml` in the current directory. When neither
+/// exists, the defaults drive chatgpt.com through a browser.
+///
+/// ```toml
+/// glossary = "glossary.toml"
+///
+/// [backend]
+/// kind = "completions"
+/// base_url = "http://localhost:8080/v1"
+/// model = "gpt-4o-mini"
+/// api_key_env = "OPENAI_API_KEY"
+/// ```
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub backend: BackendConfig,
+    /// the glossary bcct translates with, see [`crate::glossary::Glossary::load`]
+    pub glossary: Option<PathBuf>,
+    /// where chati keeps the transcripts, `conversations` by default
+    pub transcripts: Option<PathBuf>,
+    pub server: ServerConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BackendConfig {
+    /// drive https://chatgpt.com with webdriver and cdp
+    Web(WebConfig),
+    /// talk to an OpenAI-compatible `/chat/completions` endpoint
+    Completions(CompletionsConfig),
+    /// answer with the network events recorded by a web backend
+    Replay(ReplayConfig),
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig::Web(WebConfig::default())
+    }
+}
+
+impl BackendConfig {
+    /// The backend of the `index`th session of a
+    /// [`crate::pool::SessionPool`]. A browser of a session after the first
+    /// one listens on a debugging port of its own, and keeps its profile,
+    /// records and steps in files numbered after it.
+    pub fn session(&self, index: usize) -> BackendConfig {
+        let BackendConfig::Web(web) = self else {
+            return self.clone();
+        };
+        if index == 0 {
+            return self.clone();
+        }
+        let mut web = web.clone();
+        let browser = &mut web.browser;
+        browser.debugging_port = browser.debugging_port.saturating_add(index as u16);
+        browser.user_data_dir = browser
+            .user_data_dir
+            .as_deref()
+            .map(|dir| numbered(dir, index));
+        for profile in &mut web.rate_limit.profiles {
+            profile.user_data_dir = profile
+                .user_data_dir
+                .as_deref()
+                .map(|dir| numbered(dir, index));
+        }
+        web.record = web.record.as_deref().map(|record| numbered(record, index));
+        if let Some(step_debug) = &mut web.step_debug {
+            step_debug.dir = numbered(&step_debug.dir, index);
+        }
+        BackendConfig::Web(web)
+    }
+}
+
+/// `events.jsonl` as `events-1.jsonl`
+fn numbered(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{index}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebConfig {
+    #[serde(flatten)]
+    pub browser: BrowserConfig,
+    /// append the network events of every conversation to this file
+    pub record: Option<PathBuf>,
+    /// fail with [`crate::backend::BackendError::LoggedOut`] rather than
+    /// wait for somebody to log in
+    pub fail_fast: bool,
+    /// said at the beginning of every browser session, their answers dropped
+    pub initial_prompts: Vec<String>,
+    /// how many times the browser is started again for one message
+    pub max_recoveries: usize,
+    pub rate_limit: RateLimitConfig,
+    /// keep the page at every step, see [`crate::trace::StepDebugger`]
+    pub step_debug: Option<StepDebugConfig>,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            browser: BrowserConfig::default(),
+            record: None,
+            fail_fast: false,
+            initial_prompts: Vec::new(),
+            max_recoveries: 3,
+            rate_limit: RateLimitConfig::default(),
+            step_debug: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompletionsConfig {
+    /// for example, `https://api.openai.com/v1`
+    pub base_url: String,
+    pub model: String,
+    /// name of the environment variable holding the api key
+    pub api_key_env: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReplayConfig {
+    /// a file recorded with `record` of [`WebConfig`]
+    pub path: PathBuf,
+}
+
+/// How `chati_server` serves `/v1/chat/completions`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub listen: String,
+    /// the model told to the clients asking for none
+    pub model: String,
+    /// requests waiting for the session beyond it are turned away
+    pub max_queue: usize,
+    /// how many times a message is said again when nothing is heard
+    pub retries: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: "127.0.0.1:8080".to_string(),
+            model: "chati".to_string(),
+            max_queue: 16,
+            retries: 3,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("parse {0}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+    #[error("browser: {0}")]
+    Browser(#[from] BrowserConfigError),
+    #[error("log filter {0}")]
+    LogFilter(String),
+}
+
+impl Config {
+    pub const DEFAULT_PATH: &'static str = "chati.toml";
+
+    /// see [`Config`] for where the file is searched
+    pub fn load() -> Result<Self, ConfigError> {
+        match std::env::var("CHATI_CONFIG") {
+            Ok(path) => Config::from_file(path),
+            Err(_) => {
+                if Path::new(Self::DEFAULT_PATH).exists() {
+                    Config::from_file(Self::DEFAULT_PATH)
+                } else {
+                    let config = Config::default();
+                    config.validate()?;
+                    Ok(config)
+                }
+            }
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        let config: Config =
+            toml::from_str(&text).map_err(|e| ConfigError::Toml(path.to_path_buf(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// fail at startup on what the browser would trip over later
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.log.validate().map_err(ConfigError::LogFilter)?;
+        if let BackendConfig::Web(web) = &self.backend {
+            web.browser.validate()?;
+            for profile in &web.rate_limit.profiles {
+                profile.apply(&web.browser).validate()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::config::ConfigError;
+use crate::util::content_hash;
+
+/// How comments are translated, rendered into the prompt bcct starts a
+/// conversation with.
+///
+/// ```toml
+/// target_language = "Chinese"
+/// do_not_translate = ["placement", "colocation"]
+///
+/// [terms]
+/// cursor = "游标"
+/// shard = "分片"
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Glossary {
+    pub target_language: String,
+    /// English terms and how they should be translated
+    pub terms: BTreeMap<String, String>,
+    /// words left as they are within a sentence
+    pub do_not_translate: Vec<String>,
+    /// the words of `terms` and `do_not_translate`, compiled once when the
+    /// glossary is loaded rather than for every reply verified
+    #[serde(skip)]
+    words: HashMap<String, Regex>,
+}
+
+impl Default for Glossary {
+    fn default() -> Self {
+        let terms = [("cursor", "游标"), ("shard", "分片"), ("helper", "辅助")];
+        Glossary {
+            target_language: "Chinese".to_string(),
+            terms: terms
+                .into_iter()
+                .map(|(term, translated)| (term.to_string(), translated.to_string()))
+                .collect(),
+            do_not_translate: [
+                "placement",
+                "colocation",
+                "colocation id",
+                "colocation group",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            words: HashMap::new(),
+        }
+        .compiled()
+    }
+}
+
+/// Something wrong with a translation found by [`Glossary::verify`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// a code name or an ALL-CAPS word of the original is lost
+    Lost(String),
+    /// a word never to be translated is translated
+    Translated(String),
+    /// a glossary term is translated otherwise
+    Glossary { term: String, expected: String },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Lost(word) => write!(f, "'{word}' should be kept unchanged"),
+            Violation::Translated(word) => write!(f, "'{word}' should not be translated"),
+            Violation::Glossary { term, expected } => {
+                write!(f, "'{term}' should be translated as '{expected}'")
+            }
+        }
+    }
+}
+
+impl Glossary {
+    pub const DEFAULT_PATH: &'static str = "glossary.toml";
+
+    /// Read the glossary from `path`, or from `glossary.toml` in the current
+    /// directory when not given. The default one is taken when neither exists.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Glossary::from_file(path),
+            None if Path::new(Self::DEFAULT_PATH).exists() => {
+                Glossary::from_file(Self::DEFAULT_PATH)
+            }
+            None => Ok(Glossary::default()),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&text)
+            .map(Glossary::compiled)
+            .map_err(|e| ConfigError::Toml(path.to_path_buf(), e))
+    }
+
+    fn compiled(mut self) -> Self {
+        for word in self.terms.keys().chain(&self.do_not_translate) {
+            if let Some(regex) = word_regex(word) {
+                self.words.insert(word.clone(), regex);
+            }
+        }
+        self
+    }
+
+    /// [`contains_word`] with the regex compiled at load
+    fn has_word(&self, text: &str, word: &str) -> bool {
+        match self.words.get(word) {
+            Some(regex) => regex.is_match(text),
+            None => contains_word(text, word),
+        }
+    }
+
+    /// the prompt telling the assistant how to translate
+    pub fn prompt(&self) -> String {
+        let language = &self.target_language;
+        let mut prompt = format!(
+            r#"Suppose you are a specialized code comment translator.
+Translate code comments I will provide to you coming from database project in following conversations into {language}.
+You need obey strictly the following rules:
+
+* The {language} text should be placed in an independent comment of which style is the same as the original.
+* You should not output the original comment.
+* For some code-related names, say data structure name, function name, variable name, you should not translate it.
+* For some English terms which may have different meaning in regular English context, you should also not translate it.
+* The word in all upper case letters, you should not translate it.
+* Keep the original whitespaces at the head of every line.
+* The output should be in a code block of the language of the comment, as the comment is given.
+* Wrap the line at around the column position 80.
+* Add one space between {language} text and English text.
+"#
+        );
+        if !self.terms.is_empty() {
+            prompt.push_str("\nAnd remember the following translation rules:\n");
+            for (term, translated) in &self.terms {
+                prompt.push_str(&format!(
+                    "* '{term}' should be translated as '{translated}'\n"
+                ));
+            }
+        }
+        if !self.do_not_translate.is_empty() {
+            prompt.push_str("\nAnd do not translate the word/words below within a sentence:\n");
+            for word in &self.do_not_translate {
+                prompt.push_str(&format!("  * {word}\n"));
+            }
+        }
+        prompt.push_str(&format!(
+            "\nThe final translation should preserve the structure and meaning of the original comment in {language}."
+        ));
+        prompt
+    }
+
+    /// tells translations made with another prompt apart
+    pub fn version(&self) -> String {
+        content_hash(&self.prompt())
+    }
+
+    /// Check the `translated` comment against the `original` one.
+    ///
+    /// Code names, ALL-CAPS words and the words never to be translated have
+    /// to be there unchanged, and the glossary terms have to be translated
+    /// as told.
+    pub fn verify(&self, original: &str, translated: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut seen = Vec::new();
+        for word in protected_words(original) {
+            if !seen.contains(&word) && !contains_word(translated, word) {
+                violations.push(Violation::Lost(word.to_string()));
+            }
+            seen.push(word);
+        }
+        for word in &self.do_not_translate {
+            if self.has_word(original, word) && !self.has_word(translated, word) {
+                violations.push(Violation::Translated(word.clone()));
+            }
+        }
+        for (term, expected) in &self.terms {
+            if self.has_word(original, term) && !translated.contains(expected.as_str()) {
+                violations.push(Violation::Glossary {
+                    term: term.clone(),
+                    expected: expected.clone(),
+                });
+            }
+        }
+        violations
+    }
+}
+
+/// Words which look like code, such as `pg_largeobject`, `RelationGetForm`
+/// or `palloc()`, and ALL-CAPS words such as `NULL`.
+fn protected_words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| word.chars().any(|c| c.is_ascii_alphabetic()))
+        .filter(|word| {
+            let all_caps = word.len() > 1 && !word.chars().any(|c| c.is_ascii_lowercase());
+            let snake = word.contains('_');
+            let camel = word
+                .as_bytes()
+                .windows(2)
+                .any(|pair| pair[0].is_ascii_lowercase() && pair[1].is_ascii_uppercase());
+            all_caps || snake || camel
+        })
+}
+
+/// `word` is in `text` as a whole word, regardless of case. Only ascii
+/// counts for word boundaries, as translations glue words to CJK text.
+fn contains_word(text: &str, word: &str) -> bool {
+    word_regex(word).is_some_and(|regex| regex.is_match(text))
+}
+
+fn word_regex(word: &str) -> Option<Regex> {
+    Regex::new(&format!(r"(?i)(?-u:\b){}(?-u:\b)", regex::escape(word))).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glossary_file() {
+        let glossary: Glossary = toml::from_str(
+            r#"
+target_language = "Japanese"
+do_not_translate = ["tuple"]
+
+[terms]
+shard = "シャード"
+"#,
+        )
+        .unwrap();
+        assert_eq!(glossary.terms["shard"], "シャード");
+
+        let prompt = glossary.prompt();
+        assert!(prompt.contains("into Japanese."));
+        assert!(prompt.contains("* 'shard' should be translated as 'シャード'\n"));
+        assert!(prompt.contains("  * tuple\n"));
+        assert!(!prompt.contains("游标"));
+        assert!(prompt.contains("in a code block of the language of the comment"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let glossary = Glossary::default();
+        assert_eq!(glossary.words.len(), 3 + 4);
+        let original = "/*\n * The cursor of NULL shards in pg_dist_placement, see GetPlacement().\n * One placement per shard.\n */";
+
+        let good = "/*\n * pg_dist_placement 中NULL分片的游标，参见 GetPlacement()。\n * 每个分片一个 placement。\n */";
+        assert_eq!(glossary.verify(original, good), vec![]);
+
+        let bad =
+            "/*\n * pg_dist 中空分片的光标，参见 GetPlacement()。\n * 每个分片一个放置。\n */";
+        assert_eq!(
+            glossary.verify(original, bad),
+            vec![
+                Violation::Lost("NULL".to_string()),
+                Violation::Lost("pg_dist_placement".to_string()),
+                Violation::Translated("placement".to_string()),
+                Violation::Glossary {
+                    term: "cursor".to_string(),
+                    expected: "游标".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::cdp::{self, CdpClient, CdpError, Event, TargetInfo};
+
+use tracing::{debug, error};
+
+type UrlPredicate = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Which tab of the browser to listen to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetSelector {
+    Id(String),
+    Title(String),
+    /// the first tab whose url begins with it
+    Url(String),
+}
+
+impl TargetSelector {
+    pub fn matches(&self, target: &TargetInfo) -> bool {
+        target.kind == "page"
+            && match self {
+                TargetSelector::Id(id) => target.id == *id,
+                TargetSelector::Title(title) => target.title == *title,
+                TargetSelector::Url(url) => target.url.starts_with(url.as_str()),
+            }
+    }
+}
+
+/// What an [`Interceptor`] sees of the network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NetworkEvent {
+    /// attached to the tab, again after a drop, in which case what happened
+    /// in between is lost
+    Attached(TargetInfo),
+    /// a response to a watched url began
+    Response {
+        request_id: String,
+        url: String,
+        status: u16,
+    },
+    /// a chunk of a streamed response. A chunk may end in the middle of a
+    /// utf-8 character, so bytes are handed out.
+    Data {
+        request_id: String,
+        /// base64 when serialized
+        #[serde(with = "base64_data")]
+        data: Vec<u8>,
+    },
+    /// the tab went to another page, the responses in flight are abandoned
+    Navigated { url: String },
+    /// the websocket dropped or the tab is gone, attached again later
+    Detached { reason: String },
+}
+
+/// Listens to the responses of one tab through cdp.
+///
+/// Before we listen, launch Chrome with `--remote-debugging-port=<port>`.
+///
+/// ```no_run
+/// # async fn run() {
+/// use chati::intercept::{Interceptor, TargetSelector};
+/// use futures::StreamExt;
+///
+/// let mut events = Interceptor::new(9222)
+///     .target(TargetSelector::Url("https://chatgpt.com/".to_string()))
+///     .stream(|url| url == "https://chatgpt.com/backend-anon/conversation")
+///     .listen();
+/// while let Some(event) = events.next().await {
+///     println!("{event:?}");
+/// }
+/// # }
+/// ```
+pub struct Interceptor {
+    port: u16,
+    target: TargetSelector,
+    watch: Vec<UrlPredicate>,
+    stream: Vec<UrlPredicate>,
+    capacity: usize,
+    reattach_delay: Duration,
+}
+
+impl Interceptor {
+    pub fn new(port: u16) -> Self {
+        Interceptor {
+            port,
+            target: TargetSelector::Url(String::new()),
+            watch: Vec::new(),
+            stream: Vec::new(),
+            capacity: 256,
+            reattach_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// the tab to listen to, the first one by default
+    pub fn target(mut self, target: TargetSelector) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// tell the responses to the urls `pred` accepts
+    pub fn watch(mut self, pred: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.watch.push(Box::new(pred));
+        self
+    }
+
+    /// tell the responses to the urls `pred` accepts along with their content
+    pub fn stream(mut self, pred: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.stream.push(Box::new(pred));
+        self
+    }
+
+    /// How many events may wait for the consumer. Beyond that, the
+    /// interceptor waits too, while the websocket is still read so that
+    /// responses to commands are not held back.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// how long to wait before attaching again when the tab is gone
+    pub fn reattach_delay(mut self, delay: Duration) -> Self {
+        self.reattach_delay = delay;
+        self
+    }
+
+    /// Start listening. The tab is attached to again whenever the websocket
+    /// drops or the tab is closed, until the stream is dropped.
+    pub fn listen(self) -> impl Stream<Item = NetworkEvent> + Send + Unpin {
+        let (tx, rx) = mpsc::channel(self.capacity);
+        tokio::spawn(async move {
+            loop {
+                let mut attached = false;
+                let reason = match self.attach(&tx, &mut attached).await {
+                    Ok(()) => {
+                        debug!("detached from {:?}", self.target);
+                        "the target is gone".to_string()
+                    }
+                    Err(error) => {
+                        error!("listen to {:?}: {error}", self.target);
+                        error.to_string()
+                    }
+                };
+                if attached && tx.send(NetworkEvent::Detached { reason }).await.is_err() {
+                    break;
+                }
+                if tx.is_closed() {
+                    break;
+                }
+                tokio::time::sleep(self.reattach_delay).await;
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+
+    /// forward what is watched of the target until it is gone or nobody listens
+    async fn attach(
+        &self,
+        tx: &mpsc::Sender<NetworkEvent>,
+        attached: &mut bool,
+    ) -> Result<(), CdpError> {
+        let (target, url) = cdp::targets(self.port)
+            .await?
+            .into_iter()
+            .filter(|target| self.target.matches(target))
+            .find_map(|target| {
+                let url = target.web_socket_debugger_url.clone()?;
+                Some((target, url))
+            })
+            .ok_or_else(|| CdpError::NoTarget(format!("{:?}", self.target)))?;
+
+        let client = CdpClient::connect(&url).await?;
+        let mut events = client.events();
+        // https://github.com/aslushnikov/getting-started-with-cdp/blob/master/README.md
+        client.send(&cdp::NetworkEnable {}).await?;
+        client.send(&cdp::PageEnable {}).await?;
+        if tx.send(NetworkEvent::Attached(target)).await.is_err() {
+            return Ok(());
+        }
+        *attached = true;
+
+        let mut streamed = HashSet::new();
+        while let Some(event) = events.recv().await {
+            let forward = match event {
+                Event::ResponseReceived(event) => {
+                    let url = event.response.url;
+                    let streams = self.stream.iter().any(|pred| pred(&url));
+                    if !streams && !self.watch.iter().any(|pred| pred(&url)) {
+                        continue;
+                    }
+                    let status = event.response.status;
+                    let mut forward = vec![NetworkEvent::Response {
+                        request_id: event.request_id.clone(),
+                        url,
+                        status,
+                    }];
+                    if streams && status / 100 == 2 {
+                        let command = cdp::StreamResourceContent {
+                            request_id: event.request_id.clone(),
+                        };
+                        match client.send(&command).await {
+                            // what is received before streaming is enabled comes back at once
+                            Ok(response) => {
+                                forward.push(NetworkEvent::Data {
+                                    request_id: event.request_id.clone(),
+                                    data: decode_base64(&response.buffered_data)?,
+                                });
+                                streamed.insert(event.request_id);
+                            }
+                            // such as a response which is over already
+                            Err(error @ CdpError::Protocol { .. }) => error!("{error}"),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    forward
+                }
+                Event::DataReceived(event) if streamed.contains(&event.request_id) => {
+                    match event.data {
+                        Some(data) => vec![NetworkEvent::Data {
+                            request_id: event.request_id,
+                            data: decode_base64(&data)?,
+                        }],
+                        None => continue,
+                    }
+                }
+                Event::FrameNavigated(event) if event.frame.parent_id.is_none() => {
+                    streamed.clear();
+                    vec![NetworkEvent::Navigated {
+                        url: event.frame.url,
+                    }]
+                }
+                Event::Detached(event) => {
+                    debug!("target detached: {}", event.reason);
+                    return Ok(());
+                }
+                _ => continue,
+            };
+            for event in forward {
+                if tx.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    use base64::{engine::general_purpose, Engine};
+
+    general_purpose::STANDARD.decode(encoded)
+}
+
+/// the content of a response as base64, the way cdp hands it out
+mod base64_data {
+    use base64::{engine::general_purpose, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&general_purpose::STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        super::decode_base64(&encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdp::tests::{cdp_server, respond};
+    use crate::completions::tests::mock_server;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    const CONVERSATION: &str = "https://chatgpt.com/backend-anon/conversation";
+    const SENTINEL: &str = "https://chatgpt.com/backend-anon/sentinel/chat-requirements";
+
+    fn response_received(request_id: &str, url: &str, status: u16) -> serde_json::Value {
+        json!({"method": "Network.responseReceived", "params": {
+            "requestId": request_id, "response": {"url": url, "status": status}}})
+    }
+
+    fn data_received(request_id: &str, data: &str) -> serde_json::Value {
+        json!({"method": "Network.dataReceived", "params": {
+            "requestId": request_id, "dataLength": 2, "data": data}})
+    }
+
+    #[test]
+    fn test_target_selector() {
+        let target = TargetInfo {
+            id: "A1".to_string(),
+            kind: "page".to_string(),
+            title: "ChatGPT".to_string(),
+            url: "https://chatgpt.com/c/1".to_string(),
+            web_socket_debugger_url: None,
+        };
+        assert!(TargetSelector::Id("A1".to_string()).matches(&target));
+        assert!(TargetSelector::Title("ChatGPT".to_string()).matches(&target));
+        assert!(TargetSelector::Url("https://chatgpt.com/".to_string()).matches(&target));
+        assert!(!TargetSelector::Url("https://chat.openai.com/".to_string()).matches(&target));
+        let worker = TargetInfo {
+            kind: "service_worker".to_string(),
+            ..target
+        };
+        assert!(!TargetSelector::Id("A1".to_string()).matches(&worker));
+    }
+
+    #[tokio::test]
+    async fn test_listen() {
+        let ws_url = cdp_server(
+            respond,
+            vec![
+                // dropped before attached
+                vec![json!("close")],
+                vec![
+                    response_received("r0", "https://chatgpt.com/", 200),
+                    response_received("r2", SENTINEL, 403),
+                    response_received("r1", CONVERSATION, 200),
+                    data_received("r1", "IHRoZXJl"),
+                    data_received("r0", "aGk="),
+                    json!({"method": "Page.frameNavigated", "params": {
+                        "frame": {"id": "f1", "url": "https://chatgpt.com/c/1"}}}),
+                    data_received("r1", "IHRoZXJl"),
+                ],
+            ],
+        )
+        .await;
+        let targets = json!([
+            {"id": "W", "type": "service_worker", "title": "", "url": "https://chatgpt.com/sw.js",
+             "webSocketDebuggerUrl": "ws://127.0.0.1:1/"},
+            {"id": "P", "type": "page", "title": "ChatGPT", "url": "https://chatgpt.com/",
+             "webSocketDebuggerUrl": ws_url},
+        ]);
+        let listing = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n{targets}"
+        );
+        let (base_url, _requests) = mock_server(vec![listing.clone(), listing]).await;
+        let port = url::Url::parse(&base_url).unwrap().port().unwrap();
+
+        let mut events = Interceptor::new(port)
+            .target(TargetSelector::Title("ChatGPT".to_string()))
+            .stream(|url| url == CONVERSATION)
+            .watch(|url| url == SENTINEL)
+            .reattach_delay(Duration::from_millis(10))
+            .capacity(1)
+            .listen();
+
+        let mut received = Vec::new();
+        for _ in 0..6 {
+            received.push(events.next().await.unwrap());
+        }
+        assert!(matches!(&received[0], NetworkEvent::Attached(target) if target.id == "P"));
+        assert_eq!(
+            received[1..],
+            [
+                NetworkEvent::Response {
+                    request_id: "r2".to_string(),
+                    url: SENTINEL.to_string(),
+                    status: 403
+                },
+                NetworkEvent::Response {
+                    request_id: "r1".to_string(),
+                    url: CONVERSATION.to_string(),
+                    status: 200
+                },
+                NetworkEvent::Data {
+                    request_id: "r1".to_string(),
+                    data: b"hi".to_vec()
+                },
+                NetworkEvent::Data {
+                    request_id: "r1".to_string(),
+                    data: b" there".to_vec()
+                },
+                NetworkEvent::Navigated {
+                    url: "https://chatgpt.com/c/1".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::comment_extractor::CommentOfC;
+use crate::util::content_hash;
+
+use tracing::warn;
+
+/// One translated comment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// byte offset of the comment in the code file
+    pub offset: usize,
+    /// `content_hash` of the original comment
+    pub hash: String,
+    pub translated: String,
+}
+
+/// A checkpoint journal of translated comments, one json object per line.
+///
+/// Every entry is flushed to disk as soon as it is recorded, so a restarted
+/// run can skip the comments already translated. It is the translation file
+/// merger reads as well, see [`crate::merge`].
+pub struct Journal {
+    file: File,
+    entries: HashMap<(usize, String), JournalEntry>,
+}
+
+impl Journal {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut entries = HashMap::new();
+        if path.exists() {
+            for entry in read(path)? {
+                entries.insert((entry.offset, entry.hash.clone()), entry);
+            }
+        }
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        // do not glue a new entry to a torn line
+        if file.metadata()?.len() > 0 && !std::fs::read(path)?.ends_with(b"\n") {
+            file.write_all(b"\n")?;
+        }
+        Ok(Journal { file, entries })
+    }
+
+    pub fn get(&self, com: &CommentOfC) -> Option<&JournalEntry> {
+        self.entries.get(&(com.offset, content_hash(&com.content)))
+    }
+
+    pub fn record(&mut self, com: &CommentOfC, translated: &str) -> std::io::Result<()> {
+        let entry = JournalEntry {
+            offset: com.offset,
+            hash: content_hash(&com.content),
+            translated: translated.to_string(),
+        };
+        let mut line = serde_json::to_string(&entry).expect("serialize journal entry");
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.entries
+            .insert((entry.offset, entry.hash.clone()), entry);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// all entries of the journal at `path`, in the order they were recorded
+pub fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<JournalEntry>> {
+    let path = path.as_ref();
+    let mut entries = Vec::new();
+    for (lineno, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<JournalEntry>(&line) {
+            Ok(entry) => entries.push(entry),
+            // most likely the tail written by a crashed run
+            Err(error) => warn!("skip line {} of {}: {error}", lineno + 1, path.display()),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(offset: usize, content: &str) -> CommentOfC {
+        CommentOfC {
+            offset,
+            end: offset + content.len(),
+            content: content.to_string(),
+            ..CommentOfC::new()
+        }
+    }
+
+    #[test]
+    fn test_journal_resume() {
+        let path = std::env::temp_dir().join(format!("journal-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let first = comment(0, "/*\n * first\n */");
+        let second = comment(20, "/*\n * second\n */");
+        {
+            let mut journal = Journal::open(&path).unwrap();
+            assert!(journal.is_empty());
+            journal.record(&first, "/*\n * 第一\n */").unwrap();
+        }
+        // a crash in the middle of writing an entry
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"offset\":20,\"ha")
+            .unwrap();
+
+        let mut journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.len(), 1);
+        assert_eq!(journal.get(&first).unwrap().translated, "/*\n * 第一\n */");
+        assert!(journal.get(&second).is_none());
+        // the same place with different content is not done
+        assert!(journal.get(&comment(0, "/*\n * changed\n */")).is_none());
+
+        journal.record(&second, "/*\n * 第二\n */").unwrap();
+        let journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.len(), 2);
+        assert!(journal.get(&second).is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,27 @@
+pub mod backend;
+pub mod batch;
+pub mod browser;
+pub mod cdp;
 pub mod chatgpt;
 pub mod chati;
 pub mod comment_extractor;
 pub mod comment_of_c;
+pub mod completions;
+pub mod config;
+pub mod glossary;
+pub mod intercept;
+pub mod journal;
+pub mod memory;
+pub mod merge;
 pub mod openai;
+pub mod page;
+pub mod pool;
+pub mod ratelimit;
+pub mod repl;
+pub mod replay;
+pub mod server;
+pub mod supervisor;
+pub mod trace;
+pub mod transcript;
 pub mod util;
+pub mod validator;
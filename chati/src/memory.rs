@@ -0,0 +1,199 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// Translations of earlier runs, kept in a sqlite file.
+///
+/// A translation is found by the normalized text of the comment, see
+/// [`normalize`], and the version of the prompt it was translated with, so
+/// moving or re-indenting a comment does not miss it, while a changed
+/// glossary does.
+pub struct TranslationMemory {
+    conn: Connection,
+    stats: MemoryStats,
+}
+
+/// How often the memory was of help
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MemoryStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl std::fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.hits + self.misses;
+        let rate = if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 * 100.0 / total as f64
+        };
+        write!(
+            f,
+            "{} hits, {} misses, {rate:.1}% hit rate",
+            self.hits, self.misses
+        )
+    }
+}
+
+impl TranslationMemory {
+    pub const DEFAULT_PATH: &'static str = "translation_memory.db";
+
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS translation (
+                normalized TEXT NOT NULL,
+                version TEXT NOT NULL,
+                translated TEXT NOT NULL,
+                PRIMARY KEY (normalized, version)
+            )",
+            [],
+        )?;
+        Ok(TranslationMemory {
+            conn,
+            stats: MemoryStats::default(),
+        })
+    }
+
+    /// the translation of `comment` made with the prompt of `version`
+    pub fn get(&mut self, comment: &str, version: &str) -> rusqlite::Result<Option<String>> {
+        let translated = self
+            .conn
+            .query_row(
+                "SELECT translated FROM translation WHERE normalized = ?1 AND version = ?2",
+                params![normalize(comment), version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match translated {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        Ok(translated)
+    }
+
+    pub fn put(&self, comment: &str, version: &str, translated: &str) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO translation (normalized, version, translated) VALUES (?1, ?2, ?3)",
+            params![normalize(comment), version, translated],
+        )?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        self.stats
+    }
+}
+
+/// The opening marker of a comment and its words, without gutters and the
+/// layout of whitespaces. A `//` comment never gets the translation of a
+/// `/* */` one, which would be merged in the other style.
+pub fn normalize(comment: &str) -> String {
+    let mut words = vec![style(comment)];
+    for line in comment.lines() {
+        let mut line = line.trim();
+        for marker in ["/*", "//", "--", "#"] {
+            line = line.strip_prefix(marker).unwrap_or(line);
+        }
+        let line = line.strip_suffix("*/").unwrap_or(line);
+        // the ` * ` gutter, banners such as `/*-----` and doc markers
+        let line = line.trim_start_matches(['*', '/', '!', '-', '=']);
+        words.extend(line.split_whitespace());
+    }
+    words.join(" ")
+}
+
+/// the marker opening `comment`, `/**` and `///` telling doc comments
+fn style(comment: &str) -> &'static str {
+    let first = comment.trim_start();
+    let doc = |marker: &str| {
+        first
+            .strip_prefix(marker)
+            .is_some_and(|rest| !rest.starts_with(&marker[1..2]))
+    };
+    if doc("/**") {
+        "/**"
+    } else if first.starts_with("/*") {
+        "/*"
+    } else if doc("///") {
+        "///"
+    } else if first.starts_with("//") {
+        "//"
+    } else if first.starts_with("--") {
+        "--"
+    } else if first.starts_with('#') {
+        "#"
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let comment = "\t/*-----\n\t * Returns the  shard\n\t * placement.\n\t */";
+        assert_eq!(normalize(comment), "/* Returns the shard placement.");
+        assert_eq!(
+            normalize("/*\n * Returns the shard placement.\n */"),
+            normalize(comment)
+        );
+        assert_eq!(
+            normalize("/******\n * Returns the shard placement.\n */"),
+            normalize(comment)
+        );
+        // the styles do not share translations
+        let line = "    // Returns the shard\n    // placement.";
+        assert_eq!(normalize(line), "// Returns the shard placement.");
+        assert_ne!(normalize(line), normalize(comment));
+        assert_ne!(
+            normalize("/** Returns the shard placement. */"),
+            normalize(comment)
+        );
+        assert_ne!(
+            normalize("/// Returns the shard\n/// placement."),
+            normalize(line)
+        );
+        assert_ne!(
+            normalize("-- Returns the shard placement."),
+            normalize(line)
+        );
+        assert_ne!(normalize("/* Returns the shard. */"), normalize(comment));
+    }
+
+    #[test]
+    fn test_memory() {
+        let path = std::env::temp_dir().join(format!("memory-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let original = "/*\n * Returns the shard.\n */";
+        {
+            let mut memory = TranslationMemory::open(&path).unwrap();
+            assert_eq!(memory.get(original, "v1").unwrap(), None);
+            memory
+                .put(original, "v1", "/*\n * 返回分片。\n */")
+                .unwrap();
+        }
+
+        let mut memory = TranslationMemory::open(&path).unwrap();
+        // indented elsewhere in a newer release
+        let moved = "\t/*\n\t * Returns the shard.\n\t */";
+        assert_eq!(
+            memory.get(moved, "v1").unwrap().as_deref(),
+            Some("/*\n * 返回分片。\n */")
+        );
+        // another glossary
+        assert_eq!(memory.get(original, "v2").unwrap(), None);
+        // the same words in line comments
+        assert_eq!(memory.get("// Returns the shard.", "v1").unwrap(), None);
+        assert_eq!(memory.stats(), MemoryStats { hits: 1, misses: 2 });
+        assert_eq!(
+            memory.stats().to_string(),
+            "1 hits, 2 misses, 33.3% hit rate"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use metacomment::language::{CommentSyntax, Language};
+use metacomment::scanner::{CommentKind, CommentScanner};
+
+use crate::comment_extractor::CommentOfC;
+use crate::journal::JournalEntry;
+use crate::util::{
+    content_hash, merge_comments, merge_line_comments, stack_comments, MergeError, MergeMode,
+};
+use crate::validator::{Problem, ReplyValidator};
+
+/// How the entries of a translation file line up with the comments of a code file
+#[derive(Debug, Default)]
+pub struct Matching {
+    /// comments with their translation, in the order of the code file
+    pub matched: Vec<(CommentOfC, JournalEntry)>,
+    /// comments nothing is translated for
+    pub unmatched: Vec<CommentOfC>,
+    /// translations of comments which are not in the code file any more
+    pub stale: Vec<JournalEntry>,
+}
+
+/// Pair every comment with its translation by offset and content hash.
+///
+/// A translation whose comment moved, that is, only the hash is the same,
+/// is still taken. When a comment is translated more than once, the last
+/// entry wins.
+pub fn match_translations(comments: Vec<CommentOfC>, entries: Vec<JournalEntry>) -> Matching {
+    let mut by_key = HashMap::new();
+    for entry in entries {
+        by_key.insert((entry.offset, entry.hash.clone()), entry);
+    }
+
+    let mut matching = Matching::default();
+    let mut moved = Vec::new();
+    for com in comments {
+        let key = (com.offset, content_hash(&com.content));
+        match by_key.remove(&key) {
+            Some(entry) => matching.matched.push((com, entry)),
+            None => moved.push((com, key.1)),
+        }
+    }
+
+    let mut rest = by_key.into_values().collect::<Vec<_>>();
+    rest.sort_by_key(|entry| entry.offset);
+    for (com, hash) in moved {
+        // the nearest entry of the same content
+        let nearest = rest
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.hash == hash)
+            .min_by_key(|(_, entry)| entry.offset.abs_diff(com.offset))
+            .map(|(i, _)| i);
+        match nearest {
+            Some(i) => matching.matched.push((com, rest.remove(i))),
+            None => matching.unmatched.push(com),
+        }
+    }
+    matching.matched.sort_by_key(|(com, _)| com.offset);
+    matching.stale = rest;
+    matching
+}
+
+/// The comment inside what the assistant replied, which is usually wrapped
+/// in a code block.
+pub fn translated_comment(reply: &str, language: &'static dyn Language) -> Option<String> {
+    // a code block of another language is not taken
+    let text = match ReplyValidator::new(language).code_block(reply) {
+        Ok(block) => block,
+        Err(Problem::NoCodeBlock(_)) => reply,
+        Err(_) => return None,
+    };
+    let mut scanner = CommentScanner::new(language);
+    while let Some((start, end, kind)) = scanner.scan(text.as_bytes(), true) {
+        if kind != CommentKind::TrailingInline {
+            return Some(text[start..end].to_string());
+        }
+    }
+    None
+}
+
+/// Merge the `translated` comment with the `original` one.
+pub fn merge(
+    original: &str,
+    translated: &str,
+    syntax: &CommentSyntax,
+    mode: MergeMode,
+) -> Result<String, MergeError> {
+    if !original.trim().contains('\n') {
+        return Ok(stack_comments(translated, original, mode));
+    }
+    let is_block = syntax
+        .block
+        .is_some_and(|(open, _)| original.trim_start().starts_with(open));
+    if is_block {
+        merge_comments(translated, original, mode)
+    } else {
+        Ok(merge_line_comments(
+            translated,
+            original,
+            syntax.line.unwrap_or("//"),
+            mode,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metacomment::language::C;
+
+    fn comment(offset: usize, content: &str) -> CommentOfC {
+        CommentOfC {
+            offset,
+            end: offset + content.len(),
+            content: content.to_string(),
+            ..CommentOfC::new()
+        }
+    }
+
+    fn entry(offset: usize, content: &str, translated: &str) -> JournalEntry {
+        JournalEntry {
+            offset,
+            hash: content_hash(content),
+            translated: translated.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_match_translations() {
+        let comments = vec![
+            comment(0, "/*\n * same\n */"),
+            comment(50, "/*\n * moved\n */"),
+            comment(100, "/*\n * changed\n */"),
+            comment(150, "/*\n * new\n */"),
+        ];
+        let entries = vec![
+            entry(0, "/*\n * same\n */", "old"),
+            entry(0, "/*\n * same\n */", "retried"),
+            entry(40, "/*\n * moved\n */", "moved"),
+            entry(100, "/*\n * before change\n */", "stale"),
+        ];
+        let matching = match_translations(comments, entries);
+        let matched = matching
+            .matched
+            .iter()
+            .map(|(com, entry)| (com.offset, entry.translated.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(matched, vec![(0, "retried"), (50, "moved")]);
+        let unmatched = matching
+            .unmatched
+            .iter()
+            .map(|com| com.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(unmatched, vec![100, 150]);
+        assert_eq!(matching.stale.len(), 1);
+        assert_eq!(matching.stale[0].translated, "stale");
+    }
+
+    #[test]
+    fn test_translated_comment() {
+        let reply = "```c\n    /*\n     * 中文\n     */\n```";
+        assert_eq!(
+            translated_comment(reply, &C).as_deref(),
+            Some("    /*\n     * 中文\n     */")
+        );
+        assert_eq!(translated_comment("对不起", &C), None);
+        assert_eq!(translated_comment("```rust\n// 中文\n```", &C), None);
+    }
+}
@@ -1,7 +1,5 @@
-use lazy_static::lazy_static;
-use std::collections::HashMap;
-use std::sync::Mutex;
-use log::error;
+use std::collections::{HashMap, VecDeque};
+use tracing::error;
 
 #[derive(serde::Deserialize, Debug)]
 pub struct Conversation {
@@ -49,84 +47,314 @@ pub struct Metadata {
     pub model_switcher_deny: Option<Vec<String>>,
 }
 
-/// handle service-sent events coming from https://chatgpt.com/backend-anon/conversation
-pub fn assistant_sse(data: &str, mut outfn: impl FnMut(&str, bool)) {
-    static mut CONTENT_OFFSET: usize = 0;
-    static mut MESSAGE_ENDED: bool = false;
-    lazy_static! {
-        static ref MESSAGE_ID: Mutex<String> = Mutex::new(String::from(""));
-    }
+/// One server-sent event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// the `event:` field, a plain `message` when missing
+    pub event: Option<String>,
+    /// the last `id:` seen so far
+    pub id: Option<String>,
+    /// the `data:` lines joined by newlines
+    pub data: String,
+}
+
+/// Split a byte stream into server-sent events as the html spec says.
+///
+/// Chunks may end anywhere, even inside a utf-8 character; what is not a
+/// complete line yet is kept for the next chunk.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+    last_id: Option<String>,
+}
 
-    if data.len() == 0 {
-        return;
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
     }
-    // println!("BEGIN--");
-    // println!("{data}");
-    // println!("--END");
-
-    let conversations = data
-        .split("\n\n")
-        .collect::<Vec<_>>()
-        .into_iter()
-        .filter(|line| line.len() > 0 && line.trim() != "data: [DONE]")
-        .map(|line| {
-            match serde_json::from_str::<Conversation>(line.trim_start_matches("data:").trim()) {
-                Ok(x) => Some(x),
-                Err(error) => {
-                    error!("when parsing json text as Conversation: {line} {error:#?}");
-                    None
+
+    /// decode `chunk` and return the events completed by it
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = self.buffer[start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            let end = start + pos;
+            let mut next = end + 1;
+            if self.buffer[end] == b'\r' {
+                match self.buffer.get(next) {
+                    Some(b'\n') => next += 1,
+                    Some(_) => {}
+                    // a `\n` may follow in the next chunk
+                    None => break,
                 }
             }
-        });
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).to_string();
+            if let Some(event) = self.line(&line) {
+                events.push(event);
+            }
+            start = next;
+        }
+        self.buffer.drain(..start);
+        events
+    }
 
-    for con in conversations {
-        if con.is_none() {
-            continue;
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            // dispatch, an event without data is dropped
+            let event = self.event.take();
+            let mut data = self.data.take()?;
+            data.pop();
+            return Some(SseEvent {
+                event,
+                id: self.last_id.clone(),
+                data,
+            });
+        }
+        if line.starts_with(':') {
+            // a comment
+            return None;
         }
-        let con = con.unwrap();
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                let data = self.data.get_or_insert_with(String::new);
+                data.push_str(value);
+                data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
 
-        // each part message contains previous part message
-        let cont_part = &con.message.content.parts[0];
+/// Where a message of the assistant belongs and who wrote it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageInfo {
+    pub conversation_id: Option<String>,
+    pub message_id: Option<String>,
+    /// the message it answers
+    pub parent_id: Option<String>,
+    pub model_slug: Option<String>,
+}
+
+/// where chatgpt.com streams the answers from
+pub const CONVERSATION_URL: &str = "https://chatgpt.com/backend-anon/conversation";
+/// answered with an error when chatgpt.com refuses to answer
+pub const SENTINEL_URL: &str = "https://chatgpt.com/backend-anon/sentinel/chat-requirements";
 
-        // println!("message id: {}", con.message.id);
-        // let _ = std::io::stdout().flush();
+/// how many finished messages are remembered, to drop their repeated ends
+const FINISHED_KEPT: usize = 16;
 
-        let mut msgid = MESSAGE_ID.lock().unwrap();
-        let offset = if *msgid == con.message.id {
-            let old_offset = unsafe { CONTENT_OFFSET };
-            unsafe {
-                CONTENT_OFFSET = cont_part.len();
+/// how far a message has been told
+#[derive(Debug, Default)]
+struct MessageProgress {
+    offset: usize,
+}
+
+/// Decode the server-sent events coming from [`CONVERSATION_URL`]
+/// into what each message newly says.
+///
+/// Every event carries the whole message so far, only the part after what
+/// was told before is handed out. One decoder follows one response stream,
+/// so several conversations or tabs are decoded with a decoder each.
+#[derive(Debug, Default)]
+pub struct AssistantDecoder {
+    sse: SseDecoder,
+    /// the messages being told
+    messages: HashMap<String, MessageProgress>,
+    /// the ids of the messages told to the end, the latest last
+    finished: VecDeque<String>,
+}
+
+impl AssistantDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode `chunk` and call `outfn` with the message, its new words and
+    /// whether it is finished. The end of a message is told once.
+    pub fn feed(&mut self, chunk: &[u8], mut outfn: impl FnMut(&MessageInfo, &str, bool)) {
+        for event in self.sse.feed(chunk) {
+            if event.data == "[DONE]" {
+                continue;
             }
-            old_offset
-        } else {
-            *msgid = con.message.id;
-            unsafe {
-                CONTENT_OFFSET = cont_part.len();
+            let con = match serde_json::from_str::<Conversation>(&event.data) {
+                Ok(con) => con,
+                Err(error) => {
+                    error!(
+                        "when parsing json text as Conversation: {} {error:#?}",
+                        event.data
+                    );
+                    continue;
+                }
+            };
+            // each part message contains previous part message
+            let Some(cont_part) = con.message.content.parts.first() else {
+                continue;
+            };
+            let metadata = con.message.metadata.as_ref();
+            let info = MessageInfo {
+                conversation_id: Some(con.conversation_id.clone()),
+                message_id: Some(con.message.id.clone()),
+                parent_id: metadata.and_then(|metadata| metadata.parent_id.clone()),
+                model_slug: metadata.and_then(|metadata| metadata.model_slug.clone()),
+            };
+            if self.finished.contains(&con.message.id) {
+                // openai could send multiple events of "message.status = 'finished_successfully'"
+                continue;
             }
-            unsafe {
-                MESSAGE_ENDED = false;
+            let progress = self.messages.entry(con.message.id.clone()).or_default();
+            let new_words = cont_part.get(progress.offset..).unwrap_or_else(|| {
+                error!("message shrank to {cont_part}, tell it again");
+                cont_part
+            });
+            progress.offset = cont_part.len();
+            let ended = con.message.status.as_deref().is_some_and(is_final);
+            if ended {
+                self.messages.remove(&con.message.id);
+                if self.finished.len() == FINISHED_KEPT {
+                    self.finished.pop_front();
+                }
+                self.finished.push_back(con.message.id);
             }
-            0
-        };
+            outfn(&info, new_words, ended);
+        }
+    }
+}
 
-        // openai could send multiple events of "message.status = 'finished_successfully'"
-        // we need only one 'ended' notification
-        let ended = if let Some(status) = con.message.status {
-            status == "finished_successfully"
-        } else {
-            false
-        };
+/// `finished_successfully`, `finished_partial_completion` and the like,
+/// after which a message says nothing more
+fn is_final(status: &str) -> bool {
+    status.starts_with("finished")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
 
-        // println!("offset: {offset}, ended: {ended}");
+    fn decode_in_pieces(stream: &[u8], piece: usize) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        stream
+            .chunks(piece)
+            .flat_map(|chunk| decoder.feed(chunk))
+            .collect()
+    }
 
-        let msg_ended = unsafe { MESSAGE_ENDED };
-        if !msg_ended {
-            outfn(&cont_part[offset..], ended);
+    #[test]
+    fn test_sse_decoder() {
+        let stream = ": keep alive\r\nevent: delta\r\nid: 7\r\ndata: first\r\ndata:  second\r\n\r\ndata\rretry: 10\r\r\n\ndata: 中文\n\n";
+        let expected = vec![
+            SseEvent {
+                event: Some("delta".to_string()),
+                id: Some("7".to_string()),
+                data: "first\n second".to_string(),
+            },
+            SseEvent {
+                event: None,
+                id: Some("7".to_string()),
+                data: "".to_string(),
+            },
+            SseEvent {
+                event: None,
+                id: Some("7".to_string()),
+                data: "中文".to_string(),
+            },
+        ];
+        for piece in 1..stream.len() {
+            assert_eq!(
+                decode_in_pieces(stream.as_bytes(), piece),
+                expected,
+                "in pieces of {piece}"
+            );
         }
-        if ended {
-            unsafe {
-                MESSAGE_ENDED = true;
+    }
+
+    pub(crate) fn message_event(id: &str, text: &str, status: &str) -> String {
+        let con = serde_json::json!({
+            "message": {
+                "id": id,
+                "content": {"content_type": "text", "parts": [text]},
+                "status": status,
+                "metadata": {"model_slug": "gpt-4o-mini", "parent_id": "u1"},
+            },
+            "conversation_id": "c1",
+            "error": null,
+        });
+        format!("data: {con}\n\n")
+    }
+
+    #[test]
+    fn test_assistant_decoder() {
+        let stream = [
+            message_event("m1", "你", "in_progress"),
+            message_event("m2", "other", "in_progress"),
+            message_event("m1", "你好", "in_progress"),
+            message_event("m1", "你好！", "finished_successfully"),
+            message_event("m1", "你好！", "finished_successfully"),
+            message_event("m2", "other tab", "finished_successfully"),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        for piece in [1, 2, 5, stream.len()] {
+            let mut decoder = AssistantDecoder::new();
+            let mut told = Vec::new();
+            for chunk in stream.as_bytes().chunks(piece) {
+                decoder.feed(chunk, |info, words, ended| {
+                    let id = info.message_id.clone().unwrap();
+                    told.push((id, words.to_string(), ended))
+                });
             }
+            let expected = [
+                ("m1", "你", false),
+                ("m2", "other", false),
+                ("m1", "好", false),
+                ("m1", "！", true),
+                ("m2", " tab", true),
+            ]
+            .map(|(id, words, ended)| (id.to_string(), words.to_string(), ended));
+            assert_eq!(told, expected, "in pieces of {piece}");
+            assert!(decoder.messages.is_empty());
         }
+
+        // only the latest finished messages are remembered
+        let mut decoder = AssistantDecoder::new();
+        for i in 0..FINISHED_KEPT + 4 {
+            let event = message_event(&format!("m{i}"), "done", "finished_successfully");
+            decoder.feed(event.as_bytes(), |_, _, _| {});
+        }
+        assert!(decoder.messages.is_empty());
+        assert_eq!(decoder.finished.len(), FINISHED_KEPT);
+        let mut told = 0;
+        let last = format!("m{}", FINISHED_KEPT + 3);
+        let event = message_event(&last, "done", "finished_successfully");
+        decoder.feed(event.as_bytes(), |_, _, _| told += 1);
+        assert_eq!(told, 0);
+
+        let mut decoder = AssistantDecoder::new();
+        let mut infos = Vec::new();
+        decoder.feed(
+            message_event("m1", "你", "in_progress").as_bytes(),
+            |info, _, _| infos.push(info.clone()),
+        );
+        assert_eq!(
+            infos,
+            vec![MessageInfo {
+                conversation_id: Some("c1".to_string()),
+                message_id: Some("m1".to_string()),
+                parent_id: Some("u1".to_string()),
+                model_slug: Some("gpt-4o-mini".to_string()),
+            }]
+        );
     }
 }
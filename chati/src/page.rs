@@ -0,0 +1,662 @@
+use async_trait::async_trait;
+use fantoccini::error::CmdError;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use tracing::{debug, error, info, info_span, Instrument, Span};
+
+/// how often the page is looked at
+const POLL: Duration = Duration::from_millis(500);
+/// how many times "重新生成" is clicked before the page is refreshed
+const REGENERATIONS: usize = 2;
+
+/// Why the page can not take a message now
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unavailable {
+    /// "You've reached our limit of messages per hour"
+    Tired,
+    /// a challenge or a login stands in front of the chatbox
+    LoggedOut,
+    /// the webdriver session or its window is gone
+    Lost,
+    /// a message has been sending for too long
+    Stuck,
+}
+
+/// What the page shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageState {
+    /// not looked at yet, or the page could not be told
+    Unknown,
+    /// the cloudflare challenge
+    LoggingIn,
+    /// the dialog asking to log in
+    LoginTip,
+    /// "You've reached our limit of messages per hour"
+    Tired,
+    /// the chatbox is gone, the answer failed and is to be regenerated
+    NeedReopen,
+    /// the send button is spinning
+    Sending,
+    /// an answer is streaming in
+    Talking,
+    /// the chatbox takes a message
+    Ready,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PageError {
+    #[error("the webdriver session or its window is gone")]
+    Lost,
+    #[error("webdriver: {0}")]
+    WebDriver(CmdError),
+}
+
+impl From<CmdError> for PageError {
+    fn from(error: CmdError) -> Self {
+        match error {
+            CmdError::Lost(_) | CmdError::NoSuchWindow(_) => PageError::Lost,
+            error => PageError::WebDriver(error),
+        }
+    }
+}
+
+/// What [`PageMachine`] looks at and clicks on
+#[async_trait]
+pub trait PageDriver: Send {
+    async fn state(&mut self) -> Result<PageState, PageError>;
+
+    async fn close_login_tip(&mut self) -> Result<(), PageError>;
+
+    /// get through the challenge, or wait for somebody to
+    async fn log_in(&mut self) -> Result<(), PageError>;
+
+    /// click "重新生成"
+    async fn regenerate(&mut self) -> Result<(), PageError>;
+
+    async fn refresh(&mut self) -> Result<(), PageError>;
+
+    /// put `said` into the chatbox
+    async fn type_message(&mut self, said: &str) -> Result<(), PageError>;
+
+    /// `false` when the send button can not be clicked
+    async fn click_send(&mut self) -> Result<bool, PageError>;
+
+    /// how many answers the page shows, a limit notice among them
+    async fn answers(&mut self) -> Result<usize, PageError>;
+
+    /// the page has just become `name`, see [`crate::trace::StepDebugger`]
+    async fn step(&mut self, _name: &str) {}
+}
+
+/// How long the page may stay in a state before it is given up on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageTimeouts {
+    pub unknown: Duration,
+    pub logging_in: Duration,
+    pub login_tip: Duration,
+    pub need_reopen: Duration,
+    pub sending: Duration,
+    pub talking: Duration,
+    /// the send button stays disabled
+    pub ready: Duration,
+    /// how many times the answer may fail within one message, as when
+    /// chatgpt.com responds 403 again and again
+    pub max_reopens: usize,
+}
+
+impl Default for PageTimeouts {
+    fn default() -> Self {
+        PageTimeouts {
+            unknown: Duration::from_secs(60),
+            logging_in: Duration::from_secs(600),
+            login_tip: Duration::from_secs(30),
+            need_reopen: Duration::from_secs(60),
+            sending: Duration::from_secs(10),
+            talking: Duration::from_secs(300),
+            ready: Duration::from_secs(30),
+            max_reopens: 5,
+        }
+    }
+}
+
+impl PageTimeouts {
+    fn of(&self, state: PageState) -> Duration {
+        match state {
+            PageState::Unknown => self.unknown,
+            PageState::LoggingIn => self.logging_in,
+            PageState::LoginTip => self.login_tip,
+            // left at once, the limit is waited out by the caller
+            PageState::Tired => Duration::MAX,
+            PageState::NeedReopen => self.need_reopen,
+            PageState::Sending => self.sending,
+            PageState::Talking => self.talking,
+            PageState::Ready => self.ready,
+        }
+    }
+}
+
+/// A change of [`PageState`], `after` the former one was entered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub from: PageState,
+    pub to: PageState,
+    pub after: Duration,
+}
+
+/// Brings the page to where it takes a message, and the message to
+/// chatgpt.com.
+///
+/// Every state is left within its timeout of [`PageTimeouts`], by the page
+/// changing or by giving up with the [`Unavailable`] of the state.
+pub struct PageMachine {
+    timeouts: PageTimeouts,
+    /// give up rather than wait for somebody to log in
+    fail_fast: bool,
+    /// wait for somebody to log in rather than get through the challenge
+    manual_login: bool,
+    /// the answers on the page when a message was sent or the limit was
+    /// told last, a limit notice among them is waited out already
+    answered: Option<usize>,
+    state: PageState,
+    entered: Instant,
+    /// "重新生成" clicked since the last refresh
+    regenerated: usize,
+    reopens: usize,
+    transitions: Vec<Transition>,
+    /// of the state, what is done about it is logged in
+    span: Span,
+}
+
+impl PageMachine {
+    pub fn new(fail_fast: bool) -> Self {
+        Self::with_timeouts(PageTimeouts::default(), fail_fast)
+    }
+
+    pub fn with_timeouts(timeouts: PageTimeouts, fail_fast: bool) -> Self {
+        PageMachine {
+            timeouts,
+            fail_fast,
+            manual_login: false,
+            answered: None,
+            state: PageState::Unknown,
+            entered: Instant::now(),
+            regenerated: 0,
+            reopens: 0,
+            transitions: Vec::new(),
+            span: Span::none(),
+        }
+    }
+
+    /// leave the challenge to somebody at the browser, within the timeout of
+    /// [`PageState::LoggingIn`]
+    pub fn manual_login(mut self) -> Self {
+        self.manual_login = true;
+        self
+    }
+
+    /// go on from a machine which left the page with `answered` answers
+    pub fn with_answered(mut self, answered: Option<usize>) -> Self {
+        self.answered = answered;
+        self
+    }
+
+    /// the answers on the page when a message was sent or the limit was
+    /// told last, for the next machine of the page
+    pub fn answered(&self) -> Option<usize> {
+        self.answered
+    }
+
+    /// the transitions of the last [`PageMachine::open`] or
+    /// [`PageMachine::send`]
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    /// wait until the chatbox is there, whether it takes a message or not
+    pub async fn open(&mut self, page: &mut impl PageDriver) -> Result<(), Unavailable> {
+        self.drive(page, None).await
+    }
+
+    /// wait until the chatbox takes a message, then send `said`
+    pub async fn send(
+        &mut self,
+        page: &mut impl PageDriver,
+        said: &str,
+    ) -> Result<(), Unavailable> {
+        self.drive(page, Some(said)).await
+    }
+
+    #[tracing::instrument(name = "drive", skip_all, fields(send = said.is_some()))]
+    async fn drive(
+        &mut self,
+        page: &mut impl PageDriver,
+        said: Option<&str>,
+    ) -> Result<(), Unavailable> {
+        self.state = PageState::Unknown;
+        self.entered = Instant::now();
+        self.regenerated = 0;
+        self.reopens = 0;
+        self.transitions.clear();
+        self.span = info_span!("page", state = ?self.state);
+        loop {
+            let state = match page.state().await {
+                Ok(PageState::Tired) if said.is_some() => self.tired(page).await,
+                state => state,
+            };
+            match state {
+                Ok(state) if self.enter(state) => {
+                    let name = format!("{state:?}");
+                    page.step(&name).instrument(self.span.clone()).await;
+                }
+                Ok(_) => {}
+                Err(PageError::Lost) => return Err(Unavailable::Lost),
+                // the timeout of the state goes on
+                Err(error) => debug!("look at the page in {:?}: {error}", self.state),
+            }
+            if self.reopens > self.timeouts.max_reopens {
+                error!("the answer failed {} times", self.reopens);
+                return Err(Unavailable::Stuck);
+            }
+            let timeout = self.timeouts.of(self.state);
+            if self.entered.elapsed() >= timeout {
+                error!("the page is {:?} for {timeout:?}", self.state);
+                return Err(self.give_up());
+            }
+
+            let span = self.span.clone();
+            if self.handle(page, said).instrument(span).await? {
+                return Ok(());
+            }
+            tokio::time::sleep(POLL).await;
+        }
+    }
+
+    /// do what the state calls for, `true` once the page is where it is
+    /// driven to
+    async fn handle(
+        &mut self,
+        page: &mut impl PageDriver,
+        said: Option<&str>,
+    ) -> Result<bool, Unavailable> {
+        match self.state {
+            PageState::Unknown => {}
+            PageState::LoggingIn if self.fail_fast => return Err(Unavailable::LoggedOut),
+            PageState::LoggingIn if self.manual_login => {}
+            PageState::LoggingIn => {
+                let logged_in = self.act(page.log_in()).await;
+                self.check(logged_in)?;
+            }
+            PageState::LoginTip => {
+                let closed = self.act(page.close_login_tip()).await;
+                self.check(closed)?;
+            }
+            // it is waited out when something is to be said
+            PageState::Tired if said.is_none() => return Ok(true),
+            PageState::Tired => return Err(Unavailable::Tired),
+            PageState::NeedReopen if self.regenerated < REGENERATIONS => {
+                self.regenerated += 1;
+                let regenerated = self.act(page.regenerate()).await;
+                self.check(regenerated)?;
+            }
+            PageState::NeedReopen => {
+                self.regenerated = 0;
+                let refreshed = self.act(page.refresh()).await;
+                self.check(refreshed)?;
+            }
+            PageState::Sending | PageState::Talking => return Ok(said.is_none()),
+            PageState::Ready => match said {
+                None => return Ok(true),
+                Some(said) => {
+                    let typed = self.act(page.type_message(said)).await;
+                    self.check(typed)?;
+                    let answers = self.act(page.answers()).await;
+                    let answers = self.check(answers)?;
+                    let sent = self.act(page.click_send()).await;
+                    let sent = self.check(sent)? == Some(true);
+                    if sent {
+                        self.answered = answers;
+                    }
+                    return Ok(sent);
+                }
+            },
+        }
+        Ok(false)
+    }
+
+    /// A limit notice is told once. The one told before stays on the page
+    /// after the limit resets, and the message is sent in spite of it.
+    async fn tired(&mut self, page: &mut impl PageDriver) -> Result<PageState, PageError> {
+        let answers = page.answers().await?;
+        if self.answered.is_some_and(|answered| answers <= answered) {
+            debug!("the limit notice is waited out already");
+            return Ok(PageState::Ready);
+        }
+        self.answered = Some(answers);
+        Ok(PageState::Tired)
+    }
+
+    /// `false` when the page is still in the state it was
+    fn enter(&mut self, state: PageState) -> bool {
+        if state == self.state {
+            return false;
+        }
+        let transition = Transition {
+            from: self.state,
+            to: state,
+            after: self.entered.elapsed(),
+        };
+        info!(
+            parent: &self.span,
+            from = ?transition.from,
+            to = ?transition.to,
+            after = ?transition.after,
+            "page state"
+        );
+        self.transitions.push(transition);
+        self.state = state;
+        self.entered = Instant::now();
+        self.span = info_span!("page", state = ?state);
+        if state == PageState::NeedReopen {
+            self.reopens += 1;
+        }
+        true
+    }
+
+    fn remaining(&self) -> Duration {
+        self.timeouts
+            .of(self.state)
+            .saturating_sub(self.entered.elapsed())
+    }
+
+    /// `action` within what is left of the timeout of the state, `None`
+    /// when it is not done by then
+    async fn act<T>(
+        &self,
+        action: impl Future<Output = Result<T, PageError>>,
+    ) -> Option<Result<T, PageError>> {
+        tokio::time::timeout(self.remaining(), action).await.ok()
+    }
+
+    /// what came of an action, which failing is tried again but for a lost
+    /// session
+    fn check<T>(&self, done: Option<Result<T, PageError>>) -> Result<Option<T>, Unavailable> {
+        match done {
+            Some(Ok(done)) => Ok(Some(done)),
+            Some(Err(PageError::Lost)) => Err(Unavailable::Lost),
+            Some(Err(error)) => {
+                error!("in {:?}: {error}", self.state);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn give_up(&self) -> Unavailable {
+        match self.state {
+            PageState::Unknown => Unavailable::Lost,
+            PageState::LoggingIn | PageState::LoginTip => Unavailable::LoggedOut,
+            PageState::Tired => Unavailable::Tired,
+            PageState::NeedReopen | PageState::Sending | PageState::Talking | PageState::Ready => {
+                Unavailable::Stuck
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Action {
+        CloseLoginTip,
+        LogIn,
+        Regenerate,
+        Refresh,
+        Type(String),
+        Click,
+    }
+
+    /// Shows the states of its script one after another, the last one for
+    /// ever, and keeps what is done to it.
+    struct ScriptedPage {
+        script: VecDeque<PageState>,
+        /// the answers shown
+        answers: usize,
+        lost: bool,
+        send_enabled: bool,
+        actions: Vec<Action>,
+        steps: Vec<String>,
+    }
+
+    impl ScriptedPage {
+        fn new(script: impl IntoIterator<Item = PageState>) -> Self {
+            ScriptedPage {
+                script: script.into_iter().collect(),
+                answers: 0,
+                lost: false,
+                send_enabled: true,
+                actions: Vec::new(),
+                steps: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl PageDriver for ScriptedPage {
+        async fn state(&mut self) -> Result<PageState, PageError> {
+            if self.lost {
+                return Err(PageError::Lost);
+            }
+            let state = *self.script.front().expect("a script");
+            if self.script.len() > 1 {
+                self.script.pop_front();
+            }
+            Ok(state)
+        }
+
+        async fn close_login_tip(&mut self) -> Result<(), PageError> {
+            self.actions.push(Action::CloseLoginTip);
+            Ok(())
+        }
+
+        async fn log_in(&mut self) -> Result<(), PageError> {
+            self.actions.push(Action::LogIn);
+            Ok(())
+        }
+
+        async fn regenerate(&mut self) -> Result<(), PageError> {
+            self.actions.push(Action::Regenerate);
+            Ok(())
+        }
+
+        async fn refresh(&mut self) -> Result<(), PageError> {
+            self.actions.push(Action::Refresh);
+            Ok(())
+        }
+
+        async fn type_message(&mut self, said: &str) -> Result<(), PageError> {
+            self.actions.push(Action::Type(said.to_string()));
+            Ok(())
+        }
+
+        async fn click_send(&mut self) -> Result<bool, PageError> {
+            self.actions.push(Action::Click);
+            Ok(self.send_enabled)
+        }
+
+        async fn answers(&mut self) -> Result<usize, PageError> {
+            Ok(self.answers)
+        }
+
+        async fn step(&mut self, name: &str) {
+            self.steps.push(name.to_string());
+        }
+    }
+
+    fn path(machine: &PageMachine) -> Vec<PageState> {
+        machine
+            .transitions()
+            .iter()
+            .map(|transition| transition.to)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send() {
+        use PageState::*;
+        let mut page = ScriptedPage::new([LoginTip, Talking, Talking, Ready]);
+        let mut machine = PageMachine::new(false);
+        machine.send(&mut page, "hello").await.unwrap();
+        assert_eq!(path(&machine), [LoginTip, Talking, Ready]);
+        assert_eq!(
+            page.actions,
+            [
+                Action::CloseLoginTip,
+                Action::Type("hello".to_string()),
+                Action::Click
+            ]
+        );
+        assert_eq!(machine.transitions()[2].after, POLL * 2);
+        assert_eq!(page.steps, ["LoginTip", "Talking", "Ready"]);
+
+        // an answer streaming in has the chatbox open
+        let mut page = ScriptedPage::new([Unknown, Talking]);
+        machine.open(&mut page).await.unwrap();
+        assert_eq!(path(&machine), [Talking]);
+        assert!(page.actions.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_sending() {
+        use PageState::*;
+        let timeouts = PageTimeouts::default();
+        let mut page = ScriptedPage::new([Ready, Sending]);
+        page.send_enabled = false;
+        let mut machine = PageMachine::new(false);
+        let start = Instant::now();
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Stuck)
+        );
+        assert_eq!(path(&machine), [Ready, Sending]);
+        assert!(start.elapsed() >= timeouts.sending);
+        assert!(start.elapsed() < timeouts.sending + POLL * 2);
+
+        // the send button never comes back
+        let mut page = ScriptedPage::new([Ready]);
+        page.send_enabled = false;
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Stuck)
+        );
+        assert_eq!(path(&machine), [Ready]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeated_403() {
+        use PageState::*;
+        // the answer fails as soon as the message is sent again
+        let mut page = ScriptedPage::new([NeedReopen, Sending].repeat(10));
+        let mut machine = PageMachine::new(false);
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Stuck)
+        );
+        let max_reopens = PageTimeouts::default().max_reopens;
+        let reopens = path(&machine)
+            .into_iter()
+            .filter(|state| *state == NeedReopen)
+            .count();
+        assert_eq!(reopens, max_reopens + 1);
+        assert_eq!(page.actions.len(), max_reopens);
+        assert_eq!(page.actions[..REGENERATIONS], vec![Action::Regenerate; 2]);
+        assert_eq!(page.actions[REGENERATIONS], Action::Refresh);
+
+        // it comes back in the end
+        let mut script = [NeedReopen, Sending].repeat(2);
+        script.push(Ready);
+        let mut page = ScriptedPage::new(script);
+        machine.send(&mut page, "hello").await.unwrap();
+        assert_eq!(page.actions[..2], vec![Action::Regenerate; 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unavailable() {
+        use PageState::*;
+        let mut machine = PageMachine::new(true);
+        let mut page = ScriptedPage::new([Tired]);
+        machine.open(&mut page).await.unwrap();
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Tired)
+        );
+        let mut page = ScriptedPage::new([LoggingIn]);
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::LoggedOut)
+        );
+        assert!(page.actions.is_empty());
+
+        // a tired page is left to the rate limiter, whether or not
+        let mut machine = PageMachine::new(false);
+        let mut page = ScriptedPage::new([Tired, Ready]);
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Tired)
+        );
+        assert!(page.actions.is_empty());
+
+        // a login is waited for until its timeout
+        let mut page = ScriptedPage::new([LoggingIn]);
+        assert_eq!(machine.open(&mut page).await, Err(Unavailable::LoggedOut));
+        assert!(page.actions.iter().all(|action| *action == Action::LogIn));
+
+        // or left to somebody at the browser
+        let mut machine = PageMachine::new(false).manual_login();
+        let mut page = ScriptedPage::new([LoggingIn, LoggingIn, Ready]);
+        machine.open(&mut page).await.unwrap();
+        assert!(page.actions.is_empty());
+        let mut page = ScriptedPage::new([LoggingIn]);
+        assert_eq!(machine.open(&mut page).await, Err(Unavailable::LoggedOut));
+
+        let mut page = ScriptedPage::new([Ready]);
+        page.lost = true;
+        assert_eq!(machine.open(&mut page).await, Err(Unavailable::Lost));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stale_limit() {
+        use PageState::*;
+        let hello = [Action::Type("hello".to_string()), Action::Click];
+        // the notice answering the last message stays after the limit resets
+        let mut page = ScriptedPage::new([Tired]);
+        page.answers = 3;
+        let mut machine = PageMachine::new(false);
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Tired)
+        );
+        assert_eq!(machine.answered(), Some(3));
+        assert!(page.actions.is_empty());
+
+        // waited out by the caller, the message goes in spite of it
+        let mut machine = PageMachine::new(false).with_answered(machine.answered());
+        machine.send(&mut page, "hello").await.unwrap();
+        assert_eq!(path(&machine), [Ready]);
+        assert_eq!(page.actions, hello);
+
+        // a new notice answers it
+        page.answers = 4;
+        assert_eq!(
+            machine.send(&mut page, "hello").await,
+            Err(Unavailable::Tired)
+        );
+        assert_eq!(page.actions, hello);
+        machine.send(&mut page, "hello").await.unwrap();
+        assert_eq!(page.actions, [hello.clone(), hello].concat());
+    }
+}
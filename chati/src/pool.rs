@@ -0,0 +1,335 @@
+use futures::{stream, Future, FutureExt, Stream};
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::backend::BackendError;
+use crate::chati::Chati;
+use crate::config::BackendConfig;
+
+use tracing::{debug, error, info};
+
+/// An item of [`SessionPool::run`] which no session came back from
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("item {seq} is lost: {reason}")]
+pub struct Lost {
+    /// where the item was in the items
+    pub seq: usize,
+    pub reason: String,
+}
+
+/// Several [`Chati`] working through one queue, each with a backend of its
+/// own: for the `web` backend a webdriver session, a debugging port and a
+/// tab, see [`BackendConfig::session`].
+pub struct SessionPool {
+    sessions: Vec<Chati>,
+}
+
+impl SessionPool {
+    /// Start `size` sessions of `config`. A session failing to start is left
+    /// out, as long as one starts.
+    pub async fn start(config: &BackendConfig, size: usize) -> Result<Self, BackendError> {
+        let mut sessions = Vec::new();
+        let mut failure = None;
+        for index in 0..size.max(1) {
+            match Chati::new(&config.session(index)).await {
+                Ok(chati) => sessions.push(chati),
+                Err(error) => {
+                    error!("start session {index}: {error}");
+                    failure = Some(error);
+                }
+            }
+        }
+        match failure {
+            Some(error) if sessions.is_empty() => Err(error),
+            _ => {
+                info!("{} sessions started", sessions.len());
+                Ok(SessionPool { sessions })
+            }
+        }
+    }
+
+    pub fn with_sessions(sessions: Vec<Chati>) -> Self {
+        SessionPool { sessions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// to start the conversation of every session before [`SessionPool::run`]
+    pub fn sessions_mut(&mut self) -> &mut [Chati] {
+        &mut self.sessions
+    }
+
+    /// Hand `items` to whichever session is idle, `work` doing one of them
+    /// with the session and giving it back. The results come in the order of
+    /// `items` however the sessions go, and the sessions end once the items
+    /// run out.
+    ///
+    /// A session panicking in `work` is gone, its item comes as [`Lost`].
+    /// Once no session is left, so do the items not taken yet. Such a
+    /// session is dropped without [`Chati::end`]: the `web` backend stops
+    /// listening to its tab, and its browser closes with the webdriver
+    /// session.
+    pub fn run<T, R, F, Fut>(
+        self,
+        items: impl IntoIterator<Item = T>,
+        work: F,
+    ) -> impl Stream<Item = Result<R, Lost>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(Chati, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = (Chati, R)> + Send + 'static,
+    {
+        let queue = items.into_iter().enumerate().collect::<VecDeque<_>>();
+        let queue = Arc::new(Mutex::new(queue));
+        let work = Arc::new(work);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let alive = Arc::new(AtomicUsize::new(self.sessions.len()));
+        for (session, mut chati) in self.sessions.into_iter().enumerate() {
+            let queue = Arc::clone(&queue);
+            let work = Arc::clone(&work);
+            let alive = Arc::clone(&alive);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let next = queue.lock().unwrap().pop_front();
+                    let Some((seq, item)) = next else {
+                        break;
+                    };
+                    debug!("session {session} takes item {seq}");
+                    let result = match AssertUnwindSafe(work(chati, item)).catch_unwind().await {
+                        Ok((back, result)) => {
+                            chati = back;
+                            Ok(result)
+                        }
+                        Err(panic) => {
+                            let reason = panic_message(&*panic);
+                            error!("session {session} panicked at item {seq}: {reason}");
+                            let _ = tx.send((seq, Err(Lost { seq, reason })));
+                            if alive.fetch_sub(1, Ordering::AcqRel) == 1 {
+                                let rest = std::mem::take(&mut *queue.lock().unwrap());
+                                for (seq, _) in rest {
+                                    let reason = "no session is left".to_string();
+                                    let _ = tx.send((seq, Err(Lost { seq, reason })));
+                                }
+                            }
+                            return;
+                        }
+                    };
+                    // nobody waits for the results any more
+                    if tx.send((seq, result)).is_err() {
+                        break;
+                    }
+                }
+                alive.fetch_sub(1, Ordering::AcqRel);
+                chati.end().await;
+            });
+        }
+        in_order(rx)
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+/// The results numbered from 0 of `rx`, in the order of their numbers.
+/// What is left once `rx` closes comes in order, over the numbers never
+/// received.
+fn in_order<R>(rx: mpsc::UnboundedReceiver<(usize, R)>) -> impl Stream<Item = R> {
+    let done = BTreeMap::new();
+    stream::unfold((rx, done, 0), |(mut rx, mut done, next)| async move {
+        loop {
+            if let Some(result) = done.remove(&next) {
+                return Some((result, (rx, done, next + 1)));
+            }
+            match rx.recv().await {
+                Some((seq, result)) => {
+                    done.insert(seq, result);
+                }
+                None => {
+                    let (seq, result) = done.pop_first()?;
+                    error!("no result for {next}..{seq}");
+                    return Some((result, (rx, done, seq + 1)));
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ChatBackend, Reply};
+    use async_trait::async_trait;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    /// answers what is said, the later the longer it is, and panics when
+    /// told to
+    struct Echo {
+        replies: Option<mpsc::UnboundedSender<Reply>>,
+    }
+
+    #[async_trait]
+    impl ChatBackend for Echo {
+        async fn new_conversation(
+            &mut self,
+            _auto_login: bool,
+            replies: mpsc::UnboundedSender<Reply>,
+        ) -> Result<(), BackendError> {
+            self.replies = Some(replies);
+            Ok(())
+        }
+
+        async fn isaid(&mut self, said: &str) -> Result<(), BackendError> {
+            let replies = self.replies.clone().ok_or(BackendError::NoConversation)?;
+            if said == "panic" {
+                panic!("told to panic");
+            }
+            let delay = Duration::from_millis(said.len() as u64 * 10);
+            let said = said.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = replies.send(Reply::Words(said));
+                let _ = replies.send(Reply::End);
+            });
+            Ok(())
+        }
+
+        async fn end(self: Box<Self>) -> Result<(), BackendError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool() {
+        let pool = echo_sessions(3).await;
+        assert_eq!(pool.len(), 3);
+
+        // the first ones take the longest
+        let items = (1..=9).rev().map(|n| "x".repeat(n)).collect::<Vec<_>>();
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        let results = pool.run(items.clone(), move |chati, said: String| {
+            let done_tx = done_tx.clone();
+            async move {
+                let (chati, heard) = echo(chati, said).await;
+                let _ = done_tx.send(heard.len());
+                (chati, heard)
+            }
+        });
+        let results = results.collect::<Vec<_>>().await;
+        assert_eq!(results, items.into_iter().map(Ok).collect::<Vec<_>>());
+
+        // done out of order, by the sessions idle
+        let mut done = Vec::new();
+        while let Some(n) = done_rx.recv().await {
+            done.push(n);
+        }
+        assert_eq!(done.len(), 9);
+        assert_eq!(done[..3], [7, 8, 9]);
+    }
+
+    async fn echo(mut chati: Chati, said: String) -> (Chati, String) {
+        chati.isaid(&said).await.unwrap();
+        let mut heard = String::new();
+        chati
+            .hesaid(|words| {
+                heard.push_str(&words.unwrap_or_default());
+                async {}
+            })
+            .await;
+        (chati, heard)
+    }
+
+    async fn echo_sessions(count: usize) -> SessionPool {
+        let mut sessions = Vec::new();
+        for _ in 0..count {
+            let mut chati = Chati::with_backend(Box::new(Echo { replies: None }));
+            chati.new_converstation(false).await.unwrap();
+            sessions.push(chati);
+        }
+        SessionPool::with_sessions(sessions)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicked() {
+        let lost = |seq: usize, reason: &str| {
+            Err(Lost {
+                seq,
+                reason: reason.to_string(),
+            })
+        };
+
+        // the other session carries on
+        let items = ["xx", "panic", "xxxxxx", "x", "xxx"].map(String::from);
+        let results = echo_sessions(2).await.run(items.clone(), echo);
+        assert_eq!(
+            results.collect::<Vec<_>>().await,
+            vec![
+                Ok(items[0].clone()),
+                lost(1, "told to panic"),
+                Ok(items[2].clone()),
+                Ok(items[3].clone()),
+                Ok(items[4].clone()),
+            ]
+        );
+
+        // with no session left the rest are lost as well
+        let items = ["x", "panic", "xx", "xxx"].map(String::from);
+        let results = echo_sessions(1).await.run(items.clone(), echo);
+        assert_eq!(
+            results.collect::<Vec<_>>().await,
+            vec![
+                Ok(items[0].clone()),
+                lost(1, "told to panic"),
+                lost(2, "no session is left"),
+                lost(3, "no session is left"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_config() {
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [backend]
+            kind = "web"
+            user_data_dir = "profiles/chrome"
+            record = "events.jsonl"
+
+            [[backend.rate_limit.profiles]]
+            user_data_dir = "chrome-2"
+            "#,
+        )
+        .unwrap();
+        let BackendConfig::Web(first) = config.backend.session(0) else {
+            panic!("not a web backend");
+        };
+        assert_eq!(first.browser.debugging_port, 9222);
+        assert_eq!(first.record, Some("events.jsonl".into()));
+        let BackendConfig::Web(web) = config.backend.session(2) else {
+            panic!("not a web backend");
+        };
+        assert_eq!(web.browser.debugging_port, 9224);
+        assert_eq!(web.browser.user_data_dir, Some("profiles/chrome-2".into()));
+        assert_eq!(
+            web.rate_limit.profiles[0].user_data_dir,
+            Some("chrome-2-2".into())
+        );
+        assert_eq!(web.record, Some("events-2.jsonl".into()));
+    }
+}
@@ -0,0 +1,356 @@
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone};
+use regex::Regex;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::browser::BrowserConfig;
+
+use tracing::info;
+
+/// "You've reached our limit of messages per hour", "You've hit your
+/// limit", "You've reached the current usage cap for GPT-4"
+static LIMIT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^you(?:'ve|’ve| have) (?:reached|hit)\b.*\b(?:limit|cap)\b").unwrap()
+});
+/// "please try again after 2:07 PM", "your limit resets after 17:51"
+static AFTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bafter (\d{1,2}):(\d{2})(?:\s*([ap])\.?m\b\.?)?").unwrap());
+/// "try again in 1 hour and 5 minutes"
+static IN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\bin ((?:\d+\s*(?:hours?|hrs?|minutes?|mins?|seconds?|secs?)(?:,?\s*(?:and\s+)?)?)+)",
+    )
+    .unwrap()
+});
+static AMOUNT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(\d+)\s*(h|m|s)").unwrap());
+
+/// the assistant answered that it takes no more messages for a while
+pub fn is_limit_message(text: &str) -> bool {
+    let text = text.trim();
+    // an answer talking about some limit is longer
+    text.len() < 300 && LIMIT.is_match(text)
+}
+
+/// How long until the limit told by `message` resets, when it tells.
+/// A time of the day already past `now` is the one of the next day.
+pub fn parse_reset<Tz: TimeZone>(message: &str, now: &DateTime<Tz>) -> Option<Duration> {
+    if let Some(caps) = AFTER.captures(message) {
+        let mut hour = caps[1].parse::<u32>().ok()?;
+        let minute = caps[2].parse::<u32>().ok()?;
+        match caps.get(3).map(|m| m.as_str().to_ascii_lowercase()) {
+            Some(_) if hour == 0 || hour > 12 => return None,
+            Some(half) if half == "p" => hour = hour % 12 + 12,
+            Some(_) => hour %= 12,
+            None => {}
+        }
+        let time = now.date_naive().and_hms_opt(hour, minute, 0)?;
+        let mut reset = now.timezone().from_local_datetime(&time).earliest()?;
+        if reset <= *now {
+            reset += ChronoDuration::days(1);
+        }
+        return (reset - now.clone()).to_std().ok();
+    }
+    let amounts = IN.captures(message)?;
+    let mut reset = Duration::ZERO;
+    for caps in AMOUNT.captures_iter(&amounts[1]) {
+        let amount = caps[1].parse::<u64>().ok()?;
+        let unit = match caps[2].to_ascii_lowercase().as_str() {
+            "h" => 3600,
+            "m" => 60,
+            _ => 1,
+        };
+        reset += Duration::from_secs(amount * unit);
+    }
+    Some(reset)
+}
+
+/// Another account to go on with while the others are limited. What it
+/// leaves out is taken from the browser of the backend.
+///
+/// ```toml
+/// [[backend.rate_limit.profiles]]
+/// user_data_dir = "chrome-profile-2"
+/// proxy = "socks5://127.0.0.1:1080"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub user_data_dir: Option<PathBuf>,
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Profile {
+    /// `browser` launched with this profile
+    pub fn apply(&self, browser: &BrowserConfig) -> BrowserConfig {
+        let mut browser = browser.clone();
+        if let Some(user_data_dir) = &self.user_data_dir {
+            browser.user_data_dir = Some(user_data_dir.clone());
+        }
+        if let Some(proxy) = &self.proxy {
+            browser.proxy = Some(proxy.clone());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            browser.user_agent = user_agent.clone();
+        }
+        browser
+    }
+}
+
+/// What is done when the assistant takes no more messages, the `rate_limit`
+/// table of a `web` backend
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// waited the first time the limit tells no reset time, doubled every
+    /// time after
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// gone on with in turn, after the browser of the backend
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            initial_backoff_secs: 60,
+            max_backoff_secs: 3600,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+/// What to do about a limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// launch the browser with another profile
+    pub switch_to: Option<BrowserConfig>,
+    /// before saying anything again
+    pub wait: Duration,
+}
+
+struct ProfileState {
+    browser: BrowserConfig,
+    /// limited in a row without a reset time
+    strikes: u32,
+    limited_until: Option<Instant>,
+}
+
+/// Keeps track of the limits of the browser profiles, and picks the one to
+/// go on with.
+pub struct RateLimiter {
+    initial: Duration,
+    max: Duration,
+    profiles: Vec<ProfileState>,
+    current: usize,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig, browser: &BrowserConfig) -> Self {
+        let browsers = std::iter::once(browser.clone())
+            .chain(config.profiles.iter().map(|profile| profile.apply(browser)));
+        RateLimiter {
+            initial: Duration::from_secs(config.initial_backoff_secs),
+            max: Duration::from_secs(config.max_backoff_secs),
+            profiles: browsers
+                .map(|browser| ProfileState {
+                    browser,
+                    strikes: 0,
+                    limited_until: None,
+                })
+                .collect(),
+            current: 0,
+        }
+    }
+
+    /// a message went through
+    pub fn passed(&mut self) {
+        let profile = &mut self.profiles[self.current];
+        profile.strikes = 0;
+        profile.limited_until = None;
+    }
+
+    /// The current profile is limited, for `reset` when the limit tells.
+    /// Otherwise it is waited out a backoff doubling every time.
+    pub fn limited(&mut self, reset: Option<Duration>) -> Backoff {
+        let now = Instant::now();
+        let profile = &mut self.profiles[self.current];
+        let wait = reset.unwrap_or_else(|| {
+            let backoff = self.initial.saturating_mul(1 << profile.strikes.min(16));
+            profile.strikes += 1;
+            backoff.min(self.max)
+        });
+        profile.limited_until = Some(now + wait);
+
+        // the profile free the soonest, the next ones first
+        let count = self.profiles.len();
+        let (next, wait) = (1..=count)
+            .map(|i| (self.current + i) % count)
+            .map(|i| {
+                let until = self.profiles[i].limited_until;
+                (
+                    i,
+                    until.map_or(Duration::ZERO, |until| until - now.min(until)),
+                )
+            })
+            .min_by_key(|(_, wait)| *wait)
+            .expect("a profile at least");
+        let switch_to = (next != self.current).then(|| {
+            info!("switch to browser profile {next}");
+            self.current = next;
+            self.profiles[next].browser.clone()
+        });
+        Backoff { switch_to, wait }
+    }
+}
+
+/// hh:mm:ss
+pub fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Count `wait` down on stderr, and return once it is over.
+pub async fn countdown(wait: Duration) {
+    let end = Instant::now() + wait;
+    let mut stderr = std::io::stderr();
+    loop {
+        let left = end.saturating_duration_since(Instant::now());
+        let _ = write!(stderr, "\rrate limited, going on in {}", clock(left));
+        let _ = stderr.flush();
+        if left.is_zero() {
+            let _ = writeln!(stderr);
+            return;
+        }
+        tokio::time::sleep(left.min(Duration::from_secs(1))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BackendConfig, Config};
+    use chrono::{FixedOffset, Utc};
+
+    #[test]
+    fn test_parse_reset() {
+        let now = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 13, 30, 0)
+            .unwrap();
+        let hour_limit = "You've reached our limit of messages per hour. Please try again later.";
+        assert!(is_limit_message(hour_limit));
+        assert_eq!(parse_reset(hour_limit, &now), None);
+
+        let cap = "You've reached the current usage cap for GPT-4, please try again after 2:07 PM.";
+        assert!(is_limit_message(cap));
+        assert_eq!(parse_reset(cap, &now), Some(Duration::from_secs(37 * 60)));
+        // a time past is the one of tomorrow
+        let resets = "You’ve hit your limit. Your limit resets after 9:00 am.";
+        assert!(is_limit_message(resets));
+        assert_eq!(
+            parse_reset(resets, &now),
+            Some(Duration::from_secs((24 * 60 - 270) * 60))
+        );
+        assert_eq!(
+            parse_reset("the limit resets after 13:45", &now),
+            Some(Duration::from_secs(15 * 60))
+        );
+        assert_eq!(
+            parse_reset("Please try again in 1 hour and 5 minutes.", &Utc::now()),
+            Some(Duration::from_secs(3900))
+        );
+        assert_eq!(parse_reset("try again after 25:00 pm", &now), None);
+
+        assert!(!is_limit_message("A buffer that has reached its limit."));
+        assert!(!is_limit_message(&format!(
+            "{hour_limit}{}",
+            " ".repeat(300) + "x"
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_limiter() {
+        let config: Config = toml::from_str(
+            r#"
+            [backend]
+            kind = "web"
+            debugging_port = 9333
+
+            [backend.rate_limit]
+            max_backoff_secs = 600
+
+            [[backend.rate_limit.profiles]]
+            user_data_dir = "second"
+            "#,
+        )
+        .unwrap();
+        let BackendConfig::Web(web) = config.backend else {
+            panic!("not a web backend: {:?}", config.backend);
+        };
+        let (browser, config) = (web.browser, web.rate_limit);
+        assert_eq!(config.initial_backoff_secs, 60);
+        assert_eq!(config.max_backoff_secs, 600);
+        let second = config.profiles[0].apply(&browser);
+        assert_eq!(second.user_data_dir, Some("second".into()));
+        assert_eq!(second.debugging_port, 9333);
+
+        let mut limiter = RateLimiter::new(&config, &browser);
+        // the other profile is free
+        assert_eq!(
+            limiter.limited(None),
+            Backoff {
+                switch_to: Some(second.clone()),
+                wait: Duration::ZERO
+            }
+        );
+        // both are limited, the first one is free the soonest
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(
+            limiter.limited(None),
+            Backoff {
+                switch_to: Some(browser.clone()),
+                wait: Duration::from_secs(50)
+            }
+        );
+        // it is limited again, for longer
+        tokio::time::advance(Duration::from_secs(50)).await;
+        assert_eq!(
+            limiter.limited(None),
+            Backoff {
+                switch_to: Some(second.clone()),
+                wait: Duration::from_secs(10)
+            }
+        );
+        assert_eq!(
+            limiter.limited(Some(Duration::from_secs(30 * 60))),
+            Backoff {
+                switch_to: Some(browser.clone()),
+                wait: Duration::from_secs(120)
+            }
+        );
+
+        // a single profile backs off exponentially, up to the max
+        let config = RateLimitConfig {
+            max_backoff_secs: 300,
+            ..RateLimitConfig::default()
+        };
+        let mut limiter = RateLimiter::new(&config, &browser);
+        let waits = (0..5)
+            .map(|_| limiter.limited(None))
+            .map(|backoff| {
+                assert_eq!(backoff.switch_to, None);
+                backoff.wait.as_secs()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(waits, [60, 120, 240, 300, 300]);
+        limiter.passed();
+        assert_eq!(limiter.limited(None).wait, Duration::from_secs(60));
+
+        assert_eq!(clock(Duration::from_secs(3723)), "01:02:03");
+    }
+}
@@ -0,0 +1,297 @@
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Completer, Helper, Highlighter, Hinter};
+use std::path::PathBuf;
+
+/// A line of it begins and ends a message of several lines
+pub const TERMINATOR: &str = r#"""""#;
+
+pub const HELP: &str = r#"Say something, or begin a line with """ to say several lines until another """.
+
+\new             start a new conversation
+\retry           say the last message again, forgetting its answer
+\save <file>     save the transcript, as Markdown when the file ends with .md
+\load <file>     say what is in the file
+\system <text>   set the instructions of the rest of the conversation
+\history         list the turns so far
+\help            show this
+\q               quit"#;
+
+/// What begins with a backslash
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    New,
+    Retry,
+    Save(PathBuf),
+    Load(PathBuf),
+    System(String),
+    History,
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// `None` when `input` is a message rather than a command
+    pub fn parse(input: &str) -> Option<Result<Command, String>> {
+        let rest = input.trim().strip_prefix('\\')?;
+        let (name, arg) = match rest.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (rest, ""),
+        };
+        let command = match (name, arg) {
+            ("new", "") => Command::New,
+            ("retry", "") => Command::Retry,
+            ("save", file) if !file.is_empty() => Command::Save(file.into()),
+            ("load", file) if !file.is_empty() => Command::Load(file.into()),
+            ("system", text) if !text.is_empty() => Command::System(text.to_string()),
+            ("history", "") => Command::History,
+            ("help", "") => Command::Help,
+            ("q" | "quit", "") => Command::Quit,
+            _ => return Some(Err(format!("can not understand \\{rest}, see \\help"))),
+        };
+        Some(Ok(command))
+    }
+}
+
+/// the input is a message of several lines not terminated yet
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lines = input.lines().map(str::trim);
+    lines.next() == Some(TERMINATOR) && lines.next_back() != Some(TERMINATOR)
+}
+
+/// the message of `input`, without the terminator lines
+pub fn message(input: &str) -> String {
+    let lines = input.lines().collect::<Vec<_>>();
+    match lines.as_slice() {
+        [first, body @ .., last] if first.trim() == TERMINATOR && last.trim() == TERMINATOR => {
+            body.join("\n")
+        }
+        _ => input.trim().to_string(),
+    }
+}
+
+/// Lets rustyline go on reading the lines of a message until it is terminated
+#[derive(Completer, Helper, Highlighter, Hinter)]
+pub struct InputHelper;
+
+impl Validator for InputHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const CODE: &str = "\x1b[36m";
+const HEADING: &str = "\x1b[1;35m";
+
+/// Colors Markdown as it streams in: headings, ``` blocks, `code` and
+/// **bold**. A few characters are held back until it is clear what they
+/// are, [`MarkdownHighlighter::finish`] hands them out.
+#[derive(Debug, Default)]
+pub struct MarkdownHighlighter {
+    /// past the head of the line
+    in_line: bool,
+    /// the head of the line while it may become a fence
+    head: String,
+    fence: bool,
+    fence_line: bool,
+    heading: bool,
+    code: bool,
+    bold: bool,
+    /// a `*` which may begin a `**`
+    star: bool,
+}
+
+impl MarkdownHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, text: &str) -> String {
+        let mut out = String::new();
+        for c in text.chars() {
+            self.push_char(c, &mut out);
+        }
+        out
+    }
+
+    /// the characters held back, and the colors reset
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        let head = std::mem::take(&mut self.head);
+        self.in_line = true;
+        for c in head.chars() {
+            self.inline(c, &mut out);
+        }
+        if self.star {
+            out.push('*');
+        }
+        if self.fence || self.fence_line || self.heading || self.code || self.bold {
+            out.push_str(RESET);
+        }
+        *self = Self::default();
+        out
+    }
+
+    fn style(&self) -> String {
+        let mut style = RESET.to_string();
+        if self.heading {
+            style.push_str(HEADING);
+        } else if self.bold {
+            style.push_str(BOLD);
+        }
+        if self.fence || self.fence_line || self.code {
+            style.push_str(CODE);
+        }
+        style
+    }
+
+    fn push_char(&mut self, c: char, out: &mut String) {
+        if self.in_line {
+            return self.inline(c, out);
+        }
+        self.head.push(c);
+        if "```".starts_with(self.head.as_str()) {
+            if self.head == "```" {
+                self.head.clear();
+                self.in_line = true;
+                self.fence = !self.fence;
+                self.fence_line = true;
+                if self.fence {
+                    out.push_str(&self.style());
+                }
+                out.push_str("```");
+            }
+            return;
+        }
+        let head = std::mem::take(&mut self.head);
+        self.in_line = true;
+        if head == "#" && !self.fence {
+            self.heading = true;
+            out.push_str(&self.style());
+            out.push('#');
+            return;
+        }
+        for c in head.chars() {
+            self.inline(c, out);
+        }
+    }
+
+    fn inline(&mut self, c: char, out: &mut String) {
+        if c == '\n' {
+            if std::mem::take(&mut self.star) {
+                out.push('*');
+            }
+            let styled = self.fence_line || self.heading || self.code || self.bold;
+            self.fence_line = false;
+            self.heading = false;
+            self.code = false;
+            self.bold = false;
+            if styled && !self.fence {
+                out.push_str(RESET);
+            }
+            out.push('\n');
+            self.in_line = false;
+            return;
+        }
+        if self.fence || self.fence_line {
+            out.push(c);
+            return;
+        }
+        if c == '*' {
+            if std::mem::take(&mut self.star) {
+                self.bold = !self.bold;
+                if self.bold {
+                    out.push_str(&self.style());
+                    out.push_str("**");
+                } else {
+                    out.push_str("**");
+                    out.push_str(&self.style());
+                }
+            } else {
+                self.star = true;
+            }
+            return;
+        }
+        if std::mem::take(&mut self.star) {
+            out.push('*');
+        }
+        if c == '`' {
+            self.code = !self.code;
+            if self.code {
+                out.push_str(&self.style());
+                out.push('`');
+            } else {
+                out.push('`');
+                out.push_str(&self.style());
+            }
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse("hello"), None);
+        assert_eq!(Command::parse(" \\new "), Some(Ok(Command::New)));
+        assert_eq!(
+            Command::parse("\\save  chat.md"),
+            Some(Ok(Command::Save("chat.md".into())))
+        );
+        assert_eq!(
+            Command::parse("\\system You are a translator."),
+            Some(Ok(Command::System("You are a translator.".to_string())))
+        );
+        assert!(matches!(Command::parse("\\save"), Some(Err(_))));
+        assert!(matches!(Command::parse("\\nothing"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_multi_line() {
+        assert!(!is_incomplete("hello"));
+        assert!(is_incomplete("\"\"\""));
+        assert!(is_incomplete("\"\"\"\nfirst\nsecond"));
+        assert!(!is_incomplete("\"\"\"\nfirst\nsecond\n\"\"\""));
+        assert_eq!(
+            message("\"\"\"\nfirst\n  second\n\"\"\""),
+            "first\n  second"
+        );
+        assert_eq!(message("  hello\n"), "hello");
+    }
+
+    #[test]
+    fn test_highlight() {
+        let text = "# Title\nSome **bold** and `code`.\n```c\n/* `a` **b** */\n```\n2 * 3 done";
+        let mut highlighter = MarkdownHighlighter::new();
+        let mut whole = highlighter.push(text);
+        whole.push_str(&highlighter.finish());
+        assert_eq!(
+            whole,
+            [
+                "\x1b[0m\x1b[1;35m# Title\x1b[0m\n",
+                "Some \x1b[0m\x1b[1m**bold**\x1b[0m and \x1b[0m\x1b[36m`code`\x1b[0m.\n",
+                "\x1b[0m\x1b[36m```c\n/* `a` **b** */\n```\x1b[0m\n",
+                "2 * 3 done",
+            ]
+            .concat()
+        );
+        for piece in 1..text.len() {
+            let mut highlighter = MarkdownHighlighter::new();
+            let mut out = String::new();
+            for chunk in text.as_bytes().chunks(piece) {
+                out.push_str(&highlighter.push(std::str::from_utf8(chunk).unwrap()));
+            }
+            out.push_str(&highlighter.finish());
+            assert_eq!(out, whole, "in pieces of {piece}");
+        }
+    }
+}
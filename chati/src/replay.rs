@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::Path;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::backend::{self, BackendError, ChatBackend, Reply};
+use crate::intercept::NetworkEvent;
+use crate::transcript::Transcript;
+
+use tracing::debug;
+
+/// One line of a recording
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recorded {
+    /// since the recorder was created
+    pub elapsed_ms: u64,
+    pub event: NetworkEvent,
+}
+
+/// Appends the network events of a tab to a file, one json object per line,
+/// for [`ReplayBackend`] to play them again.
+pub struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Recorder {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, event: &NetworkEvent) -> std::io::Result<()> {
+        let recorded = Recorded {
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            event: event.clone(),
+        };
+        let mut line = serde_json::to_string(&recorded).expect("serialize network event");
+        line.push('\n');
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+/// all events of the recording at `path`
+pub fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<Recorded>> {
+    let path = path.as_ref();
+    let mut events = Vec::new();
+    for (lineno, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded = serde_json::from_str(&line).map_err(|error| {
+            let message = format!("line {} of {}: {error}", lineno + 1, path.display());
+            Error::new(ErrorKind::InvalidData, message)
+        })?;
+        events.push(recorded);
+    }
+    Ok(events)
+}
+
+/// Plays a recording through the decoding a web backend does, so the
+/// conversations in it can be had again without a browser.
+///
+/// What is said is only kept. The answers come in the order they were
+/// recorded, the next one for every `hesaid` of [`crate::chati::Chati`].
+pub struct ReplayBackend {
+    events: Vec<NetworkEvent>,
+    said: Vec<String>,
+}
+
+impl ReplayBackend {
+    pub fn new(events: Vec<NetworkEvent>) -> Self {
+        ReplayBackend {
+            events,
+            said: Vec::new(),
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let events = read(path)?
+            .into_iter()
+            .map(|recorded| recorded.event)
+            .collect();
+        Ok(ReplayBackend::new(events))
+    }
+
+    /// what is said so far
+    pub fn said(&self) -> &[String] {
+        &self.said
+    }
+}
+
+#[async_trait]
+impl ChatBackend for ReplayBackend {
+    async fn new_conversation(
+        &mut self,
+        _auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        let events = futures::stream::iter(std::mem::take(&mut self.events));
+        tokio::spawn(backend::forward_replies(events, replies));
+        Ok(())
+    }
+
+    async fn reopen_conversation(
+        &mut self,
+        _transcript: &Transcript,
+        auto_login: bool,
+        replies: mpsc::UnboundedSender<Reply>,
+    ) -> Result<(), BackendError> {
+        self.new_conversation(auto_login, replies).await
+    }
+
+    async fn isaid(&mut self, said: &str) -> Result<(), BackendError> {
+        debug!("replay the answer to {said}");
+        self.said.push(said.to_string());
+        Ok(())
+    }
+
+    async fn end(self: Box<Self>) -> Result<(), BackendError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chati::Chati;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("replay")
+            .join(name)
+    }
+
+    /// what `Chati::hesaid` passes on for each of `said`
+    async fn replay(name: &str, said: &[&str]) -> Vec<Option<String>> {
+        let backend = ReplayBackend::open(fixture(name)).unwrap();
+        let mut ci = Chati::with_backend(Box::new(backend));
+        ci.new_converstation(false).await.unwrap();
+        let mut answers = Vec::new();
+        for said in said {
+            ci.isaid(said).await.unwrap();
+            let mut answer = Some(String::new());
+            ci.hesaid(|words| {
+                match words {
+                    Some(words) => answer.get_or_insert_with(String::new).push_str(&words),
+                    None => answer = None,
+                }
+                async {}
+            })
+            .await;
+            answers.push(answer);
+        }
+        ci.end().await;
+        answers
+    }
+
+    #[tokio::test]
+    async fn test_multi_part_replies() {
+        assert_eq!(
+            replay("multi_part.jsonl", &["translate", "and this"]).await,
+            vec![
+                Some("```c\n/*\n * 返回 shard 的 placement。\n */\n```".to_string()),
+                Some("```c\n// 辅助函数\n```".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_403_retries() {
+        // the sentinel and the conversation both fail, said nothing once
+        assert_eq!(
+            replay("retry_403.jsonl", &["translate", "translate"]).await,
+            vec![None, Some("```c\n/* 游标 */\n```".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_finished() {
+        assert_eq!(
+            replay("duplicate_finished.jsonl", &["one", "two"]).await,
+            vec![Some("一".to_string()), Some("二".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("record-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let events = vec![
+            NetworkEvent::Response {
+                request_id: "r1".to_string(),
+                url: "https://chatgpt.com/backend-anon/conversation".to_string(),
+                status: 200,
+            },
+            NetworkEvent::Data {
+                request_id: "r1".to_string(),
+                data: "data: 中".as_bytes()[..7].to_vec(),
+            },
+        ];
+        let mut recorder = Recorder::create(&path).unwrap();
+        for event in &events {
+            recorder.record(event).unwrap();
+        }
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains(r#""kind":"data","request_id":"r1","data":"ZGF0YTog5A==""#));
+        let recorded = read(&path).unwrap();
+        assert_eq!(
+            recorded.into_iter().map(|r| r.event).collect::<Vec<_>>(),
+            events
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,668 @@
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::backend::BackendError;
+use crate::chati::Chati;
+use crate::completions::ChatMessage;
+use crate::config::ServerConfig;
+use crate::transcript::{Role, Transcript};
+
+use tracing::{debug, info};
+
+#[derive(Deserialize, Debug)]
+pub struct CompletionRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// An error answered the way OpenAI does, `{"error": {...}}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub kind: &'static str,
+    pub code: &'static str,
+    pub message: String,
+    /// told as `Retry-After`
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    fn invalid(message: &str) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            kind: "invalid_request_error",
+            code: "invalid_messages",
+            message: message.to_string(),
+            retry_after: None,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({"error": {"message": self.message, "type": self.kind, "code": self.code}})
+    }
+}
+
+impl From<BackendError> for ApiError {
+    fn from(error: BackendError) -> Self {
+        let retry_after = match error {
+            BackendError::Tired { retry_after } => retry_after,
+            _ => None,
+        };
+        let (status, kind, code) = match error {
+            BackendError::Tired { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error", "tired")
+            }
+            BackendError::LoggedOut => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                "logged_out",
+            ),
+            _ => (StatusCode::BAD_GATEWAY, "server_error", "backend_error"),
+        };
+        ApiError {
+            status,
+            kind,
+            code,
+            message: error.to_string(),
+            retry_after,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(self.to_json())).into_response();
+        if let Some(retry_after) = self.retry_after {
+            // whole seconds, rounded up
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+/// What the exchange of a request hands to its response
+#[derive(Debug)]
+enum Piece {
+    Words(String),
+    Failed(ApiError),
+    Done,
+}
+
+struct Served {
+    /// one web session serves one request at a time, the others wait in
+    /// the order they came
+    chati: Arc<Mutex<Chati>>,
+    waiting: AtomicUsize,
+    completions: AtomicU64,
+    config: ServerConfig,
+}
+
+/// counts a request among the waiting ones until dropped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Serve `chati` as `POST /v1/chat/completions` and `GET /v1/models`.
+///
+/// A request going on with the conversation of the session says only its
+/// last message. Any other starts a new conversation, with the former
+/// messages told along with the last one.
+pub fn router(chati: Chati, config: &ServerConfig) -> Router {
+    let served = Arc::new(Served {
+        chati: Arc::new(Mutex::new(chati)),
+        waiting: AtomicUsize::new(0),
+        completions: AtomicU64::new(0),
+        config: config.clone(),
+    });
+    Router::new()
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/models", get(models))
+        .with_state(served)
+}
+
+async fn models(State(served): State<Arc<Served>>) -> Json<Value> {
+    Json(json!({"object": "list", "data": [
+        {"id": served.config.model, "object": "model", "owned_by": "chati"}]}))
+}
+
+async fn chat_completions(
+    State(served): State<Arc<Served>>,
+    request: Result<Json<CompletionRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(request) = request.map_err(|rejection| ApiError {
+        status: rejection.status(),
+        ..ApiError::invalid(&rejection.body_text())
+    })?;
+    let Some(said) = request.messages.last() else {
+        return Err(ApiError::invalid("messages must not be empty"));
+    };
+    if said.role != "user" {
+        return Err(ApiError::invalid("the last message must be from the user"));
+    }
+    let model = request.model.clone().unwrap_or(served.config.model.clone());
+    let id = format!(
+        "chatcmpl-{}",
+        served.completions.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let chati = match Arc::clone(&served.chati).try_lock_owned() {
+        Ok(chati) => chati,
+        Err(_) => {
+            let waiting = served.waiting.fetch_add(1, Ordering::AcqRel);
+            let _waiting = Waiting(&served.waiting);
+            if waiting >= served.config.max_queue {
+                return Err(ApiError {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    kind: "rate_limit_error",
+                    code: "queue_full",
+                    message: format!("{waiting} requests are waiting already"),
+                    retry_after: None,
+                });
+            }
+            debug!("{id} waits behind {waiting}");
+            Arc::clone(&served.chati).lock_owned().await
+        }
+    };
+
+    // the exchange goes on when the client leaves, or the answer would be
+    // heard by the next request
+    let (tx, rx) = mpsc::unbounded_channel();
+    let retries = served.config.retries;
+    let messages = request.messages.clone();
+    tokio::spawn(async move {
+        let mut chati = chati;
+        let (said, history) = messages.split_last().expect("checked messages");
+        let piece = match exchange(&mut chati, history, &said.content, retries, &tx).await {
+            Ok(()) => Piece::Done,
+            Err(error) => Piece::Failed(error),
+        };
+        let _ = tx.send(piece);
+    });
+    let mut pieces = UnboundedReceiverStream::new(rx);
+
+    // fail with a status until the first words
+    let first = pieces.next().await.unwrap_or(Piece::Done);
+    if let Piece::Failed(error) = first {
+        return Err(error);
+    }
+    let chunk = Chunk {
+        id,
+        model,
+        created: chrono::Utc::now().timestamp(),
+    };
+
+    if request.stream {
+        let events = stream::once(async { first })
+            .chain(pieces)
+            .flat_map(move |piece| stream::iter(chunk.events(piece)))
+            .map(Ok::<_, Infallible>);
+        return Ok(Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response());
+    }
+
+    let mut answer = String::new();
+    let mut piece = first;
+    loop {
+        match piece {
+            Piece::Words(words) => answer.push_str(&words),
+            Piece::Failed(error) => return Err(error),
+            Piece::Done => break,
+        }
+        piece = pieces.next().await.unwrap_or(Piece::Done);
+    }
+    Ok(Json(chunk.completion(&answer)).into_response())
+}
+
+/// Say the last message of a request and pass the answer to `tx`, saying
+/// it again when nothing is heard. Once words are passed on, an answer cut
+/// off is a failure, or the client would hear them twice.
+async fn exchange(
+    chati: &mut Chati,
+    history: &[ChatMessage],
+    said: &str,
+    retries: usize,
+    tx: &mpsc::UnboundedSender<Piece>,
+) -> Result<(), ApiError> {
+    let prompt = if continues(chati.transcript(), history) {
+        said.to_string()
+    } else {
+        info!(
+            "start a new conversation for {} messages",
+            history.len() + 1
+        );
+        chati.new_converstation(true).await?;
+        let mut told = Vec::new();
+        for message in history {
+            if message.role == "system" && chati.set_system(&message.content).await? {
+                continue;
+            }
+            told.push(message);
+        }
+        fold(&told, said)
+    };
+
+    for _ in 0..=retries {
+        chati.isaid(&prompt).await?;
+        let mut heard = true;
+        let mut passed = false;
+        chati
+            .hesaid(|words| {
+                match words {
+                    Some(words) => {
+                        passed = true;
+                        let _ = tx.send(Piece::Words(words));
+                    }
+                    None => heard = false,
+                }
+                async {}
+            })
+            .await;
+        if heard {
+            return Ok(());
+        }
+        if passed {
+            return Err(ApiError {
+                status: StatusCode::BAD_GATEWAY,
+                kind: "server_error",
+                code: "cut_off",
+                message: "the answer was cut off after its first words".to_string(),
+                retry_after: None,
+            });
+        }
+        info!("nothing heard, say it again");
+    }
+    Err(ApiError {
+        status: StatusCode::BAD_GATEWAY,
+        kind: "server_error",
+        code: "said_nothing",
+        message: format!("nothing heard after saying it {} times", retries + 1),
+        retry_after: None,
+    })
+}
+
+/// the session has had the conversation of `history` so far
+fn continues(transcript: &Transcript, history: &[ChatMessage]) -> bool {
+    transcript.turns.len() == history.len()
+        && transcript.turns.iter().zip(history).all(|(turn, message)| {
+            let role = match turn.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            role == message.role && turn.text.trim() == message.content.trim()
+        })
+}
+
+/// the messages a new conversation has not heard, told before `said`
+fn fold(told: &[&ChatMessage], said: &str) -> String {
+    if told.is_empty() {
+        return said.to_string();
+    }
+    let mut prompt = String::from("The conversation so far:\n\n");
+    for message in told {
+        prompt.push_str(&format!("{}: {}\n\n", message.role, message.content.trim()));
+    }
+    prompt.push_str(&format!("Go on with it, answer this.\n\n{said}"));
+    prompt
+}
+
+/// what every chunk of a completion shares
+struct Chunk {
+    id: String,
+    model: String,
+    created: i64,
+}
+
+impl Chunk {
+    fn events(&self, piece: Piece) -> Vec<Event> {
+        let chunk = |delta: Value, finish_reason: Value| {
+            json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+            })
+        };
+        match piece {
+            Piece::Words(words) => {
+                let delta = json!({"role": "assistant", "content": words});
+                vec![Event::default().data(chunk(delta, Value::Null).to_string())]
+            }
+            // the words so far are all there is, the error goes in the last chunk
+            Piece::Failed(error) => {
+                let mut last = chunk(json!({}), json!("error"));
+                last["error"] = error.to_json()["error"].take();
+                vec![
+                    Event::default().data(last.to_string()),
+                    Event::default().data("[DONE]"),
+                ]
+            }
+            Piece::Done => vec![
+                Event::default().data(chunk(json!({}), json!("stop")).to_string()),
+                Event::default().data("[DONE]"),
+            ],
+        }
+    }
+
+    fn completion(&self, answer: &str) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": answer},
+                "finish_reason": "stop",
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ChatBackend, Reply};
+    use crate::completions::tests::{mock_server, sse_response};
+    use crate::completions::CompletionsBackend;
+    use crate::config::CompletionsConfig;
+    use async_trait::async_trait;
+
+    async fn serve(chati: Chati) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(chati, &ServerConfig::default());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/v1/chat/completions")
+    }
+
+    fn message(role: &str, content: &str) -> Value {
+        json!({"role": role, "content": content})
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions() {
+        let (base_url, mut requests) = mock_server(vec![
+            sse_response(&["Hello", ", world"]),
+            sse_response(&["again"]),
+            sse_response(&["fresh"]),
+        ])
+        .await;
+        let config = CompletionsConfig {
+            base_url,
+            model: "mock".to_string(),
+            api_key_env: None,
+        };
+        let backend = CompletionsBackend::new(&config).unwrap();
+        let mut chati = Chati::with_backend(Box::new(backend));
+        chati.new_converstation(false).await.unwrap();
+        let url = serve(chati).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(&url)
+            .json(&json!({"messages": [message("user", "hi")], "stream": true}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body = response.text().await.unwrap();
+        let data = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect::<Vec<_>>();
+        assert_eq!(data.len(), 4);
+        let first: Value = serde_json::from_str(data[0]).unwrap();
+        assert_eq!(first["object"], "chat.completion.chunk");
+        assert_eq!(first["choices"][0]["delta"]["content"], "Hello");
+        let last: Value = serde_json::from_str(data[2]).unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "stop");
+        assert_eq!(data[3], "[DONE]");
+        requests.recv().await.unwrap();
+
+        // going on with the conversation says the last message only
+        let history = json!([
+            message("user", "hi"),
+            message("assistant", "Hello, world"),
+            message("user", "once more"),
+        ]);
+        let response: Value = client
+            .post(&url)
+            .json(&json!({"model": "m", "messages": history}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["model"], "m");
+        assert_eq!(response["choices"][0]["message"]["content"], "again");
+        let request: Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert_eq!(request["messages"].as_array().unwrap().len(), 3);
+
+        let history = json!([message("system", "Be brief."), message("user", "hi")]);
+        let response: Value = client
+            .post(&url)
+            .json(&json!({"messages": history}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["choices"][0]["message"]["content"], "fresh");
+        let request: Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert_eq!(
+            request["messages"],
+            json!([message("system", "Be brief."), message("user", "hi")])
+        );
+
+        let response = client
+            .post(&url)
+            .json(&json!({"messages": [message("assistant", "hi")]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // an error of the same shape for a body which is not a request
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body("{\"messages\": ")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    /// says `first` and is cut off, then answers in full when said again
+    struct Cut {
+        replies: Option<mpsc::UnboundedSender<Reply>>,
+        first: &'static str,
+        said: usize,
+    }
+
+    impl Cut {
+        async fn serve(first: &'static str) -> String {
+            let cut = Cut {
+                replies: None,
+                first,
+                said: 0,
+            };
+            let mut chati = Chati::with_backend(Box::new(cut));
+            chati.new_converstation(false).await.unwrap();
+            serve(chati).await
+        }
+    }
+
+    #[async_trait]
+    impl ChatBackend for Cut {
+        async fn new_conversation(
+            &mut self,
+            _auto_login: bool,
+            replies: mpsc::UnboundedSender<Reply>,
+        ) -> Result<(), BackendError> {
+            self.replies = Some(replies);
+            Ok(())
+        }
+
+        async fn isaid(&mut self, _said: &str) -> Result<(), BackendError> {
+            let replies = self.replies.clone().ok_or(BackendError::NoConversation)?;
+            self.said += 1;
+            if self.said > 1 {
+                let _ = replies.send(Reply::Words("Hello".to_string()));
+                let _ = replies.send(Reply::End);
+                return Ok(());
+            }
+            if !self.first.is_empty() {
+                let _ = replies.send(Reply::Words(self.first.to_string()));
+            }
+            let _ = replies.send(Reply::Nothing);
+            Ok(())
+        }
+
+        async fn end(self: Box<Self>) -> Result<(), BackendError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream() {
+        let client = reqwest::Client::new();
+        let post = |url: String, stream: bool| {
+            client
+                .post(url)
+                .json(&json!({"messages": [message("user", "hi")], "stream": stream}))
+                .send()
+        };
+        let data = |body: String| {
+            body.lines()
+                .filter_map(|line| line.strip_prefix("data: "))
+                .map(|data| data.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // the words heard are not heard again
+        let url = Cut::serve("Hel").await;
+        let body = post(url, true).await.unwrap().text().await.unwrap();
+        let data = data(body);
+        assert_eq!(data.len(), 3);
+        let first: Value = serde_json::from_str(&data[0]).unwrap();
+        assert_eq!(first["choices"][0]["delta"]["content"], "Hel");
+        let last: Value = serde_json::from_str(&data[1]).unwrap();
+        assert_eq!(last["object"], "chat.completion.chunk");
+        assert_eq!(last["choices"][0]["finish_reason"], "error");
+        assert_eq!(last["error"]["code"], "cut_off");
+        assert_eq!(data[2], "[DONE]");
+
+        let url = Cut::serve("Hel").await;
+        let response = post(url, false).await.unwrap();
+        assert_eq!(response.status(), 502);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "cut_off");
+
+        // cut off before a word, it is said again
+        let url = Cut::serve("").await;
+        let body: Value = post(url, false).await.unwrap().json().await.unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], "Hello");
+    }
+
+    /// a page stuck in `error`
+    struct Unavailable(fn() -> BackendError);
+
+    #[async_trait]
+    impl ChatBackend for Unavailable {
+        async fn new_conversation(
+            &mut self,
+            _auto_login: bool,
+            _replies: mpsc::UnboundedSender<Reply>,
+        ) -> Result<(), BackendError> {
+            Ok(())
+        }
+
+        async fn isaid(&mut self, _said: &str) -> Result<(), BackendError> {
+            Err((self.0)())
+        }
+
+        async fn end(self: Box<Self>) -> Result<(), BackendError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unavailable() {
+        let client = reqwest::Client::new();
+        for (error, status, code, retry_after) in [
+            (
+                (|| BackendError::Tired {
+                    retry_after: Some(Duration::from_millis(1500)),
+                }) as fn() -> BackendError,
+                429,
+                "tired",
+                Some("2"),
+            ),
+            (|| BackendError::LoggedOut, 503, "logged_out", None),
+        ] {
+            let chati = Chati::with_backend(Box::new(Unavailable(error)));
+            let url = serve(chati).await;
+            let response = client
+                .post(&url)
+                .json(&json!({"messages": [message("user", "hi")], "stream": true}))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+            assert_eq!(
+                response
+                    .headers()
+                    .get("retry-after")
+                    .map(|value| value.to_str().unwrap()),
+                retry_after
+            );
+            let body: Value = response.json().await.unwrap();
+            assert_eq!(body["error"]["code"], code);
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let system = ChatMessage {
+            role: "system".to_string(),
+            content: "Be brief.".to_string(),
+        };
+        assert_eq!(fold(&[], "hi"), "hi");
+        assert_eq!(
+            fold(&[&system], "hi"),
+            "The conversation so far:\n\nsystem: Be brief.\n\nGo on with it, answer this.\n\nhi"
+        );
+    }
+}
@@ -0,0 +1,552 @@
+use async_trait::async_trait;
+use chrono::Local;
+use futures::{Stream, StreamExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+use crate::backend::{self, BackendError, Reply};
+use crate::browser::BrowserConfig;
+use crate::chatgpt::{ChatGPT, Unavailable};
+use crate::intercept::NetworkEvent;
+use crate::ratelimit::{self, RateLimiter};
+
+use tracing::{debug, error, info};
+
+/// how long a new browser has to show its tab to the interceptor
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(30);
+/// how long the answer to an initial prompt is waited for
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Passes the answers of a browser session on to chati, and tells the
+/// [`Supervisor`] what it sees of the session on the way.
+#[derive(Clone)]
+pub struct Relay(Arc<RelayState>);
+
+struct RelayState {
+    replies: Mutex<Option<mpsc::UnboundedSender<Reply>>>,
+    /// takes the answers while the initial prompts are said
+    aside: Mutex<Option<mpsc::UnboundedSender<Reply>>>,
+    /// an answer is awaited
+    pending: AtomicBool,
+    /// how many times the tab was attached to
+    attached: watch::Sender<usize>,
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Relay(Arc::new(RelayState {
+            replies: Mutex::new(None),
+            aside: Mutex::new(None),
+            pending: AtomicBool::new(false),
+            attached: watch::Sender::new(0),
+        }))
+    }
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// where the answers go from now on
+    pub fn connect(&self, replies: mpsc::UnboundedSender<Reply>) {
+        *self.0.replies.lock().unwrap() = Some(replies);
+    }
+
+    /// an answer is awaited from now on
+    pub fn said(&self) {
+        self.0.pending.store(true, Ordering::Release);
+    }
+
+    /// nothing is awaited any more
+    fn settle(&self) {
+        self.0.pending.store(false, Ordering::Release);
+    }
+
+    pub fn attached(&self) -> watch::Receiver<usize> {
+        self.0.attached.subscribe()
+    }
+
+    /// take the answers until [`Relay::back`]
+    fn aside(&self) -> mpsc::UnboundedReceiver<Reply> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.0.aside.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    fn back(&self) {
+        *self.0.aside.lock().unwrap() = None;
+    }
+
+    fn deliver(&self, reply: Reply) {
+        if matches!(reply, Reply::End | Reply::Nothing) {
+            self.settle();
+        }
+        let aside = self.0.aside.lock().unwrap().clone();
+        let replies = aside.or_else(|| self.0.replies.lock().unwrap().clone());
+        match replies {
+            Some(replies) => {
+                if let Err(error) = replies.send(reply) {
+                    error!("send response data to inner channel: {error:#?}");
+                }
+            }
+            None => debug!("nobody waits for {reply:?}"),
+        }
+    }
+
+    /// Decode the answers among `events` and pass them on. When the tab is
+    /// detached from while an answer is awaited, the answer is lost, so
+    /// [`Reply::Nothing`] tells the message to be said again.
+    pub async fn run(self, events: impl Stream<Item = NetworkEvent> + Unpin) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let relay = self.clone();
+        let lost = tx.clone();
+        let events = events.inspect(move |event| match event {
+            NetworkEvent::Attached(_) => relay.0.attached.send_modify(|n| *n += 1),
+            NetworkEvent::Detached { reason } if relay.0.pending.load(Ordering::Acquire) => {
+                error!("detached while an answer is awaited: {reason}");
+                let _ = lost.send(Reply::Nothing);
+            }
+            _ => {}
+        });
+        let forward = backend::forward_replies(events, tx);
+        let deliver = async {
+            while let Some(reply) = rx.recv().await {
+                self.deliver(reply);
+            }
+        };
+        tokio::join!(forward, deliver);
+    }
+}
+
+/// The browser a [`Supervisor`] keeps going, a [`ChatGPT`] but in tests
+#[async_trait]
+pub trait Session: Send {
+    /// bring the page to where it takes a message and send `said`
+    async fn send(&mut self, said: &str) -> Result<(), Unavailable>;
+
+    /// what the page told when it became [`Unavailable::Tired`]
+    async fn limit_message(&mut self) -> Option<String>;
+
+    /// close what is left of the browser and start another one, from
+    /// `browser` when another one is given
+    async fn relaunch(&mut self, browser: Option<&BrowserConfig>) -> Result<(), BackendError>;
+
+    /// open chatgpt.com in the browser started again and log in
+    async fn open(&mut self) -> Result<(), Unavailable>;
+}
+
+#[async_trait]
+impl Session for ChatGPT {
+    async fn send(&mut self, said: &str) -> Result<(), Unavailable> {
+        self.send_my_said(said).await
+    }
+
+    async fn limit_message(&mut self) -> Option<String> {
+        ChatGPT::limit_message(self).await
+    }
+
+    async fn relaunch(&mut self, browser: Option<&BrowserConfig>) -> Result<(), BackendError> {
+        match browser {
+            Some(browser) => self.restart_with(browser).await?,
+            None => self.restart().await?,
+        }
+        Ok(())
+    }
+
+    async fn open(&mut self) -> Result<(), Unavailable> {
+        // the relay goes on listening, it attaches to the new tab by itself
+        self.new_session(Arc::new(AtomicBool::new(true))).await
+    }
+}
+
+/// Keeps a browser session going.
+///
+/// When the webdriver session or its window is lost, or a message is stuck
+/// sending, the browser is started again and logged in, the initial prompts
+/// are said again and then the message. A dropped cdp websocket is told by
+/// the [`Relay`] instead, through the message being said again.
+///
+/// When the assistant takes no more messages, the browser goes on with
+/// another profile of the [`RateLimiter`] when one is free, or the caller is
+/// told how long to wait.
+pub struct Supervisor {
+    relay: Relay,
+    /// said at the beginning of every session, their answers dropped
+    initial_prompts: Vec<String>,
+    /// the instructions of the conversation, said after the initial prompts
+    system: Option<String>,
+    max_recoveries: usize,
+    limiter: RateLimiter,
+}
+
+impl Supervisor {
+    pub fn new(
+        relay: Relay,
+        initial_prompts: Vec<String>,
+        max_recoveries: usize,
+        limiter: RateLimiter,
+    ) -> Self {
+        Supervisor {
+            relay,
+            initial_prompts,
+            system: None,
+            max_recoveries,
+            limiter,
+        }
+    }
+
+    pub fn relay(&self) -> &Relay {
+        &self.relay
+    }
+
+    /// a new conversation has no instructions
+    pub fn forget_system(&mut self) {
+        self.system = None;
+    }
+
+    /// tell `prompt` now and to every session started again
+    pub async fn set_system(
+        &mut self,
+        gpt: &mut impl Session,
+        prompt: &str,
+    ) -> Result<(), BackendError> {
+        self.tell(gpt, prompt).await?;
+        self.system = Some(prompt.to_string());
+        Ok(())
+    }
+
+    /// say `said`, starting the session again when it is lost or stuck
+    pub async fn say(&mut self, gpt: &mut impl Session, said: &str) -> Result<(), BackendError> {
+        let mut recoveries = 0;
+        loop {
+            self.relay.said();
+            match gpt.send(said).await {
+                Ok(()) => {
+                    self.limiter.passed();
+                    return Ok(());
+                }
+                Err(Unavailable::Tired) => {
+                    self.relay.settle();
+                    let message = gpt.limit_message().await;
+                    info!("limited: {message:?}");
+                    let reset = message
+                        .as_deref()
+                        .and_then(|message| ratelimit::parse_reset(message, &Local::now()));
+                    let backoff = self.limiter.limited(reset);
+                    let switched = backoff.switch_to.is_some();
+                    if let Some(browser) = backoff.switch_to {
+                        self.recover(gpt, Some(&browser)).await?;
+                    }
+                    // another profile is free at once
+                    if !switched || !backoff.wait.is_zero() {
+                        return Err(BackendError::Tired {
+                            retry_after: Some(backoff.wait),
+                        });
+                    }
+                }
+                Err(fault @ (Unavailable::Lost | Unavailable::Stuck))
+                    if recoveries < self.max_recoveries =>
+                {
+                    recoveries += 1;
+                    self.relay.settle();
+                    error!(
+                        "{fault:?}, start the session again ({recoveries}/{})",
+                        self.max_recoveries
+                    );
+                    self.recover(gpt, None).await?;
+                }
+                Err(unavailable) => {
+                    self.relay.settle();
+                    return Err(unavailable.into());
+                }
+            }
+        }
+    }
+
+    /// start the browser again, from `browser` when another one is given
+    async fn recover(
+        &self,
+        gpt: &mut impl Session,
+        browser: Option<&BrowserConfig>,
+    ) -> Result<(), BackendError> {
+        let mut attached = self.relay.attached();
+        attached.borrow_and_update();
+        gpt.relaunch(browser).await?;
+        gpt.open().await?;
+        if tokio::time::timeout(ATTACH_TIMEOUT, attached.changed())
+            .await
+            .is_err()
+        {
+            error!("not attached to the new tab in {ATTACH_TIMEOUT:?}");
+        }
+        self.prime(gpt).await
+    }
+
+    /// Say the initial prompts and the instructions, dropping their answers.
+    /// Answers are only heard once the tab is attached to.
+    pub async fn prime(&self, gpt: &mut impl Session) -> Result<(), BackendError> {
+        let prompts = self.initial_prompts.iter().chain(&self.system);
+        for prompt in prompts {
+            self.tell(gpt, prompt).await?;
+        }
+        Ok(())
+    }
+
+    /// say `prompt` and wait for its answer, which nobody hears
+    async fn tell(&self, gpt: &mut impl Session, prompt: &str) -> Result<(), BackendError> {
+        let mut attached = self.relay.attached();
+        if tokio::time::timeout(ATTACH_TIMEOUT, attached.wait_for(|n| *n > 0))
+            .await
+            .is_err()
+        {
+            error!("not attached to the tab in {ATTACH_TIMEOUT:?}");
+        }
+
+        let mut answers = self.relay.aside();
+        self.relay.said();
+        let told = gpt.send(prompt).await;
+        if told.is_ok() {
+            loop {
+                match tokio::time::timeout(ANSWER_TIMEOUT, answers.recv()).await {
+                    Ok(Some(Reply::End)) => break,
+                    Ok(Some(Reply::Nothing)) => {
+                        info!("nothing answered to an initial prompt");
+                        break;
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) | Err(_) => {
+                        error!("no answer to an initial prompt in {ANSWER_TIMEOUT:?}");
+                        break;
+                    }
+                }
+            }
+        }
+        self.relay.settle();
+        self.relay.back();
+        Ok(told?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdp::TargetInfo;
+    use crate::openai::tests::message_event;
+    use crate::ratelimit::{Profile, RateLimitConfig};
+    use std::collections::VecDeque;
+
+    fn data(request_id: &str, text: &str) -> NetworkEvent {
+        NetworkEvent::Data {
+            request_id: request_id.to_string(),
+            data: text.as_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let target = TargetInfo {
+            id: "P".to_string(),
+            kind: "page".to_string(),
+            title: "ChatGPT".to_string(),
+            url: "https://chatgpt.com/".to_string(),
+            web_socket_debugger_url: None,
+        };
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let relay = Relay::new();
+        let (replies_tx, mut replies) = mpsc::unbounded_channel();
+        relay.connect(replies_tx);
+        let mut attached = relay.attached();
+        tokio::spawn(
+            relay
+                .clone()
+                .run(tokio_stream::wrappers::UnboundedReceiverStream::new(
+                    events_rx,
+                )),
+        );
+
+        events_tx
+            .send(NetworkEvent::Attached(target.clone()))
+            .unwrap();
+        attached.changed().await.unwrap();
+        assert_eq!(*attached.borrow(), 1);
+
+        // an initial prompt is answered aside
+        let mut aside = relay.aside();
+        relay.said();
+        events_tx
+            .send(data(
+                "r1",
+                &message_event("m1", "好", "finished_successfully"),
+            ))
+            .unwrap();
+        assert!(matches!(aside.recv().await, Some(Reply::Message(_))));
+        assert_eq!(aside.recv().await, Some(Reply::Words("好".to_string())));
+        assert_eq!(aside.recv().await, Some(Reply::End));
+        relay.back();
+
+        // the socket drops in the middle of an answer
+        relay.said();
+        events_tx
+            .send(data("r2", &message_event("m2", "一", "in_progress")))
+            .unwrap();
+        events_tx
+            .send(NetworkEvent::Detached {
+                reason: "closed".to_string(),
+            })
+            .unwrap();
+        assert!(matches!(replies.recv().await, Some(Reply::Message(_))));
+        assert_eq!(replies.recv().await, Some(Reply::Words("一".to_string())));
+        assert_eq!(replies.recv().await, Some(Reply::Nothing));
+
+        // nothing is awaited any more
+        events_tx
+            .send(NetworkEvent::Detached {
+                reason: "closed".to_string(),
+            })
+            .unwrap();
+        events_tx.send(NetworkEvent::Attached(target)).unwrap();
+        attached.changed().await.unwrap();
+        assert_eq!(*attached.borrow(), 2);
+        assert!(replies.try_recv().is_err());
+    }
+
+    /// Sends as its script says, and once the script runs out, answering
+    /// every message sent through the relay
+    struct ScriptedSession {
+        relay: Relay,
+        script: VecDeque<Result<(), Unavailable>>,
+        said: Vec<String>,
+        launched: Vec<Option<BrowserConfig>>,
+    }
+
+    impl ScriptedSession {
+        fn new(relay: &Relay, script: impl IntoIterator<Item = Result<(), Unavailable>>) -> Self {
+            ScriptedSession {
+                relay: relay.clone(),
+                script: script.into_iter().collect(),
+                said: Vec::new(),
+                launched: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Session for ScriptedSession {
+        async fn send(&mut self, said: &str) -> Result<(), Unavailable> {
+            self.said.push(said.to_string());
+            let sent = self.script.pop_front().unwrap_or(Ok(()));
+            if sent.is_ok() {
+                self.relay.deliver(Reply::End);
+            }
+            sent
+        }
+
+        async fn limit_message(&mut self) -> Option<String> {
+            None
+        }
+
+        async fn relaunch(&mut self, browser: Option<&BrowserConfig>) -> Result<(), BackendError> {
+            self.launched.push(browser.cloned());
+            Ok(())
+        }
+
+        async fn open(&mut self) -> Result<(), Unavailable> {
+            self.relay.0.attached.send_modify(|n| *n += 1);
+            Ok(())
+        }
+    }
+
+    fn supervisor(
+        relay: &Relay,
+        max_recoveries: usize,
+        rate_limit: &RateLimitConfig,
+    ) -> Supervisor {
+        Supervisor::new(
+            relay.clone(),
+            vec!["be brief".to_string()],
+            max_recoveries,
+            RateLimiter::new(rate_limit, &BrowserConfig::default()),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recover() {
+        let relay = Relay::new();
+        let (replies_tx, mut replies) = mpsc::unbounded_channel();
+        relay.connect(replies_tx);
+        let mut supervisor = supervisor(&relay, 2, &RateLimitConfig::default());
+        let mut session = ScriptedSession::new(&relay, [Ok(()), Err(Unavailable::Lost)]);
+        session.open().await.unwrap();
+        supervisor
+            .set_system(&mut session, "translate")
+            .await
+            .unwrap();
+
+        // started again and told the prompts again, then the message
+        supervisor.say(&mut session, "hello").await.unwrap();
+        assert_eq!(session.launched, [None]);
+        assert_eq!(
+            session.said,
+            ["translate", "hello", "be brief", "translate", "hello"]
+        );
+        // only the answer to the message is heard
+        assert_eq!(replies.recv().await, Some(Reply::End));
+        assert!(replies.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_give_up() {
+        let relay = Relay::new();
+        let mut supervisor = supervisor(&relay, 2, &RateLimitConfig::default());
+        let stuck = Err(Unavailable::Stuck);
+        let script = [stuck, Ok(()), stuck, Ok(()), stuck];
+        let mut session = ScriptedSession::new(&relay, script);
+        session.open().await.unwrap();
+        assert!(matches!(
+            supervisor.say(&mut session, "hello").await,
+            Err(BackendError::Stuck)
+        ));
+        assert_eq!(session.launched, [None, None]);
+        assert_eq!(
+            session.said,
+            ["hello", "be brief", "hello", "be brief", "hello"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_profile() {
+        let relay = Relay::new();
+        let rate_limit = RateLimitConfig {
+            profiles: vec![Profile {
+                user_data_dir: Some("chrome-2".into()),
+                ..Profile::default()
+            }],
+            ..RateLimitConfig::default()
+        };
+        let mut supervisor = supervisor(&relay, 2, &rate_limit);
+        let tired = Err(Unavailable::Tired);
+        let mut session = ScriptedSession::new(&relay, [tired]);
+        session.open().await.unwrap();
+
+        // the other profile is free at once
+        supervisor.say(&mut session, "hello").await.unwrap();
+        let second = rate_limit.profiles[0].apply(&BrowserConfig::default());
+        assert_eq!(session.launched, [Some(second)]);
+        assert_eq!(session.said, ["hello", "be brief", "hello"]);
+
+        // both are limited, the first one is waited for
+        session.script.push_back(tired);
+        let limited = supervisor.say(&mut session, "again").await;
+        assert!(matches!(
+            limited,
+            Err(BackendError::Tired {
+                retry_after: Some(wait)
+            }) if wait == Duration::from_secs(60)
+        ));
+        assert_eq!(session.launched.len(), 2);
+        assert_eq!(session.launched[1], Some(BrowserConfig::default()));
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing_subscriber::EnvFilter;
+
+use tracing::info;
+
+/// How the executables log, the `[log]` table.
+///
+/// ```toml
+/// [log]
+/// format = "json"
+/// filter = "chati=debug"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// directives such as `chati=debug`, `error` by default; `RUST_LOG`
+    /// overrides them
+    pub filter: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// one json object per line, with the spans it happened in
+    Json,
+}
+
+impl LogConfig {
+    fn env_filter(&self) -> Result<EnvFilter, String> {
+        if let Ok(filter) = std::env::var(EnvFilter::DEFAULT_ENV) {
+            return EnvFilter::try_new(&filter).map_err(|error| format!("{filter:?}: {error}"));
+        }
+        let filter = self.filter.as_deref().unwrap_or("error");
+        EnvFilter::try_new(filter).map_err(|error| format!("{filter:?}: {error}"))
+    }
+
+    /// fail at startup on a filter which would be dropped
+    pub fn validate(&self) -> Result<(), String> {
+        self.env_filter().map(|_| ())
+    }
+}
+
+/// Log to stderr as `config` says, what dependencies log through the `log`
+/// crate too.
+pub fn init(config: &LogConfig) {
+    let filter = config
+        .env_filter()
+        .unwrap_or_else(|_| EnvFilter::new("error"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    let inited = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+    if let Err(error) = inited {
+        eprintln!("log: {error}");
+    }
+}
+
+/// Where the `web` backend keeps a screenshot and the DOM of the page at
+/// every step, the `[backend.step_debug]` table.
+///
+/// ```toml
+/// [backend.step_debug]
+/// dir = "steps"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StepDebugConfig {
+    pub dir: PathBuf,
+    /// Wait for Enter at every step as well. Not for the `chati` REPL,
+    /// whose line editor reads stdin too.
+    #[serde(default)]
+    pub pause: bool,
+}
+
+/// Keeps what the page looked like at every step, to see afterwards where
+/// an unattended run went wrong.
+pub struct StepDebugger {
+    dir: PathBuf,
+    pause: bool,
+    steps: AtomicUsize,
+}
+
+impl StepDebugger {
+    pub fn new(config: &StepDebugConfig) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(StepDebugger {
+            dir: config.dir.clone(),
+            pause: config.pause,
+            steps: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Keep `screenshot` and `dom` of the step `name` as `<n>-<name>.png`
+    /// and `<n>-<name>.html`, and wait for Enter when asked to pause. Return
+    /// the path without the extension.
+    pub async fn step(
+        &self,
+        name: &str,
+        screenshot: Option<&[u8]>,
+        dom: Option<&str>,
+    ) -> std::io::Result<PathBuf> {
+        let step = self.steps.fetch_add(1, Ordering::AcqRel) + 1;
+        let name = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        let path = self.dir.join(format!("{step:04}-{name}"));
+        if let Some(screenshot) = screenshot {
+            tokio::fs::write(path.with_extension("png"), screenshot).await?;
+        }
+        if let Some(dom) = dom {
+            tokio::fs::write(path.with_extension("html"), dom).await?;
+        }
+        info!(step, path = %path.display(), "step");
+        if self.pause {
+            eprintln!("step {step} {name}, press Enter to continue...");
+            // the buffer of stdin is shared, nothing typed ahead is lost
+            tokio::task::spawn_blocking(|| std::io::stdin().read_line(&mut String::new()))
+                .await
+                .map_err(std::io::Error::other)??;
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_step_debugger() {
+        let dir = std::env::temp_dir().join(format!("chati-steps-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let steps = StepDebugger::new(&StepDebugConfig {
+            dir: dir.clone(),
+            pause: false,
+        })
+        .unwrap();
+
+        let first = steps
+            .step("Ready", Some(b"\x89PNG"), Some("<html></html>"))
+            .await
+            .unwrap();
+        assert_eq!(first, dir.join("0001-Ready"));
+        assert_eq!(
+            std::fs::read(dir.join("0001-Ready.png")).unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("0001-Ready.html")).unwrap(),
+            "<html></html>"
+        );
+        // what could not be taken is left out
+        let second = steps.step("send/click", None, Some("")).await.unwrap();
+        assert_eq!(second, dir.join("0002-send-click"));
+        assert!(!dir.join("0002-send-click.png").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_config() {
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [log]
+            format = "json"
+            filter = "chati=debug,fantoccini=warn"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.log.format, LogFormat::Json);
+        if std::env::var(EnvFilter::DEFAULT_ENV).is_err() {
+            config.log.validate().unwrap();
+            let config = LogConfig {
+                filter: Some("chati=loud".to_string()),
+                ..LogConfig::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::openai::MessageInfo;
+
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+/// What one side said in a conversation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub role: Role,
+    pub text: String,
+    pub time: DateTime<Utc>,
+    /// the ids and the model the backend told along with an answer
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub model_slug: Option<String>,
+}
+
+impl Turn {
+    pub fn system(text: &str) -> Self {
+        Turn {
+            role: Role::System,
+            ..Turn::user(text)
+        }
+    }
+
+    pub fn user(text: &str) -> Self {
+        Turn {
+            role: Role::User,
+            text: text.to_string(),
+            time: Utc::now(),
+            message_id: None,
+            parent_id: None,
+            model_slug: None,
+        }
+    }
+
+    pub fn assistant(text: &str, info: &MessageInfo) -> Self {
+        Turn {
+            role: Role::Assistant,
+            text: text.to_string(),
+            time: Utc::now(),
+            message_id: info.message_id.clone(),
+            parent_id: info.parent_id.clone(),
+            model_slug: info.model_slug.clone(),
+        }
+    }
+}
+
+/// The turns of a conversation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transcript {
+    /// the name it is kept under, from when it was created. It stays so when
+    /// the backend tells the `conversation_id`, which it is found by as well.
+    pub id: String,
+    /// as the backend knows it, such as the one in `https://chatgpt.com/c/<id>`
+    pub conversation_id: Option<String>,
+    pub created: DateTime<Utc>,
+    pub turns: Vec<Turn>,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        let created = Utc::now();
+        Transcript {
+            id: format!("chati-{}", created.format("%Y%m%d-%H%M%S%.3f")),
+            conversation_id: None,
+            created,
+            turns: Vec::new(),
+        }
+    }
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// keep what the backend told about the conversation
+    pub fn learn(&mut self, info: &MessageInfo) {
+        if self.conversation_id.is_none() {
+            self.conversation_id = info.conversation_id.clone();
+        }
+    }
+
+    /// the model of the latest answer
+    pub fn model_slug(&self) -> Option<&str> {
+        self.turns
+            .iter()
+            .rev()
+            .find_map(|turn| turn.model_slug.as_deref())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serialize transcript")
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Conversation {}\n\n", self.id);
+        if let Some(conversation_id) = &self.conversation_id {
+            markdown.push_str(&format!("- conversation: `{conversation_id}`\n"));
+        }
+        if let Some(model_slug) = self.model_slug() {
+            markdown.push_str(&format!("- model: `{model_slug}`\n"));
+        }
+        markdown.push_str(&format!("- created: {}\n", self.created.to_rfc3339()));
+        for turn in &self.turns {
+            let who = match turn.role {
+                Role::System => "System",
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            markdown.push_str(&format!(
+                "\n## {who} ({})\n\n{}\n",
+                turn.time.format("%Y-%m-%d %H:%M:%S UTC"),
+                turn.text.trim_end()
+            ));
+        }
+        markdown
+    }
+}
+
+/// A directory of transcripts, one json file each named after its id
+#[derive(Debug, Clone)]
+pub struct TranscriptStore {
+    dir: PathBuf,
+}
+
+impl TranscriptStore {
+    pub const DEFAULT_DIR: &'static str = "conversations";
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        TranscriptStore { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    pub fn save(&self, transcript: &Transcript) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        // a crash in the middle of writing keeps the former one
+        let path = self.path(&transcript.id);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, transcript.to_json())?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// the transcript kept as `id`, or the one of the conversation `id`
+    pub fn load(&self, id: &str) -> std::io::Result<Transcript> {
+        let path = self.path(id);
+        if path.exists() {
+            return read(&path);
+        }
+        for transcript in self.list()? {
+            if transcript.conversation_id.as_deref() == Some(id) {
+                return Ok(transcript);
+            }
+        }
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no conversation {id} in {}", self.dir.display()),
+        ))
+    }
+
+    /// every transcript, the oldest first. A file which can not be read as
+    /// one is left out.
+    pub fn list(&self) -> std::io::Result<Vec<Transcript>> {
+        let mut transcripts = Vec::new();
+        if !self.dir.exists() {
+            return Ok(transcripts);
+        }
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match read(&path) {
+                Ok(transcript) => transcripts.push(transcript),
+                Err(error) => error!("skip the transcript {}: {error}", path.display()),
+            }
+        }
+        transcripts.sort_by_key(|transcript| transcript.created);
+        Ok(transcripts)
+    }
+}
+
+fn read(path: &Path) -> std::io::Result<Transcript> {
+    let text = std::fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: {error}", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        let info = MessageInfo {
+            conversation_id: Some("c1".to_string()),
+            message_id: Some("m1".to_string()),
+            parent_id: Some("u1".to_string()),
+            model_slug: Some("gpt-4o-mini".to_string()),
+        };
+        let mut transcript = Transcript::new();
+        transcript.turns.push(Turn::user("hello"));
+        transcript.learn(&info);
+        transcript.turns.push(Turn::assistant("你好！\n", &info));
+        transcript
+    }
+
+    #[test]
+    fn test_export() {
+        let transcript = transcript();
+        let markdown = transcript.to_markdown();
+        assert!(markdown.starts_with(&format!("# Conversation {}\n\n", transcript.id)));
+        assert!(markdown.contains("- conversation: `c1`\n- model: `gpt-4o-mini`\n"));
+        assert!(markdown.contains("\n## User ("));
+        assert!(markdown.ends_with(" UTC)\n\n你好！\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&transcript.to_json()).unwrap();
+        assert_eq!(json["turns"][0]["role"], "user");
+        assert!(json["turns"][0].get("message_id").is_none());
+        assert_eq!(json["turns"][1]["message_id"], "m1");
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = std::env::temp_dir().join(format!("transcripts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = TranscriptStore::new(&dir);
+        assert!(store.list().unwrap().is_empty());
+
+        let transcript = transcript();
+        store.save(&transcript).unwrap();
+        assert_eq!(store.load(&transcript.id).unwrap(), transcript);
+        assert_eq!(store.load("c1").unwrap(), transcript);
+        assert_eq!(
+            store.load("c2").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        // one broken by hand hides none of the others
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        assert_eq!(store.list().unwrap(), std::slice::from_ref(&transcript));
+        assert_eq!(store.load("c1").unwrap(), transcript);
+        assert_eq!(
+            store.load("broken").unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,258 +1,280 @@
-use futures::{SinkExt, StreamExt};
-use reqwest;
-use serde_json::json;
-use std::io::Write; // flush
-use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use log::{debug, info};
-
-/// Listen specific web page with `page_url` at the tab `index`
-/// Before we invoke the function, lauch Chrome program with
-/// '--remote-debugging-port=<browser_port>'.
-pub async fn listen_webpage_stream_data(
-    browser_port: u16,
-    page_url: &str,
-    index: usize,
-    request_url: &str,
-    mut handle_fn: impl FnMut(Option<&str>),
-) -> Result<(), Box<dyn std::error::Error>> {
-    let response_text = reqwest::get(format!("http://localhost:{browser_port}/json"))
-        .await?
-        .text()
-        .await?;
-    let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
-    let mut url = String::new();
-    for (i, page) in response_json.as_array().unwrap().iter().enumerate() {
-        if index == i && page.get("url").unwrap().as_str().unwrap() == page_url {
-            url = page
-                .get("webSocketDebuggerUrl")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_string();
-            break;
-        }
+/// A stable 64-bit FNV-1a hash of `content` in hex.
+/// Unlike `DefaultHasher`, it never changes between builds, so it can be saved to files.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
+    format!("{hash:016x}")
+}
 
-    debug!("connect to page {url}");
-    let (mut ws_stream, _) = connect_async(url).await?;
-
-    // https://github.com/aslushnikov/getting-started-with-cdp/blob/master/README.md
-    // Enable network monitoring
-    let enable_network = json!({
-        "id": next_command_id(),
-        "method": "Network.enable"
-    });
-    ws_stream
-        .send(Message::Text(enable_network.to_string()))
-        .await?;
-
-    let mut stream_res_cont_cid = 0;
-    let mut conversation_request_id = String::new();
-
-    debug!("Listening for network events...");
-    while let Some(msg) = ws_stream.next().await {
-        // println!("{msg:#?}");
-        if let Ok(Message::Text(text)) = msg {
-            match serde_json::from_str::<serde_json::Value>(&text) {
-                Ok(json_msg) => {
-                    if let Some(method) = json_msg["method"].as_str() {
-                        match method {
-                            "Network.responseReceived" => {
-                                let params = &json_msg["params"];
-                                let request_id = params["requestId"].as_str().unwrap_or("0");
-                                // let wall_time = params["wallTime"].as_str().unwrap_or("0");
-                                let url = params["response"]["url"].as_str().unwrap_or("0");
-
-                                if url
-                                    == "https://chatgpt.com/backend-anon/sentinel/chat-requirements"
-                                {
-                                    if params["response"]["status"]
-                                        .as_number()
-                                        .unwrap_or(&serde_json::Number::from(404))
-                                        .as_u64()
-                                        .unwrap()
-                                        / 100
-                                        != 2
-                                    {
-                                        handle_fn(None);
-                                        continue;
-                                    }
-                                }
-
-                                if url == request_url {
-                                    // println!(
-                                    //     "requestId: {}, wallTime: {}, response.url: {}",
-                                    //     request_id, wall_time, url
-                                    // );
-                                    if params["response"]["status"]
-                                        .as_number()
-                                        .unwrap_or(&serde_json::Number::from(404))
-                                        .as_u64()
-                                        .unwrap()
-                                        / 100
-                                        != 2
-                                    {
-                                        handle_fn(None);
-                                        continue;
-                                    }
-
-                                    // https://chromedevtools.github.io/devtools-protocol/tot/Network/#method-streamResourceContent
-                                    stream_res_cont_cid = next_command_id();
-                                    conversation_request_id = request_id.to_string();
-                                    let enable_stream_data = json!({
-                                        "id": stream_res_cont_cid,
-                                        "method": "Network.streamResourceContent",
-                                        "params": {
-                                            "requestId": request_id
-                                        }
-                                    });
-                                    // println!(
-                                    //     "enable streaming resource content for request {request_id}"
-                                    // );
-                                    ws_stream
-                                        .send(Message::Text(enable_stream_data.to_string()))
-                                        .await?;
-                                }
-                            }
-                            // https://chromedevtools.github.io/devtools-protocol/tot/Network/#event-dataReceived
-                            "Network.dataReceived" => {
-                                // protocol event
-                                if json_msg.get("id").is_none() {
-                                    let request_id = json_msg["params"]["requestId"].as_str();
-                                    if request_id.unwrap_or("") == conversation_request_id {
-                                        let data = json_msg["params"]["data"].as_str();
-                                        let data = decode_base64(data.unwrap_or(""))?;
-                                        handle_fn(Some(&data));
-                                    }
-                                } else {
-                                    // command response, say "Network.streamResourceContent"
-                                    let msgid =
-                                        json_msg["id"].as_number().unwrap().as_u64().unwrap();
-                                    if msgid == stream_res_cont_cid {
-                                        let databuf = &json_msg["result"]["bufferedData"].as_str();
-                                        let data = decode_base64(databuf.unwrap_or(""))?;
-                                        handle_fn(Some(&data));
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-                Err(error) => {
-                    // Handle JSON parsing error if necessary
-                    panic!("when parsing below text as json:\n{text}: {error:#?}");
-                }
-            }
+/// Where the translation goes when merged with the original comment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// the translation takes the place of the original
+    Replace,
+    /// one comment, the translation above the original
+    #[default]
+    BilingualAbove,
+    /// one comment, the translation below the original
+    BilingualBelow,
+    /// the translation in a comment of its own right above the original
+    SeparateAdjacent,
+}
+
+impl MergeMode {
+    pub const ALL: [MergeMode; 4] = [
+        MergeMode::Replace,
+        MergeMode::BilingualAbove,
+        MergeMode::BilingualBelow,
+        MergeMode::SeparateAdjacent,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MergeMode::Replace => "replace",
+            MergeMode::BilingualAbove => "above",
+            MergeMode::BilingualBelow => "below",
+            MergeMode::SeparateAdjacent => "adjacent",
         }
     }
 
-    Ok(())
+    pub fn by_name(name: &str) -> Option<Self> {
+        MergeMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
 }
 
-fn next_command_id() -> u64 {
-    static SEQ: AtomicUsize = AtomicUsize::new(1);
-    SEQ.fetch_add(1, Ordering::Relaxed) as u64
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MergeError {
+    #[error("no '/*' in the {0} comment")]
+    NoOpen(&'static str),
+    #[error("no '*/' ending the {0} comment")]
+    NoClose(&'static str),
 }
 
-fn decode_base64(encoded: &str) -> Result<String, base64::DecodeError> {
-    use base64::{engine::general_purpose, Engine};
+/// The text before each line of a block comment body, such as `"     *"`
+#[derive(Debug, Clone, PartialEq)]
+struct Gutter {
+    prefix: String,
+    /// the prefix ends with the `*` of the ` * ` gutter
+    star: bool,
+}
 
-    // Decode the base64 string to bytes
-    let decoded_bytes = general_purpose::STANDARD.decode(encoded)?;
+impl Gutter {
+    fn line(&self, text: &str) -> String {
+        match (self.star, text.is_empty()) {
+            (true, true) => self.prefix.clone(),
+            (true, false) => format!("{} {text}", self.prefix),
+            // no trailing whitespaces
+            (false, true) => String::new(),
+            (false, false) => format!("{}{text}", self.prefix),
+        }
+    }
 
-    // Convert the bytes to a String
-    let decoded_string = String::from_utf8(decoded_bytes)
-        .expect("Failed to convert bytes to string")
-        .to_string();
+    /// the text of a body line without the gutter
+    fn strip<'a>(&self, line: &'a str) -> &'a str {
+        match line.strip_prefix(&self.prefix) {
+            Some(text) if self.star => text.strip_prefix(' ').unwrap_or(text),
+            Some(text) => text,
+            None => line.trim_start(),
+        }
+    }
+}
 
-    Ok(decoded_string)
+/// A block comment taken apart
+#[derive(Debug)]
+struct Block<'a> {
+    /// whatever before the line of `/*`
+    lead: &'a str,
+    /// the line of `/*`, the banner such as `/*-----` kept
+    open: String,
+    /// the text of body lines without gutter
+    body: Vec<String>,
+    /// the line of `*/`
+    close: String,
+    gutter: Gutter,
 }
 
-pub fn pause_sync() -> String {
-    if let Ok(pause_var) = std::env::var("PAUSE") {
-        if pause_var == "1" {
-            info!("Press Enter to continue...");
-            std::io::stdout().flush().unwrap(); // Ensure the message is displayed immediately
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            return input;
+fn parse_block<'a>(comment: &'a str, which: &'static str) -> Result<Block<'a>, MergeError> {
+    let open_at = comment.find("/*").ok_or(MergeError::NoOpen(which))?;
+    let line_start = comment[..open_at].rfind('\n').map_or(0, |i| i + 1);
+    let lead = &comment[..line_start];
+    let indent = &comment[line_start..open_at];
+    let mut lines = comment[line_start..].split('\n').collect::<Vec<_>>();
+    let last = lines.pop().unwrap_or_default();
+    let close_at = last.rfind("*/").ok_or(MergeError::NoClose(which))?;
+    if lines.is_empty() {
+        // all on one line, where `/*/` opens but does not close
+        if close_at < indent.len() + 2 {
+            return Err(MergeError::NoClose(which));
         }
+        let text = last[indent.len() + 2..close_at].trim_matches('*').trim();
+        return Ok(Block {
+            lead,
+            open: format!("{indent}/*"),
+            body: vec![text.to_string()],
+            close: format!("{indent} */"),
+            gutter: Gutter {
+                prefix: format!("{indent} *"),
+                star: true,
+            },
+        });
     }
-    String::from("")
-}
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-
-pub async fn pause() -> String {
-    if let Ok(pause_var) = std::env::var("PAUSE") {
-        if pause_var == "1" {
-            let mut stdout = tokio::io::stdout();
-            stdout
-                .write_all(b"Press Enter to continue...\n")
-                .await
-                .unwrap();
-            stdout.flush().await.unwrap(); // Ensure the message is displayed immediately
-
-            let mut stdin = BufReader::new(tokio::io::stdin());
-            let mut input = String::new();
-            stdin.read_line(&mut input).await.unwrap();
-            return input;
-        } else if pause_var == "2" {
-            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-            return "continue".to_string();
+    let first = lines.first().copied().unwrap_or_default();
+    let gutter = match lines.iter().skip(1).find(|line| !line.trim().is_empty()) {
+        Some(line) => {
+            let space = &line[..line.len() - line.trim_start().len()];
+            let star = line.trim_start().starts_with('*');
+            let prefix = if star {
+                format!("{space}*")
+            } else {
+                space.to_string()
+            };
+            Gutter { prefix, star }
         }
+        None => Gutter {
+            prefix: format!("{indent} *"),
+            star: true,
+        },
+    };
+
+    let mut body = Vec::new();
+    let mut open = first.to_string();
+    // text right after `/*` goes to the body, a banner stays
+    let after_open = &first[indent.len() + 2..];
+    if after_open.chars().any(char::is_alphanumeric) {
+        let text = after_open.trim_start_matches('*').trim();
+        body.push(text.to_string());
+        open = format!(
+            "{indent}/*{}",
+            &after_open[..after_open.len() - after_open.trim_start_matches('*').len()]
+        );
+    }
+    body.extend(
+        lines
+            .iter()
+            .skip(1)
+            .map(|line| gutter.strip(line).to_string()),
+    );
+
+    let mut close = last.to_string();
+    let before_close = gutter.strip(&last[..close_at]);
+    if before_close.chars().any(char::is_alphanumeric) {
+        body.push(before_close.trim_end().to_string());
+        close = if gutter.star {
+            format!("{}/", gutter.prefix)
+        } else {
+            format!("{indent}*/")
+        };
     }
-    String::from("")
+    Ok(Block {
+        lead,
+        open,
+        body,
+        close,
+        gutter,
+    })
 }
 
-pub async fn pause_force() -> String {
-    let mut stdout = tokio::io::stdout();
-    stdout
-        .write_all(b"Emergency!!! Press Enter to continue...\n")
-        .await
-        .unwrap();
-    stdout.flush().await.unwrap(); // Ensure the message is displayed immediately
-
-    let mut stdin = BufReader::new(tokio::io::stdin());
-    let mut input = String::new();
-    stdin.read_line(&mut input).await.unwrap();
-    return input;
+fn assemble(block: &Block, bodies: &[&[String]]) -> String {
+    let mut lines = vec![format!("{}{}", block.lead, block.open)];
+    for (i, body) in bodies.iter().enumerate() {
+        if i > 0 {
+            lines.push(block.gutter.line(""));
+        }
+        lines.extend(body.iter().map(|text| block.gutter.line(text)));
+    }
+    lines.push(block.close.clone());
+    lines.join("\n")
 }
 
-/// merge C block code comments with the other comments
-pub fn merge_comments(com1: &str, com2: &str) -> String {
-    let mut merged = String::with_capacity(com1.len() + com2.len());
-    let pos1 = com1.find("*/");
-    let pos2 = com2.find("/*");
+/// the leading whitespaces of the first line
+fn first_indent(comment: &str) -> &str {
+    let first_line = comment
+        .trim_start_matches('\n')
+        .lines()
+        .next()
+        .unwrap_or("");
+    &first_line[..first_line.len() - first_line.trim_start().len()]
+}
 
-    if pos1.is_none() {
-        panic!("can not find '*/' in {com1}");
+/// Merge the `translated` C block comment with the `original` one.
+///
+/// The banner line, the indentation and the ` * ` gutter of the original
+/// are kept, the body of the translation is put in the same gutter.
+pub fn merge_comments(
+    translated: &str,
+    original: &str,
+    mode: MergeMode,
+) -> Result<String, MergeError> {
+    if !original.trim().contains('\n') {
+        return Ok(stack_comments(translated, original, mode));
     }
+    let orig = parse_block(original, "original")?;
+    let tran = parse_block(translated, "translated")?;
+    let merged = match mode {
+        MergeMode::Replace => assemble(&orig, &[&tran.body]),
+        MergeMode::BilingualAbove => assemble(&orig, &[&tran.body, &orig.body]),
+        MergeMode::BilingualBelow => assemble(&orig, &[&orig.body, &tran.body]),
+        MergeMode::SeparateAdjacent => {
+            let translation = assemble(&orig, &[&tran.body]);
+            format!("{translation}\n{}", &original[orig.lead.len()..])
+        }
+    };
+    Ok(merged)
+}
 
-    if pos2.is_none() {
-        panic!("can not find '/*' in {com2}");
+/// Merge two runs of line comments, separated by a line holding only the
+/// comment `marker` when they make one comment.
+pub fn merge_line_comments(
+    translated: &str,
+    original: &str,
+    marker: &str,
+    mode: MergeMode,
+) -> String {
+    let indent = first_indent(original);
+    let translated = translated
+        .trim_matches('\n')
+        .lines()
+        .map(|line| format!("{indent}{}", line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    match mode {
+        MergeMode::Replace => translated,
+        MergeMode::BilingualAbove => format!("{translated}\n{indent}{marker}\n{original}"),
+        MergeMode::BilingualBelow => format!("{original}\n{indent}{marker}\n{translated}"),
+        // an empty line keeps them apart
+        MergeMode::SeparateAdjacent => format!("{translated}\n\n{original}"),
     }
+}
 
-    let pos1 = pos1.unwrap();
-    let pos2 = pos2.unwrap();
-
-    let pos3 = com2[pos2 + 2..].find("\n");
-    if pos3.is_none() {
-        panic!("can not find '\\n' after '/*' in {com2}");
+/// A one line `original` comment is not opened up, the translation is put
+/// on the lines next to it instead.
+pub fn stack_comments(translated: &str, original: &str, mode: MergeMode) -> String {
+    let indent = first_indent(original);
+    let translated = format!("{indent}{}", translated.trim_matches('\n').trim_start());
+    match mode {
+        MergeMode::Replace => translated,
+        MergeMode::BilingualAbove | MergeMode::SeparateAdjacent => {
+            format!("{translated}\n{original}")
+        }
+        MergeMode::BilingualBelow => format!("{original}\n{translated}"),
     }
-    let pos3 = pos3.unwrap();
-
-    merged.push_str(&com1[0..pos1 + 1]);
-    merged.push_str(&com2[pos2 + 2 + pos3..]);
-    merged
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(content_hash("/* a */"), content_hash("/* b */"));
+    }
+
     #[test]
     fn test_merge_comments() {
         let eng = r#"
@@ -284,7 +306,7 @@ mod test {
          * Relation reference to TopTransactionResourceOwner.
          */"#;
 
-        let merged = merge_comments(chi, eng);
+        let merged = merge_comments(chi, eng, MergeMode::BilingualAbove).unwrap();
         assert_eq!(merged, expected);
 
         let expected = r#"
@@ -300,7 +322,88 @@ mod test {
          * 我们执行了一个略微复杂的操作，将Relation引用的所有权分配给TopTransactionResourceOwner。
          */"#;
 
-        let merged = merge_comments(eng, chi);
+        let merged = merge_comments(chi, eng, MergeMode::BilingualBelow).unwrap();
         assert_eq!(merged, expected);
     }
+
+    #[test]
+    fn test_merge_modes() {
+        let eng = "\t/*-----\n\t * Returns the shard.\n\t */";
+        // the model lost the indentation and put text on the `/*` line
+        let chi = "/* 返回\n * shard。 */";
+
+        let merged = merge_comments(chi, eng, MergeMode::Replace).unwrap();
+        assert_eq!(merged, "\t/*-----\n\t * 返回\n\t * shard。\n\t */");
+
+        let merged = merge_comments(chi, eng, MergeMode::BilingualAbove).unwrap();
+        assert_eq!(
+            merged,
+            "\t/*-----\n\t * 返回\n\t * shard。\n\t *\n\t * Returns the shard.\n\t */"
+        );
+
+        let merged = merge_comments(chi, eng, MergeMode::SeparateAdjacent).unwrap();
+        assert_eq!(
+            merged,
+            "\t/*-----\n\t * 返回\n\t * shard。\n\t */\n\t/*-----\n\t * Returns the shard.\n\t */"
+        );
+
+        let merged = merge_comments("/* 返回 shard。 */", eng, MergeMode::Replace).unwrap();
+        assert_eq!(merged, "\t/*-----\n\t * 返回 shard。\n\t */");
+
+        // a gutter without `*`
+        let eng = "/*\n    Returns the shard.\n*/";
+        let merged =
+            merge_comments("/*\n 返回 shard。\n*/", eng, MergeMode::BilingualBelow).unwrap();
+        assert_eq!(merged, "/*\n    Returns the shard.\n\n    返回 shard。\n*/");
+
+        // a one line comment is not opened up
+        let merged = merge_comments(
+            "/** 返回 */",
+            "    /** Returns */",
+            MergeMode::BilingualAbove,
+        );
+        assert_eq!(merged.unwrap(), "    /** 返回 */\n    /** Returns */");
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let eng = "/*\n * Returns the shard.\n */";
+        assert_eq!(
+            merge_comments("返回 shard。", eng, MergeMode::Replace),
+            Err(MergeError::NoOpen("translated"))
+        );
+        assert_eq!(
+            merge_comments("/*\n * 返回 shard。\n", eng, MergeMode::Replace),
+            Err(MergeError::NoClose("translated"))
+        );
+        assert_eq!(
+            merge_comments(eng, "/*\n * x\n * y", MergeMode::Replace),
+            Err(MergeError::NoClose("original"))
+        );
+        assert_eq!(
+            merge_comments("/*/", eng, MergeMode::Replace),
+            Err(MergeError::NoClose("translated"))
+        );
+    }
+
+    #[test]
+    fn test_merge_line_comments() {
+        let eng = "    // Returns the shard placement.\n    // Errors out when missing.";
+        let chi = "    // 返回 shard placement。\n    // 缺失时报错。";
+        let expected = "    // 返回 shard placement。\n    // 缺失时报错。\n    //\n    // Returns the shard placement.\n    // Errors out when missing.";
+        assert_eq!(
+            merge_line_comments(chi, eng, "//", MergeMode::BilingualAbove),
+            expected
+        );
+        let expected = "    // Returns the shard placement.\n    // Errors out when missing.\n    //\n    // 返回 shard placement。\n    // 缺失时报错。";
+        assert_eq!(
+            merge_line_comments(chi, eng, "//", MergeMode::BilingualBelow),
+            expected
+        );
+        // the indentation of the original is taken
+        assert_eq!(
+            merge_line_comments("// 返回\n  // 缺失", eng, "//", MergeMode::Replace),
+            "    // 返回\n    // 缺失"
+        );
+    }
 }
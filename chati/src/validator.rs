@@ -0,0 +1,380 @@
+use std::fmt::Display;
+
+use metacomment::language::Language;
+use metacomment::scanner::{CommentKind, CommentScanner};
+
+/// Checks a reply has the shape bcct asked for: the comment in a code block,
+/// written in the style, indentation and width of the original.
+#[derive(Clone)]
+pub struct ReplyValidator {
+    pub language: &'static dyn Language,
+    /// the column lines are asked to wrap at
+    pub max_width: usize,
+    /// how many columns a line may go beyond `max_width`
+    pub width_tolerance: usize,
+    /// how much the line count may differ, as a fraction of the original
+    pub line_tolerance: f64,
+    pub tab_width: usize,
+}
+
+/// Something wrong with the shape of a reply
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// the name of the language the code block should be tagged with
+    NoCodeBlock(&'static str),
+    /// the code block is tagged with another language
+    Fence {
+        expected: &'static str,
+        found: String,
+    },
+    NoComment,
+    /// the comment is not in the style of the original, such as `/*` or `//`
+    Style(&'static str),
+    Unbalanced {
+        open: &'static str,
+        opens: usize,
+        closes: usize,
+    },
+    LineCount {
+        expected: usize,
+        found: usize,
+    },
+    /// the leading whitespaces of the original
+    Indent(String),
+    /// the lines, beginning at 1, wider than the limit
+    TooWide {
+        lines: Vec<usize>,
+        limit: usize,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::NoCodeBlock(fence) => {
+                write!(f, "the comment should be in a ```{fence} code block")
+            }
+            Problem::Fence { expected, found } => write!(
+                f,
+                "the code block should be tagged ```{expected}, not ```{found}"
+            ),
+            Problem::NoComment => write!(f, "the code block should hold the translated comment"),
+            Problem::Style(open) => {
+                write!(f, "the comment should begin with '{open}' as the original")
+            }
+            Problem::Unbalanced {
+                open,
+                opens,
+                closes,
+            } => write!(
+                f,
+                "every '{open}' should be closed, found {opens} opening and {closes} closing"
+            ),
+            Problem::LineCount { expected, found } => write!(
+                f,
+                "the comment should have about {expected} lines as the original, not {found}"
+            ),
+            Problem::Indent(indent) => write!(
+                f,
+                "keep the whitespaces {indent:?} at the head of every line"
+            ),
+            Problem::TooWide { lines, limit } => {
+                let lines = lines.iter().map(usize::to_string).collect::<Vec<_>>();
+                write!(
+                    f,
+                    "wrap line {} which go beyond the column position {limit}",
+                    lines.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl ReplyValidator {
+    pub fn new(language: &'static dyn Language) -> Self {
+        ReplyValidator {
+            language,
+            max_width: 80,
+            width_tolerance: 8,
+            line_tolerance: 0.5,
+            tab_width: 4,
+        }
+    }
+
+    /// `comment` in a code block of its language, as it is asked to be
+    /// translated
+    pub fn request(&self, comment: &str) -> String {
+        format!("```{}\n{comment}\n```", self.language.name())
+    }
+
+    /// The content of the first code block of `reply`, which has to be
+    /// tagged with the language of the comment or not at all.
+    pub fn code_block<'a>(&self, reply: &'a str) -> Result<&'a str, Problem> {
+        let expected = self.language.name();
+        let (tag, block) = code_block(reply).ok_or(Problem::NoCodeBlock(expected))?;
+        let tag = tag.to_ascii_lowercase();
+        if tag.is_empty() || tag == expected || self.language.extensions().contains(&tag.as_str()) {
+            Ok(block)
+        } else {
+            Err(Problem::Fence {
+                expected,
+                found: tag,
+            })
+        }
+    }
+
+    /// Return the code block of the `reply` to the `original` comment, or
+    /// what is wrong with it.
+    pub fn validate<'a>(&self, original: &str, reply: &'a str) -> Result<&'a str, Vec<Problem>> {
+        let block = self.code_block(reply).map_err(|problem| vec![problem])?;
+        let mut scanner = CommentScanner::new(self.language);
+        let mut comment = None;
+        while let Some((start, end, kind)) = scanner.scan(block.as_bytes(), true) {
+            if kind != CommentKind::TrailingInline {
+                comment = Some(&block[start..end]);
+                break;
+            }
+        }
+        let comment = comment.ok_or_else(|| vec![Problem::NoComment])?;
+
+        let mut problems = Vec::new();
+        let syntax = self.language.syntax();
+        let block_style = syntax
+            .block
+            .filter(|(open, _)| original.trim_start().starts_with(open));
+        match block_style {
+            Some((open, close)) => {
+                if !comment.trim_start().starts_with(open) {
+                    problems.push(Problem::Style(open));
+                }
+                let opens = block.matches(open).count();
+                let closes = block.matches(close).count();
+                if opens != closes {
+                    problems.push(Problem::Unbalanced {
+                        open,
+                        opens,
+                        closes,
+                    });
+                }
+            }
+            None => {
+                let line = syntax.line.unwrap_or("//");
+                if !comment.trim_start().starts_with(line) {
+                    problems.push(Problem::Style(line));
+                }
+            }
+        }
+
+        let expected = original.trim_matches('\n').lines().count();
+        let found = comment.lines().count();
+        let slack = (expected as f64 * self.line_tolerance).ceil() as usize + 1;
+        if found + slack < expected || found > expected + slack {
+            problems.push(Problem::LineCount { expected, found });
+        }
+
+        let indent = leading_whitespaces(original.trim_start_matches('\n'));
+        if leading_whitespaces(comment) != indent {
+            problems.push(Problem::Indent(indent.to_string()));
+        }
+
+        let widest = original
+            .lines()
+            .map(|line| self.width(line))
+            .max()
+            .unwrap_or(0);
+        let limit = (self.max_width + self.width_tolerance).max(widest);
+        let lines = comment
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| self.width(line) > limit)
+            .map(|(i, _)| i + 1)
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            problems.push(Problem::TooWide {
+                lines,
+                limit: self.max_width,
+            });
+        }
+
+        if problems.is_empty() {
+            Ok(block)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// the columns `line` takes, wide characters such as CJK take two
+    pub fn width(&self, line: &str) -> usize {
+        let mut width = 0;
+        for c in line.chars() {
+            width += match c {
+                '\t' => self.tab_width - width % self.tab_width,
+                c if is_wide(c) => 2,
+                _ => 1,
+            };
+        }
+        width
+    }
+}
+
+/// the tag and the content of the first ``` fenced block, the fences
+/// excluded
+pub fn code_block(reply: &str) -> Option<(&str, &str)> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in reply.split_inclusive('\n') {
+        if let Some(tag) = line.trim_start().strip_prefix("```") {
+            match start {
+                None => start = Some((tag.trim(), offset + line.len())),
+                Some((tag, start)) => {
+                    return Some((tag, reply[start..offset].trim_end_matches('\n')))
+                }
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn leading_whitespaces(text: &str) -> &str {
+    let first_line = text.lines().next().unwrap_or("");
+    &first_line[..first_line.len() - first_line.trim_start().len()]
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metacomment::language::{Python, Rust, C};
+
+    const ORIGINAL: &str = "\t/*\n\t * Returns the shard placement of the relation,\n\t * or NULL when there is none.\n\t */";
+
+    #[test]
+    fn test_valid_reply() {
+        let validator = ReplyValidator::new(&C);
+        let reply = "好的：\n```c\n\t/*\n\t * 返回 relation 的 shard placement，\n\t * 没有时返回 NULL。\n\t */\n```\n";
+        assert_eq!(
+            validator.validate(ORIGINAL, reply),
+            Ok("\t/*\n\t * 返回 relation 的 shard placement，\n\t * 没有时返回 NULL。\n\t */")
+        );
+    }
+
+    #[test]
+    fn test_problems() {
+        let validator = ReplyValidator::new(&C);
+        assert_eq!(
+            validator.validate(ORIGINAL, "\t/*\n\t * 返回\n\t */"),
+            Err(vec![Problem::NoCodeBlock("c")])
+        );
+        assert_eq!(
+            validator.validate(ORIGINAL, "```c\n/*\n * 返回 */ */\n */\n```"),
+            Err(vec![
+                Problem::Unbalanced {
+                    open: "/*",
+                    opens: 1,
+                    closes: 3
+                },
+                Problem::Indent("\t".to_string())
+            ])
+        );
+        let long = "很长".repeat(30);
+        let reply = format!(
+            "```c\n\t// 返回\n\t/*\n\t * {long}\n\t * 1\n\t * 2\n\t * 3\n\t * 4\n\t */\n```"
+        );
+        assert_eq!(
+            validator.validate(ORIGINAL, &reply),
+            Err(vec![Problem::Style("/*")])
+        );
+        let reply = format!(
+            "```c\n\t/*\n\t * {long}\n\t * 1\n\t * 2\n\t * 3\n\t * 4\n\t * 5\n\t * 6\n\t */\n```"
+        );
+        assert_eq!(
+            validator.validate(ORIGINAL, &reply),
+            Err(vec![
+                Problem::LineCount {
+                    expected: 4,
+                    found: 9
+                },
+                Problem::TooWide {
+                    lines: vec![2],
+                    limit: 80
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let validator = ReplyValidator::new(&Python);
+        let original = "    # Returns the shard\n    # placement.";
+        assert!(validator
+            .validate(
+                original,
+                "```python\n    # 返回 shard\n    # placement。\n```"
+            )
+            .is_ok());
+        assert_eq!(
+            validator.validate(original, "```python\n    \"\"\"返回\"\"\"\n```"),
+            Err(vec![Problem::NoComment])
+        );
+    }
+
+    #[test]
+    fn test_fence() {
+        let validator = ReplyValidator::new(&Rust);
+        let original = "    // Returns the shard.";
+        assert_eq!(
+            validator.request(original),
+            "```rust\n    // Returns the shard.\n```"
+        );
+        for reply in [
+            "```rust\n    // 返回 shard。\n```",
+            "```rs\n    // 返回 shard。\n```",
+        ] {
+            assert_eq!(
+                validator.validate(original, reply),
+                Ok("    // 返回 shard。")
+            );
+        }
+        // left untagged
+        assert!(validator
+            .validate(original, "```\n    // 返回 shard。\n```")
+            .is_ok());
+        let problems = validator
+            .validate(original, "```c\n    // 返回 shard。\n```")
+            .unwrap_err();
+        assert_eq!(
+            problems,
+            vec![Problem::Fence {
+                expected: "rust",
+                found: "c".to_string()
+            }]
+        );
+        assert_eq!(
+            problems[0].to_string(),
+            "the code block should be tagged ```rust, not ```c"
+        );
+        assert_eq!(
+            Problem::NoCodeBlock("python").to_string(),
+            "the comment should be in a ```python code block"
+        );
+    }
+
+    #[test]
+    fn test_width() {
+        let validator = ReplyValidator::new(&C);
+        assert_eq!(validator.width("\t * 中文 ab"), 4 + 3 + 4 + 3);
+        assert_eq!(validator.width("ab\tc"), 5);
+    }
+}
@@ -0,0 +1,29 @@
+/// remove `name <value>` from the arguments and return the value
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_option() {
+        let mut args = ["merger", "--lang", "c", "a.c", "--mode"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(take_option(&mut args, "--lang"), Some("c".to_string()));
+        assert_eq!(args, ["merger", "a.c", "--mode"]);
+        // without its value it is left as it is
+        assert_eq!(take_option(&mut args, "--mode"), None);
+        assert_eq!(take_option(&mut args, "--kind"), None);
+        assert_eq!(args, ["merger", "a.c", "--mode"]);
+    }
+}
@@ -3,19 +3,56 @@ use std::fmt::Display;
 use std::io::Write;
 use std::io::{Read, Seek, SeekFrom};
 
-use crate::comment_of_c::*;
+use serde::Serialize;
 
+use crate::language::{Language, C};
+pub use crate::scanner::CommentKind;
+use crate::scanner::CommentScanner;
+
+/// A block of code comment
+/// For example.
+/// ```c
+/// /*
+///  * this is a c code block comment
+///  */
+/// ```
+///
+/// Lines and columns begin at 1. Columns count characters, not bytes.
+#[derive(Debug, Clone, Serialize)]
 pub struct CommentOfC {
-    offset: usize,
-    end: usize,
-    content: String,
-    pre_whitespaces: String,
+    /// inclusive
+    pub offset: usize,
+    /// exclusive
+    pub end: usize,
+    /// include whitespaces before '/*'
+    pub content: String,
+    pub kind: CommentKind,
+    /// the line of `offset`
+    pub line: usize,
+    /// the column of `offset`
+    pub column: usize,
+    /// the line of the last character
+    pub end_line: usize,
+    /// the column right after the last character
+    pub end_column: usize,
 }
 
 impl Display for CommentOfC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "comment <{}:{}> ---", self.offset, self.end)?;
-        write!(f, "{}{}", self.pre_whitespaces, self.content)
+        writeln!(
+            f,
+            "comment <{}:{}> {} ---",
+            self.offset,
+            self.end,
+            self.kind.name()
+        )?;
+        write!(f, "{}", self.content)
+    }
+}
+
+impl Default for CommentOfC {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -25,27 +62,90 @@ impl CommentOfC {
             offset: 0,
             end: 0,
             content: String::from(""),
-            pre_whitespaces: String::from(""),
+            kind: CommentKind::Block,
+            line: 1,
+            column: 1,
+            end_line: 1,
+            end_column: 1,
+        }
+    }
+}
+
+/// Where a byte is in the file, counted from 0
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Position {
+    /// newlines before
+    lines: usize,
+    /// characters after the last newline
+    column: usize,
+}
+
+impl Position {
+    /// the position after `bytes` which follow this position
+    ///
+    /// Only the leading byte of a UTF-8 sequence is counted, so `bytes` may
+    /// begin or end in the middle of a character.
+    fn advance(self, bytes: &[u8]) -> Position {
+        let mut pos = self;
+        for &b in bytes {
+            if b == b'\n' {
+                pos.lines += 1;
+                pos.column = 0;
+            } else if b & 0xC0 != 0x80 {
+                pos.column += 1;
+            }
         }
+        pos
     }
 }
 
+/// Iterate over the comments of a code file, see [`CommentScanner`].
+///
+/// Only multi-line comments beginning a line and doc comments are extracted,
+/// unless other kinds are asked for with [`CommentExtractor::kinds`].
+///
+/// The file is read piece by piece. The buffer only grows while it holds an
+/// unfinished comment, so comments and files of any size are handled.
 pub struct CommentExtractor {
     code_file: std::fs::File,
-    buffer: [u8; 4096],
-    offset: usize,
-    end: usize,
+    buffer: Vec<u8>,
+    /// how many bytes to read from the file at a time
+    chunk_size: usize,
+    /// file offset of `buffer[0]`
     file_offset: usize,
+    /// position of `buffer[0]`
+    file_position: Position,
+    scanner: CommentScanner,
+    kinds: Vec<CommentKind>,
+    eof: bool,
 }
 
 impl CommentExtractor {
+    const CHUNK_SIZE: usize = 4096;
+
+    /// extract comments of C code
     pub fn new(code_file: std::fs::File) -> Self {
+        Self::with_language(code_file, &C)
+    }
+
+    pub fn with_language(code_file: std::fs::File, language: &'static dyn Language) -> Self {
+        Self::with_chunk_size(code_file, language, Self::CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(
+        code_file: std::fs::File,
+        language: &'static dyn Language,
+        chunk_size: usize,
+    ) -> Self {
         let mut myself = CommentExtractor {
             code_file,
-            buffer: [0; 4096],
-            offset: 0,
-            end: 0,
+            buffer: Vec::with_capacity(chunk_size),
+            chunk_size,
             file_offset: 0,
+            file_position: Position::default(),
+            scanner: CommentScanner::new(language),
+            kinds: vec![CommentKind::Block, CommentKind::Doc],
+            eof: false,
         };
         myself
             .code_file
@@ -55,15 +155,29 @@ impl CommentExtractor {
         myself
     }
 
+    /// extract the comments of these kinds only
+    pub fn kinds(mut self, kinds: &[CommentKind]) -> Self {
+        self.kinds = kinds.to_vec();
+        self
+    }
+
     fn buffer_read(&mut self) {
-        let unconsumed = self.end - self.offset;
-        self.buffer.copy_within(self.offset..self.end, 0);
-        self.file_offset += self.offset;
-        self.offset = 0;
-        self.end = unconsumed;
+        // drop what the scanner is done with
+        let consumed = self.scanner.keep_from();
+        self.file_position = self.file_position.advance(&self.buffer[..consumed]);
+        self.buffer.drain(..consumed);
+        self.scanner.shift(consumed);
+        self.file_offset += consumed;
 
+        // a long unfinished token is scanned again after each read, read more at a time
+        let unconsumed = self.buffer.len();
+        self.buffer
+            .resize(unconsumed + self.chunk_size.max(unconsumed), 0);
         let len = self.code_file.read(&mut self.buffer[unconsumed..]).unwrap();
-        self.end += len;
+        self.buffer.truncate(unconsumed + len);
+        if len == 0 {
+            self.eof = true;
+        }
 
         #[cfg(env = "OUTPUT_ORIGIN")]
         {
@@ -72,24 +186,33 @@ impl CommentExtractor {
                 .create(true) // Create the file if it doesn't exist
                 .open("out.c")
                 .expect("out.c");
-            file.write_all(&self.buffer).expect("write all");
+            file.write_all(&self.buffer[unconsumed..])
+                .expect("write all");
         }
     }
 
     fn find_first_comment(&mut self) -> Option<CommentOfC> {
-        let pos = find_first_c_comment(&self.buffer[self.offset..self.end]);
-        if let Some((start, end)) = pos {
-            let mut com = CommentOfC::new();
-            com.offset = self.file_offset + self.offset + start;
-            com.end = self.file_offset + self.offset + end;
-            com.content.push_str(&String::from_utf8_lossy(
-                &self.buffer[(self.offset + start)..(self.offset + end)],
-            ));
-            self.offset += end;
-            Some(com)
-        } else {
-            None
-        }
+        let (start, end, kind) = loop {
+            let (start, end, kind) = self.scanner.scan(&self.buffer, self.eof)?;
+            if self.kinds.contains(&kind) {
+                break (start, end, kind);
+            }
+        };
+        let mut com = CommentOfC::new();
+        com.offset = self.file_offset + start;
+        com.end = self.file_offset + end;
+        com.kind = kind;
+        let begin = self.file_position.advance(&self.buffer[..start]);
+        let finish = begin.advance(&self.buffer[start..end]);
+        com.line = begin.lines + 1;
+        com.column = begin.column + 1;
+        com.end_line = finish.lines + 1;
+        com.end_column = finish.column + 1;
+        // a comment begins and ends at ascii, so it holds whole characters,
+        // only a file which is not UTF-8 at all is replaced here
+        com.content
+            .push_str(&String::from_utf8_lossy(&self.buffer[start..end]));
+        Some(com)
     }
 }
 
@@ -97,11 +220,118 @@ impl Iterator for CommentExtractor {
     type Item = CommentOfC;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(com) = self.find_first_comment() {
-            Some(com)
-        } else {
+        loop {
+            if let Some(com) = self.find_first_comment() {
+                return Some(com);
+            }
+            if self.eof {
+                return None;
+            }
             self.buffer_read();
-            self.find_first_comment()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comment_of_c::find_c_comments;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(name)
+    }
+
+    /// reading the file piece by piece finds the same comments as scanning it at once
+    fn assert_same_as_whole(name: &str, chunk_size: usize) -> Vec<CommentOfC> {
+        let path = fixture(name);
+        let whole = std::fs::read(&path).unwrap();
+        let expected = find_c_comments(&whole);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let comments = CommentExtractor::with_chunk_size(file, &C, chunk_size).collect::<Vec<_>>();
+        assert_eq!(
+            comments
+                .iter()
+                .map(|com| (com.offset, com.end))
+                .collect::<Vec<_>>(),
+            expected,
+            "{name} read {chunk_size} bytes at a time"
+        );
+        for com in &comments {
+            assert_eq!(com.content.as_bytes(), &whole[com.offset..com.end]);
+            let text = String::from_utf8_lossy(&whole[..com.offset]);
+            let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+            assert_eq!(com.line, text.matches('\n').count() + 1);
+            assert_eq!(com.column, text[line_start..].chars().count() + 1);
+        }
+        comments
+    }
+
+    #[test]
+    fn test_extract_in_pieces() {
+        for chunk_size in [1, 2, 3, 7, 64, 4096] {
+            assert_same_as_whole("brin.c", chunk_size);
+            assert_same_as_whole("huge_comment.c", chunk_size);
+            assert_same_as_whole("utf8.c", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_huge_comment() {
+        let comments = assert_same_as_whole("huge_comment.c", CommentExtractor::CHUNK_SIZE);
+        assert_eq!(comments.len(), 4);
+        // larger than the buffer once used
+        assert!(comments[0].content.len() > 3 * CommentExtractor::CHUNK_SIZE);
+        assert!(comments[0].content.ends_with("*/"));
+        // behind more than a buffer of code without any comment
+        assert!(comments[2].offset - comments[1].end > CommentExtractor::CHUNK_SIZE);
+        assert!(comments[3].content.len() > 2 * CommentExtractor::CHUNK_SIZE);
+        assert!(comments[3].content.starts_with("\t/*"));
+    }
+
+    #[test]
+    fn test_kinds() {
+        let file = std::fs::File::open(fixture("huge_comment.c")).unwrap();
+        let comments = CommentExtractor::new(file)
+            .kinds(&[CommentKind::TrailingInline])
+            .collect::<Vec<_>>();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].content, "/* inline comment */");
+        assert_eq!(comments[0].line, 245);
+        assert_eq!(comments[0].column, 25);
+        assert_eq!(comments[0].end_line, 245);
+        assert_eq!(comments[0].end_column, 45);
+    }
+
+    #[test]
+    fn test_utf8_positions() {
+        // every read splits the multibyte characters
+        for chunk_size in [1, 2, 5] {
+            let file = std::fs::File::open(fixture("utf8.c")).unwrap();
+            let comments = CommentExtractor::with_chunk_size(file, &C, chunk_size)
+                .kinds(&CommentKind::ALL)
+                .collect::<Vec<_>>();
+            let found = comments
+                .iter()
+                .map(|com| {
+                    (
+                        com.content.as_str(),
+                        (com.line, com.column),
+                        (com.end_line, com.end_column),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                found,
+                vec![
+                    ("/*\n * 文件头注释\n */", (1, 1), (3, 4)),
+                    ("/* 计数器 */", (5, 20), (5, 29)),
+                    ("\t/*\n\t * 函数说明：返回 “值”\n\t */", (7, 1), (9, 5)),
+                ],
+                "read {chunk_size} bytes at a time"
+            );
         }
     }
 }
@@ -1,6 +1,12 @@
+use crate::language::C;
+use crate::scanner::{CommentKind, CommentScanner};
+
+/// Only multi-line comments which begin a line are found, see [`CommentKind::Block`].
+/// The returned tuple (start, end) represents the byte range of the comment.
+/// The comment starts at position `start` and ends at position `end - 1`.
 pub fn find_first_c_comment(buffer: &[u8]) -> Option<(usize, usize)> {
     let mut v = find_c_comments_internal(buffer, true);
-    if v.len() == 0 {
+    if v.is_empty() {
         None
     } else {
         Some(v.remove(0))
@@ -12,91 +18,17 @@ pub fn find_c_comments(buffer: &[u8]) -> Vec<(usize, usize)> {
 }
 
 fn find_c_comments_internal(buffer: &[u8], stop_on_first_found: bool) -> Vec<(usize, usize)> {
-    enum State {
-        BeginWithWhitespace,
-        InsideCode,
-        InsideSingleQuote,
-        InsideEscape,
-        InsideInlineComment,
-        InsideSingleLineComment,
-        InsideMultiLineComment,
-    }
-
     let mut comments = Vec::new();
-    let mut state = State::BeginWithWhitespace;
-    let mut start = 0;
-    let mut n_whitespaces = 0;
-
-    let mut i = 0;
-    while i < buffer.len() {
-        match state {
-            State::BeginWithWhitespace => {
-                if buffer[i] == b'\'' {
-                    state = State::InsideSingleQuote;
-                } else if i < buffer.len() - 1 && buffer[i] == b'/' && buffer[i + 1] == b'*' {
-                    state = State::InsideSingleLineComment;
-                    start = i - n_whitespaces;
-                    i += 1; // Move past the start of the comment
-                } else if !buffer[i].is_ascii_whitespace() {
-                    state = State::InsideCode;
-                }
-
-                if buffer[i] == b' ' || buffer[i] == b'\t' {
-                    n_whitespaces += 1;
-                } else {
-                    n_whitespaces = 0;
-                }
-            }
-            State::InsideCode => {
-                if buffer[i] == b'\'' {
-                    state = State::InsideSingleQuote;
-                } else if buffer[i] == b'\n' {
-                    state = State::BeginWithWhitespace;
-                    n_whitespaces = 0;
-                } else if i < buffer.len() - 1 && buffer[i] == b'/' && buffer[i + 1] == b'*' {
-                    state = State::InsideInlineComment;
-                    i += 1; // Move past the start of the comment
-                }
-            }
-            State::InsideSingleQuote => {
-                if buffer[i] == b'\\' {
-                    state = State::InsideEscape;
-                } else if buffer[i] == b'\'' {
-                    state = State::InsideCode;
-                }
-            }
-            State::InsideEscape => {
-                // Always return to InsideSingleQuote after an escape sequence
-                state = State::InsideSingleQuote;
-            }
-            State::InsideInlineComment => {
-                if i < buffer.len() - 1 && buffer[i] == b'*' && buffer[i + 1] == b'/' {
-                    state = State::InsideCode;
-                    i += 1;
-                }
-            }
-            State::InsideSingleLineComment => {
-                if buffer[i] == b'\n' {
-                    state = State::InsideMultiLineComment;
-                } else if i < buffer.len() - 1 && buffer[i] == b'*' && buffer[i + 1] == b'/' {
-                    state = State::InsideCode;
-                    i += 1;
-                }
-            }
-            State::InsideMultiLineComment => {
-                if i < buffer.len() - 1 && buffer[i] == b'*' && buffer[i + 1] == b'/' {
-                    state = State::InsideCode;
-                    comments.push((start, i + 2));
-                    i += 1;
-                    if stop_on_first_found {
-                        break;
-                    }
-                }
-            }
+    let mut scanner = CommentScanner::new(&C);
+    while let Some((start, end, kind)) = scanner.scan(buffer, true) {
+        if kind != CommentKind::Block && kind != CommentKind::Doc {
+            continue;
+        }
+        comments.push((start, end));
+        if stop_on_first_found {
+            break;
         }
-        i += 1;
     }
-
     comments
 }
 
@@ -106,6 +38,12 @@ mod tests {
 
     #[test]
     pub fn test_find_c_comments() {
+        let a = "s = \"/* not a comment\";\n/* multi-line comment\n hello world */";
+        assert_eq!(find_c_comments(a.as_bytes()), vec![(24, 61)]);
+
+        let a = "// line comments\n// hello world\na = 3;";
+        assert_eq!(find_c_comments(a.as_bytes()), vec![(0, 31)]);
+
         let a = "a = 3; /* inline comment */";
         assert_eq!(find_c_comments(a.as_bytes()).len(), 0);
 
@@ -0,0 +1,131 @@
+use regex::Regex;
+
+use crate::comment_extractor::{CommentKind, CommentOfC};
+
+/// Which comments to keep. Every condition set has to hold.
+#[derive(Debug, Clone, Default)]
+pub struct CommentFilter {
+    /// any kind when empty
+    pub kinds: Vec<CommentKind>,
+    /// characters of the trimmed content
+    pub min_len: usize,
+    pub regex: Option<Regex>,
+    /// inclusive line range, a comment overlapping it is kept
+    pub lines: Option<(usize, usize)>,
+}
+
+impl CommentFilter {
+    pub fn matches(&self, com: &CommentOfC) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&com.kind) {
+            return false;
+        }
+        if com.content.trim().chars().count() < self.min_len {
+            return false;
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&com.content) {
+                return false;
+            }
+        }
+        if let Some((first, last)) = self.lines {
+            if com.end_line < first || com.line > last {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// parse a comma separated list of kind names, such as `block,doc`
+pub fn parse_kinds(names: &str) -> Result<Vec<CommentKind>, String> {
+    names
+        .split(',')
+        .map(|name| {
+            CommentKind::by_name(name.trim()).ok_or_else(|| {
+                let known: Vec<_> = CommentKind::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown comment kind {name}, expect one of {}",
+                    known.join(",")
+                )
+            })
+        })
+        .collect()
+}
+
+/// parse a line range `first:last`, either end may be left out
+pub fn parse_lines(range: &str) -> Result<(usize, usize), String> {
+    let (first, last) = range
+        .split_once(':')
+        .ok_or_else(|| format!("bad line range {range}, expect <first>:<last>"))?;
+    let parse = |s: &str, default| {
+        if s.is_empty() {
+            Ok(default)
+        } else {
+            s.parse::<usize>()
+                .map_err(|error| format!("bad line range {range}: {error}"))
+        }
+    };
+    Ok((parse(first, 1)?, parse(last, usize::MAX)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(line: usize, content: &str, kind: CommentKind) -> CommentOfC {
+        CommentOfC {
+            content: content.to_string(),
+            kind,
+            line,
+            end_line: line,
+            ..CommentOfC::new()
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut block = comment(10, "/*\n * a block comment\n */", CommentKind::Block);
+        block.end_line = 12;
+        let trailing = comment(20, "/* x */", CommentKind::TrailingInline);
+
+        let all = CommentFilter::default();
+        assert!(all.matches(&block) && all.matches(&trailing));
+
+        let filter = CommentFilter {
+            kinds: parse_kinds("block,doc").unwrap(),
+            ..Default::default()
+        };
+        assert!(filter.matches(&block) && !filter.matches(&trailing));
+
+        let filter = CommentFilter {
+            min_len: 8,
+            ..Default::default()
+        };
+        assert!(filter.matches(&block) && !filter.matches(&trailing));
+
+        let filter = CommentFilter {
+            regex: Some(Regex::new(r"\bx\b").unwrap()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&block) && filter.matches(&trailing));
+
+        // the block spans lines 10 to 12
+        let filter = CommentFilter {
+            lines: Some(parse_lines("12:15").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&block) && !filter.matches(&trailing));
+        let filter = CommentFilter {
+            lines: Some(parse_lines("13:").unwrap()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&block) && filter.matches(&trailing));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse_kinds("block,inline").is_err());
+        assert_eq!(parse_lines(":5"), Ok((1, 5)));
+        assert!(parse_lines("5").is_err());
+    }
+}
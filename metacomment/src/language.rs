@@ -0,0 +1,779 @@
+use std::path::Path;
+
+/// What a [`Language`] recognizes at some position of the code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    /// a comment of `len` bytes, a line comment does not include its '\n'
+    Comment { len: usize, block: bool, doc: bool },
+    /// a string or character literal of `len` bytes, comment markers in it mean nothing
+    Literal(usize),
+    /// anything else, one byte
+    Code,
+    /// the token goes beyond the end of the buffer
+    NeedMore,
+}
+
+/// The comment markers of a language
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommentSyntax {
+    pub line: Option<&'static str>,
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/// A programming language whose comments can be extracted.
+pub trait Language: Sync {
+    fn name(&self) -> &'static str;
+
+    /// file extensions without the dot
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn syntax(&self) -> CommentSyntax;
+
+    /// Recognize the token beginning at `buffer[i]`.
+    ///
+    /// `buffer[i - 1]`, if any, is the byte before the token. Unless `eof` is
+    /// set, more bytes may follow the buffer and [`Token::NeedMore`] is
+    /// returned when they are needed to decide.
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token;
+}
+
+pub struct C;
+pub struct Cpp;
+pub struct Rust;
+pub struct Go;
+pub struct Python;
+pub struct Sql;
+
+pub static LANGUAGES: [&dyn Language; 6] = [&C, &Cpp, &Rust, &Go, &Python, &Sql];
+
+/// find a language by its name, or one of its file extensions
+pub fn by_name(name: &str) -> Option<&'static dyn Language> {
+    let name = name.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.name() == name || lang.extensions().contains(&name.as_str()))
+        .copied()
+}
+
+/// find a language by the extension of `path`
+pub fn by_path(path: impl AsRef<Path>) -> Option<&'static dyn Language> {
+    let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.extensions().contains(&ext.as_str()))
+        .copied()
+}
+
+/// The language named by a `--lang` option, or else the one of `path`'s
+/// extension. C is assumed for unknown extensions.
+pub fn detect(name: Option<&str>, path: impl AsRef<Path>) -> Result<&'static dyn Language, String> {
+    match name {
+        Some(name) => by_name(name).ok_or_else(|| {
+            let names = LANGUAGES.iter().map(|lang| lang.name()).collect::<Vec<_>>();
+            format!(
+                "unknown language {name}, expected one of {}",
+                names.join(", ")
+            )
+        }),
+        None => Ok(by_path(path).unwrap_or(&C)),
+    }
+}
+
+/// the longest marker, or marker plus the byte deciding the token, is shorter than this
+const LOOKAHEAD: usize = 8;
+
+fn need_more(buffer: &[u8], i: usize, n: usize, eof: bool) -> bool {
+    !eof && buffer.len() < i + n
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// is the byte before `i` part of an identifier or a number
+fn after_ident(buffer: &[u8], i: usize) -> bool {
+    i > 0 && is_ident(buffer[i - 1])
+}
+
+/// the comment goes until the end of the line
+fn line_comment(buffer: &[u8], i: usize, eof: bool, doc: bool) -> Token {
+    match buffer[i..].iter().position(|&b| b == b'\n') {
+        Some(len) => Token::Comment {
+            len,
+            block: false,
+            doc,
+        },
+        None if eof => Token::Comment {
+            len: buffer.len() - i,
+            block: false,
+            doc,
+        },
+        None => Token::NeedMore,
+    }
+}
+
+/// the comment begins with '/*' and ends with '*/'
+fn block_comment(buffer: &[u8], i: usize, eof: bool, nested: bool, doc: bool) -> Token {
+    let mut depth = 0;
+    let mut j = i;
+    while j + 1 < buffer.len() {
+        if buffer[j] == b'/' && buffer[j + 1] == b'*' && (nested || depth == 0) {
+            depth += 1;
+            j += 2;
+        } else if buffer[j] == b'*' && buffer[j + 1] == b'/' {
+            depth -= 1;
+            j += 2;
+            if depth == 0 {
+                return Token::Comment {
+                    len: j - i,
+                    block: true,
+                    doc,
+                };
+            }
+        } else {
+            j += 1;
+        }
+    }
+    if eof {
+        // never closed
+        Token::Comment {
+            len: buffer.len() - i,
+            block: true,
+            doc,
+        }
+    } else {
+        Token::NeedMore
+    }
+}
+
+struct Quote<'a> {
+    /// the length of the opening quote, including any prefix
+    open: usize,
+    close: &'a [u8],
+    /// a backslash escapes the next byte
+    backslash: bool,
+    /// the closing quote written twice stands for itself, as in SQL
+    doubled: bool,
+    multiline: bool,
+}
+
+fn quoted(buffer: &[u8], i: usize, eof: bool, quote: Quote) -> Token {
+    let mut j = i + quote.open;
+    while j < buffer.len() {
+        if quote.backslash && buffer[j] == b'\\' {
+            j += 2;
+        } else if buffer[j..].starts_with(quote.close) {
+            j += quote.close.len();
+            if quote.doubled {
+                if need_more(buffer, j, quote.close.len(), eof) {
+                    return Token::NeedMore;
+                }
+                if buffer[j..].starts_with(quote.close) {
+                    j += quote.close.len();
+                    continue;
+                }
+            }
+            return Token::Literal(j - i);
+        } else if buffer.len() - j < quote.close.len()
+            && quote.close.starts_with(&buffer[j..])
+            && !eof
+        {
+            return Token::NeedMore;
+        } else if buffer[j] == b'\n' && !quote.multiline {
+            // not closed on its line, do not let it eat the rest of the file
+            return Token::Literal(j - i);
+        } else {
+            j += 1;
+        }
+    }
+    if eof {
+        Token::Literal(buffer.len() - i)
+    } else {
+        Token::NeedMore
+    }
+}
+
+/// `//` and `/*` comments, as written in C and its descendants
+fn c_style_comment(buffer: &[u8], i: usize, eof: bool, nested: bool) -> Option<Token> {
+    if buffer[i] != b'/' || i + 1 >= buffer.len() {
+        return None;
+    }
+    let rest = &buffer[i..];
+    match buffer[i + 1] {
+        // `///` and `//!` document the code, `////` is just a line
+        b'/' => {
+            let doc = (rest.starts_with(b"///") && !rest.starts_with(b"////"))
+                || rest.starts_with(b"//!");
+            Some(line_comment(buffer, i, eof, doc))
+        }
+        // `/**` and `/*!` document the code, `/**/` and `/***` do not
+        b'*' => {
+            let doc = (rest.starts_with(b"/**")
+                && !rest.starts_with(b"/**/")
+                && !rest.starts_with(b"/***"))
+                || rest.starts_with(b"/*!");
+            Some(block_comment(buffer, i, eof, nested, doc))
+        }
+        _ => None,
+    }
+}
+
+fn c_string(buffer: &[u8], i: usize, eof: bool) -> Option<Token> {
+    match buffer[i] {
+        b'"' | b'\'' => Some(quoted(
+            buffer,
+            i,
+            eof,
+            Quote {
+                open: 1,
+                close: &buffer[i..i + 1],
+                backslash: true,
+                doubled: false,
+                multiline: false,
+            },
+        )),
+        _ => None,
+    }
+}
+
+impl Language for C {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["c", "h"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        CommentSyntax {
+            line: Some("//"),
+            block: Some(("/*", "*/")),
+        }
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        c_style_comment(buffer, i, eof, false)
+            .or_else(|| c_string(buffer, i, eof))
+            .unwrap_or(Token::Code)
+    }
+}
+
+impl Cpp {
+    /// `R"delimiter( ... )delimiter"`, optionally prefixed by `u8`, `u`, `U` or `L`
+    fn raw_string(buffer: &[u8], i: usize, eof: bool) -> Option<Token> {
+        if after_ident(buffer, i) {
+            return None;
+        }
+        let rest = &buffer[i..];
+        let prefix = [&b"u8R\""[..], b"uR\"", b"UR\"", b"LR\"", b"R\""]
+            .into_iter()
+            .find(|prefix| rest.starts_with(prefix))?;
+        let delim_start = i + prefix.len();
+        // the delimiter is at most 16 characters
+        let paren = match buffer[delim_start..]
+            .iter()
+            .take(17)
+            .position(|&b| b == b'(')
+        {
+            Some(paren) => delim_start + paren,
+            None if need_more(buffer, delim_start, 17, eof) => return Some(Token::NeedMore),
+            None => return None,
+        };
+        let delim = &buffer[delim_start..paren];
+        if delim
+            .iter()
+            .any(|&b| b == b')' || b == b'\\' || b.is_ascii_whitespace())
+        {
+            return None;
+        }
+        let mut close = vec![b')'];
+        close.extend_from_slice(delim);
+        close.push(b'"');
+        Some(quoted(
+            buffer,
+            i,
+            eof,
+            Quote {
+                open: paren + 1 - i,
+                close: &close,
+                backslash: false,
+                doubled: false,
+                multiline: true,
+            },
+        ))
+    }
+}
+
+impl Language for Cpp {
+    fn name(&self) -> &'static str {
+        "cpp"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cpp", "cc", "cxx", "c++", "hpp", "hh", "hxx"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        C.syntax()
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        // a digit separator, as in 1'000'000
+        if buffer[i] == b'\'' && i > 0 && buffer[i - 1].is_ascii_digit() {
+            return Token::Code;
+        }
+        c_style_comment(buffer, i, eof, false)
+            .or_else(|| Cpp::raw_string(buffer, i, eof))
+            .or_else(|| c_string(buffer, i, eof))
+            .unwrap_or(Token::Code)
+    }
+}
+
+impl Rust {
+    /// `r"..."`, `r#"..."#` and so on, optionally prefixed by `b` or `c`
+    fn raw_string(buffer: &[u8], i: usize, eof: bool) -> Option<Token> {
+        if after_ident(buffer, i) {
+            return None;
+        }
+        let mut j = i;
+        if buffer[j] == b'b' || buffer[j] == b'c' {
+            j += 1;
+        }
+        if buffer.get(j) != Some(&b'r') {
+            return None;
+        }
+        j += 1;
+        let hashes = buffer[j..].iter().take_while(|&&b| b == b'#').count();
+        j += hashes;
+        match buffer.get(j) {
+            Some(b'"') => {}
+            None if !eof => return Some(Token::NeedMore),
+            // a raw identifier such as r#type
+            _ => return None,
+        }
+        let mut close = vec![b'"'];
+        close.extend(std::iter::repeat_n(b'#', hashes));
+        Some(quoted(
+            buffer,
+            i,
+            eof,
+            Quote {
+                open: j + 1 - i,
+                close: &close,
+                backslash: false,
+                doubled: false,
+                multiline: true,
+            },
+        ))
+    }
+
+    /// a character literal, or the quote of a lifetime or a label
+    fn char_or_lifetime(buffer: &[u8], i: usize, eof: bool) -> Token {
+        let rest = &buffer[i..];
+        if rest.get(1) == Some(&b'\\') {
+            // an escape such as '\n', '\'' or '\u{1F600}'
+            return match rest.iter().skip(3).take(10).position(|&b| b == b'\'') {
+                Some(pos) => Token::Literal(pos + 4),
+                None if need_more(buffer, i, 14, eof) => Token::NeedMore,
+                None => Token::Code,
+            };
+        }
+        let char_len = match rest.get(1) {
+            Some(&b) if b < 0x80 => 1,
+            Some(&b) if b >= 0xf0 => 4,
+            Some(&b) if b >= 0xe0 => 3,
+            Some(_) => 2,
+            None => return Token::Code,
+        };
+        if rest.get(1 + char_len) == Some(&b'\'') {
+            Token::Literal(char_len + 2)
+        } else {
+            Token::Code
+        }
+    }
+}
+
+impl Language for Rust {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rs"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        C.syntax()
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        if let Some(token) = c_style_comment(buffer, i, eof, true) {
+            return token;
+        }
+        if let Some(token) = Rust::raw_string(buffer, i, eof) {
+            return token;
+        }
+        match buffer[i] {
+            b'"' => quoted(
+                buffer,
+                i,
+                eof,
+                Quote {
+                    open: 1,
+                    close: b"\"",
+                    backslash: true,
+                    doubled: false,
+                    multiline: true,
+                },
+            ),
+            b'\'' => Rust::char_or_lifetime(buffer, i, eof),
+            _ => Token::Code,
+        }
+    }
+}
+
+impl Language for Go {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["go"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        C.syntax()
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        // go has no doc comment markers, any comment before a declaration documents it
+        let comment = c_style_comment(buffer, i, eof, false).map(|token| match token {
+            Token::Comment { len, block, .. } => Token::Comment {
+                len,
+                block,
+                doc: false,
+            },
+            token => token,
+        });
+        comment
+            .or_else(|| match buffer[i] {
+                b'`' => Some(quoted(
+                    buffer,
+                    i,
+                    eof,
+                    Quote {
+                        open: 1,
+                        close: b"`",
+                        backslash: false,
+                        doubled: false,
+                        multiline: true,
+                    },
+                )),
+                _ => c_string(buffer, i, eof),
+            })
+            .unwrap_or(Token::Code)
+    }
+}
+
+impl Language for Python {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["py", "pyi"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        CommentSyntax {
+            line: Some("#"),
+            block: None,
+        }
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        let rest = &buffer[i..];
+        match buffer[i] {
+            b'#' => line_comment(buffer, i, eof, false),
+            // prefixes such as r, b and f do not change where a string ends
+            b'"' | b'\'' => {
+                let triple = rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''");
+                let open = if triple { 3 } else { 1 };
+                quoted(
+                    buffer,
+                    i,
+                    eof,
+                    Quote {
+                        open,
+                        close: &rest[..open],
+                        backslash: true,
+                        doubled: false,
+                        multiline: triple,
+                    },
+                )
+            }
+            _ => Token::Code,
+        }
+    }
+}
+
+impl Sql {
+    /// `$$ ... $$` or `$tag$ ... $tag$` as in postgres
+    fn dollar_quoted(buffer: &[u8], i: usize, eof: bool) -> Option<Token> {
+        if buffer[i] != b'$' || after_ident(buffer, i) {
+            return None;
+        }
+        let tag_len = buffer[i + 1..].iter().take_while(|&&b| is_ident(b)).count();
+        if tag_len > 0 && buffer[i + 1].is_ascii_digit() {
+            // a parameter such as $1
+            return None;
+        }
+        match buffer.get(i + 1 + tag_len) {
+            Some(b'$') => {}
+            None if !eof => return Some(Token::NeedMore),
+            _ => return None,
+        }
+        let tag = &buffer[i..i + tag_len + 2];
+        Some(quoted(
+            buffer,
+            i,
+            eof,
+            Quote {
+                open: tag.len(),
+                close: tag,
+                backslash: false,
+                doubled: false,
+                multiline: true,
+            },
+        ))
+    }
+}
+
+impl Language for Sql {
+    fn name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["sql"]
+    }
+
+    fn syntax(&self) -> CommentSyntax {
+        CommentSyntax {
+            line: Some("--"),
+            block: Some(("/*", "*/")),
+        }
+    }
+
+    fn token_at(&self, buffer: &[u8], i: usize, eof: bool) -> Token {
+        if need_more(buffer, i, LOOKAHEAD, eof) {
+            return Token::NeedMore;
+        }
+        let rest = &buffer[i..];
+        if rest.starts_with(b"--") {
+            return line_comment(buffer, i, eof, false);
+        }
+        if rest.starts_with(b"/*") {
+            // block comments nest in the SQL standard
+            return block_comment(buffer, i, eof, true, false);
+        }
+        if let Some(token) = Sql::dollar_quoted(buffer, i, eof) {
+            return token;
+        }
+        // E'...' takes backslash escapes
+        let escape =
+            (rest.starts_with(b"E'") || rest.starts_with(b"e'")) && !after_ident(buffer, i);
+        let open = if escape { 2 } else { 1 };
+        match buffer[i + open - 1] {
+            quote @ (b'\'' | b'"') => quoted(
+                buffer,
+                i,
+                eof,
+                Quote {
+                    open,
+                    close: &[quote],
+                    backslash: escape,
+                    doubled: true,
+                    multiline: true,
+                },
+            ),
+            _ => Token::Code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(None, "a/nodeAgg.c").unwrap().name(), "c");
+        assert_eq!(detect(None, "main.RS").unwrap().name(), "rust");
+        assert_eq!(detect(None, "translated.txt").unwrap().name(), "c");
+        assert_eq!(detect(Some("py"), "x.c").unwrap().name(), "python");
+        assert_eq!(detect(Some("c++"), "x.txt").unwrap().name(), "cpp");
+        assert!(detect(Some("cobol"), "x.c").is_err());
+    }
+
+    fn token(lang: &dyn Language, code: &str, eof: bool) -> Token {
+        lang.token_at(code.as_bytes(), 0, eof)
+    }
+
+    fn comment(len: usize, block: bool, doc: bool) -> Token {
+        Token::Comment { len, block, doc }
+    }
+
+    #[test]
+    fn test_c_tokens() {
+        assert_eq!(
+            token(&C, "// line\nx = 1;", false),
+            comment(7, false, false)
+        );
+        assert_eq!(token(&C, "/** doc */ x", false), comment(10, true, true));
+        assert_eq!(token(&C, "/**/ int x;", false), comment(4, true, false));
+        // block comments do not nest
+        assert_eq!(
+            token(&C, "/* a /* b */ c */", false),
+            comment(12, true, false)
+        );
+        assert_eq!(token(&C, "\"/* no */\" x", false), Token::Literal(10));
+        assert_eq!(C.token_at(b"a = 'b'; // c", 4, false), Token::Literal(3));
+        assert_eq!(token(&C, "int x = 1;", false), Token::Code);
+
+        // at the end of the buffer
+        assert_eq!(token(&C, "/* not closed yet", false), Token::NeedMore);
+        assert_eq!(
+            token(&C, "/* not closed yet", true),
+            comment(17, true, false)
+        );
+        assert_eq!(token(&C, "// no newline yet", false), Token::NeedMore);
+        assert_eq!(
+            token(&C, "// no newline yet", true),
+            comment(17, false, false)
+        );
+        assert_eq!(token(&C, "/", false), Token::NeedMore);
+        assert_eq!(token(&C, "/", true), Token::Code);
+    }
+
+    #[test]
+    fn test_cpp_tokens() {
+        assert_eq!(
+            token(&Cpp, "R\"x(a)\" )x\" + 1;", false),
+            Token::Literal(11)
+        );
+        assert_eq!(token(&Cpp, "u8R\"(/* a */)\";", false), Token::Literal(14));
+        // a digit separator
+        assert_eq!(Cpp.token_at(b"1'000'000; // n", 1, false), Token::Code);
+        assert_eq!(
+            token(&Cpp, "/// doc\nint x;", false),
+            comment(7, false, true)
+        );
+
+        assert_eq!(token(&Cpp, "R\"delim(never", false), Token::NeedMore);
+        assert_eq!(token(&Cpp, "R\"delim(never", true), Token::Literal(13));
+    }
+
+    #[test]
+    fn test_rust_tokens() {
+        // block comments nest
+        assert_eq!(
+            token(&Rust, "/* a /* b */ c */ x", false),
+            comment(17, true, false)
+        );
+        assert_eq!(
+            token(&Rust, "/// doc\nfn f", false),
+            comment(7, false, true)
+        );
+        assert_eq!(token(&Rust, "//! inner\n", false), comment(9, false, true));
+        assert_eq!(
+            token(&Rust, "//// not doc\nx", false),
+            comment(12, false, false)
+        );
+        assert_eq!(
+            token(&Rust, "r#\"say \"hi\"\"# ;", false),
+            Token::Literal(13)
+        );
+        assert_eq!(token(&Rust, "'x' + 1;", false), Token::Literal(3));
+        assert_eq!(token(&Rust, "'\\n' + 1;", false), Token::Literal(4));
+        // a lifetime and a raw identifier
+        assert_eq!(token(&Rust, "'a> // c\n", false), Token::Code);
+        assert_eq!(token(&Rust, "r#type = 1;", false), Token::Code);
+
+        assert_eq!(token(&Rust, "r#######", false), Token::NeedMore);
+        assert_eq!(token(&Rust, "r#######", true), Token::Code);
+        assert_eq!(token(&Rust, "\"not closed", false), Token::NeedMore);
+        assert_eq!(token(&Rust, "\"not closed", true), Token::Literal(11));
+    }
+
+    #[test]
+    fn test_go_tokens() {
+        // no doc comment markers
+        assert_eq!(
+            token(&Go, "/** not doc */ x", false),
+            comment(14, true, false)
+        );
+        assert_eq!(
+            token(&Go, "/// not doc\n", false),
+            comment(11, false, false)
+        );
+        assert_eq!(token(&Go, "`a\n/* b */` x", false), Token::Literal(11));
+
+        assert_eq!(token(&Go, "`never closed", false), Token::NeedMore);
+        assert_eq!(token(&Go, "`never closed", true), Token::Literal(13));
+    }
+
+    #[test]
+    fn test_python_tokens() {
+        assert_eq!(
+            token(&Python, "# c\nx = 1", false),
+            comment(3, false, false)
+        );
+        assert_eq!(
+            token(&Python, "\"\"\"doc # not\n\"\"\"  x", false),
+            Token::Literal(16)
+        );
+        assert_eq!(token(&Python, "'# no' + x", false), Token::Literal(6));
+        // not closed on its line
+        assert_eq!(token(&Python, "'abc\nx = 1", false), Token::Literal(4));
+
+        // the closing quotes cut by the end of the buffer
+        assert_eq!(token(&Python, "\"\"\"abc\"\"", false), Token::NeedMore);
+        assert_eq!(token(&Python, "\"\"\"abc\"\"", true), Token::Literal(8));
+    }
+
+    #[test]
+    fn test_sql_tokens() {
+        assert_eq!(token(&Sql, "-- c\nselect", false), comment(4, false, false));
+        assert_eq!(
+            token(&Sql, "/* a /* b */ c */ x", false),
+            comment(17, true, false)
+        );
+        assert_eq!(token(&Sql, "$$ -- no $$ x", false), Token::Literal(11));
+        assert_eq!(token(&Sql, "$fn$ body $fn$;", false), Token::Literal(14));
+        // a parameter
+        assert_eq!(token(&Sql, "$1 -- c\nx", false), Token::Code);
+        assert_eq!(token(&Sql, "'it''s' x", false), Token::Literal(7));
+        assert_eq!(token(&Sql, "E'a\\'b' x", false), Token::Literal(7));
+
+        // a doubled quote may follow
+        assert_eq!(token(&Sql, "'abcdef'", false), Token::NeedMore);
+        assert_eq!(token(&Sql, "'abcdef'", true), Token::Literal(8));
+    }
+}
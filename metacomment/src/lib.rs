@@ -0,0 +1,6 @@
+pub mod cli;
+pub mod comment_extractor;
+pub mod comment_of_c;
+pub mod filter;
+pub mod language;
+pub mod scanner;
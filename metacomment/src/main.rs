@@ -1,20 +1,81 @@
 use std::env;
 
-mod comment_of_c;
-mod comment_extractor;
-use comment_extractor::*;
+use serde::Serialize;
+
+use metacomment::cli::take_option;
+use metacomment::comment_extractor::*;
+use metacomment::filter::{self, CommentFilter};
+use metacomment::language;
+
+/// one line of the `--json` output
+#[derive(Serialize)]
+struct JsonComment<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    comment: &'a CommentOfC,
+}
+
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    })
+}
 
 fn main() -> std::io::Result<()> {
     let mut args: Vec<_> = env::args().collect();
+    let json = match args.iter().position(|arg| arg == "--json") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let lang = take_option(&mut args, "--lang");
+    let mut filter = CommentFilter::default();
+    if let Some(kinds) = take_option(&mut args, "--kind") {
+        filter.kinds = exit_on_error(filter::parse_kinds(&kinds));
+    }
+    if let Some(min_len) = take_option(&mut args, "--min-len") {
+        filter.min_len = exit_on_error(
+            min_len
+                .parse()
+                .map_err(|error| format!("--min-len: {error}")),
+        );
+    }
+    if let Some(regex) = take_option(&mut args, "--regex") {
+        filter.regex = Some(exit_on_error(
+            regex::Regex::new(&regex).map_err(|error| format!("--regex: {error}")),
+        ));
+    }
+    if let Some(lines) = take_option(&mut args, "--lines") {
+        filter.lines = Some(exit_on_error(filter::parse_lines(&lines)));
+    }
     if args.len() != 2 {
-        println!("usage: {} <c code file>", args[0]);
+        println!(
+            "usage: {} [--json] [--lang <language>] [--kind <block,line,trailing,doc>] [--min-len <chars>] [--regex <pattern>] [--lines <first>:<last>] <code file>",
+            args[0]
+        );
         return Ok(());
     }
     let infile = args.remove(1);
-    let infile = std::fs::File::open(infile)?;
-    let ce = CommentExtractor::new(infile);
-    for com in ce {
-        println!("{}", com);
+    let language = exit_on_error(language::detect(lang.as_deref(), &infile));
+    let filename = infile;
+    let infile = std::fs::File::open(&filename)?;
+    let mut ce = CommentExtractor::with_language(infile, language);
+    if !filter.kinds.is_empty() {
+        ce = ce.kinds(&filter.kinds);
+    }
+    for com in ce.filter(|com| filter.matches(com)) {
+        if json {
+            let line = JsonComment {
+                file: &filename,
+                comment: &com,
+            };
+            println!("{}", serde_json::to_string(&line)?);
+        } else {
+            println!("{}", com);
+        }
     }
 
     Ok(())
@@ -0,0 +1,391 @@
+use crate::language::{Language, Token};
+
+/// Consecutive line comments, each on its own line
+#[derive(Debug, Clone)]
+struct Group {
+    start: usize,
+    end: usize,
+    lines: usize,
+    doc: bool,
+    /// newlines seen after the last comment of the group
+    newlines: usize,
+}
+
+/// Where a comment is and what it is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentKind {
+    /// begins a line and spans several lines, either a block comment or a
+    /// run of line comments on consecutive lines
+    Block,
+    /// begins a line and ends on the same line
+    LeadingLine,
+    /// follows some code on the same line
+    TrailingInline,
+    /// documents the code, such as `/** */` and `///`, and begins a line
+    Doc,
+}
+
+impl CommentKind {
+    pub const ALL: [CommentKind; 4] = [
+        CommentKind::Block,
+        CommentKind::LeadingLine,
+        CommentKind::TrailingInline,
+        CommentKind::Doc,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommentKind::Block => "block",
+            CommentKind::LeadingLine => "line",
+            CommentKind::TrailingInline => "trailing",
+            CommentKind::Doc => "doc",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        CommentKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+impl serde::Serialize for CommentKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// The state machine finding comments and telling their [`CommentKind`].
+///
+/// Line comments on consecutive lines are taken as one comment. Whitespaces
+/// before a comment beginning a line are part of it.
+///
+/// It remembers where it stopped, so a buffer may be fed in pieces: scan,
+/// append more bytes, and scan again. Positions are indexes into the buffer
+/// handed to [`CommentScanner::scan`]; call [`CommentScanner::shift`] after
+/// dropping bytes from the head of the buffer.
+#[derive(Clone)]
+pub struct CommentScanner {
+    language: &'static dyn Language,
+    /// the next byte to look at
+    pos: usize,
+    line_start: usize,
+    /// nothing but whitespaces since `line_start`
+    leading: bool,
+    group: Option<Group>,
+}
+
+impl CommentScanner {
+    pub fn new(language: &'static dyn Language) -> Self {
+        CommentScanner {
+            language,
+            pos: 0,
+            line_start: 0,
+            leading: true,
+            group: None,
+        }
+    }
+
+    pub fn language(&self) -> &'static dyn Language {
+        self.language
+    }
+
+    /// Continue scanning `buffer` and return the next comment found as
+    /// `(start, end, kind)`.
+    ///
+    /// Unless `eof` is set, more bytes are expected to be appended, and a
+    /// token not complete in the buffer is left for the next call.
+    pub fn scan(&mut self, buffer: &[u8], eof: bool) -> Option<(usize, usize, CommentKind)> {
+        let mut i = self.pos;
+        loop {
+            if i >= buffer.len() {
+                self.pos = i;
+                return if eof { self.finish_group() } else { None };
+            }
+
+            if buffer[i] == b'\n' {
+                if let Some(group) = &mut self.group {
+                    group.newlines += 1;
+                    // an empty line ends the group
+                    if group.newlines > 1 {
+                        self.pos = i;
+                        return self.finish_group();
+                    }
+                }
+                self.line_start = i + 1;
+                self.leading = true;
+                i += 1;
+                continue;
+            }
+            if buffer[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let token = self.language.token_at(buffer, i, eof);
+            if token == Token::NeedMore {
+                self.pos = i;
+                return None;
+            }
+
+            if let Token::Comment {
+                len,
+                block: false,
+                doc,
+            } = token
+            {
+                if self.leading {
+                    match &mut self.group {
+                        Some(group) if group.newlines == 1 && group.doc == doc => {
+                            group.end = i + len;
+                            group.lines += 1;
+                            group.newlines = 0;
+                            self.leading = false;
+                            i += len;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            if self.group.is_some() {
+                self.pos = i;
+                return self.finish_group();
+            }
+
+            let leading = self.leading;
+            self.leading = false;
+            match token {
+                Token::Comment {
+                    len,
+                    block: false,
+                    doc,
+                } if leading => {
+                    self.group = Some(Group {
+                        start: indent_start(buffer, self.line_start, i),
+                        end: i + len,
+                        lines: 1,
+                        doc,
+                        newlines: 0,
+                    });
+                    i += len;
+                }
+                Token::Comment { len, doc, .. } => {
+                    self.pos = i + len;
+                    if !leading {
+                        return Some((i, i + len, CommentKind::TrailingInline));
+                    }
+                    let kind = if doc {
+                        CommentKind::Doc
+                    } else if buffer[i..i + len].contains(&b'\n') {
+                        CommentKind::Block
+                    } else {
+                        CommentKind::LeadingLine
+                    };
+                    return Some((indent_start(buffer, self.line_start, i), i + len, kind));
+                }
+                Token::Literal(len) => i += len,
+                Token::Code | Token::NeedMore => i += 1,
+            }
+        }
+    }
+
+    fn finish_group(&mut self) -> Option<(usize, usize, CommentKind)> {
+        let group = self.group.take()?;
+        let kind = if group.doc {
+            CommentKind::Doc
+        } else if group.lines > 1 {
+            CommentKind::Block
+        } else {
+            CommentKind::LeadingLine
+        };
+        Some((group.start, group.end, kind))
+    }
+
+    /// The bytes before the returned index will never be part of a comment
+    /// found later, so the caller is free to drop them.
+    pub fn keep_from(&self) -> usize {
+        let mut keep = self.pos;
+        if let Some(group) = &self.group {
+            keep = keep.min(group.start);
+        }
+        if self.leading {
+            keep = keep.min(self.line_start);
+        }
+        // languages look at the byte before a token
+        keep.saturating_sub(1)
+    }
+
+    /// the caller dropped `n` bytes from the head of the buffer
+    pub fn shift(&mut self, n: usize) {
+        debug_assert!(n <= self.keep_from());
+        self.pos -= n;
+        self.line_start = self.line_start.saturating_sub(n);
+        if let Some(group) = &mut self.group {
+            group.start -= n;
+            group.end -= n;
+        }
+    }
+}
+
+/// where the spaces and tabs before `i` begin
+fn indent_start(buffer: &[u8], line_start: usize, i: usize) -> usize {
+    let mut start = i;
+    while start > line_start && (buffer[start - 1] == b' ' || buffer[start - 1] == b'\t') {
+        start -= 1;
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::{self, by_name};
+
+    fn scan_all(lang: &str, code: &str) -> Vec<(String, CommentKind)> {
+        let mut scanner = CommentScanner::new(by_name(lang).unwrap());
+        let mut found = Vec::new();
+        while let Some((start, end, kind)) = scanner.scan(code.as_bytes(), true) {
+            found.push((code[start..end].to_string(), kind));
+        }
+        found
+    }
+
+    /// the multi-line comments beginning a line
+    fn comments(lang: &str, code: &str) -> Vec<String> {
+        scan_all(lang, code)
+            .into_iter()
+            .filter(|(_, kind)| *kind == CommentKind::Block || *kind == CommentKind::Doc)
+            .map(|(comment, _)| comment)
+            .collect()
+    }
+
+    #[test]
+    fn test_kinds() {
+        use CommentKind::*;
+        let code = "/* leading */ int a;\nint b; /* trailing\n */\n  // one line\nint c; // trailing\n\n/**\n * doc\n */\n/* block\n */\n/// doc line\n// x\n// y";
+        assert_eq!(
+            scan_all("c", code),
+            vec![
+                ("/* leading */".to_string(), LeadingLine),
+                ("/* trailing\n */".to_string(), TrailingInline),
+                ("  // one line".to_string(), LeadingLine),
+                ("// trailing".to_string(), TrailingInline),
+                ("/**\n * doc\n */".to_string(), Doc),
+                ("/* block\n */".to_string(), Block),
+                ("/// doc line".to_string(), Doc),
+                ("// x\n// y".to_string(), Block),
+            ]
+        );
+        // python has no doc comments, a lone # line is not a block
+        let code = "x = 1  # trailing\n# alone\n";
+        assert_eq!(
+            scan_all("python", code),
+            vec![
+                ("# trailing".to_string(), TrailingInline),
+                ("# alone".to_string(), LeadingLine),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_strings_hide_comments() {
+        let code = "char *s = \"/*\";\n/*\n * real\n */\nchar c = '\"';\n/*\n * second\n */";
+        assert_eq!(
+            comments("c", code),
+            vec!["/*\n * real\n */", "/*\n * second\n */"]
+        );
+        // a string is not allowed to go on to the next line
+        let code = "char *s = \"unclosed;\n/*\n * found\n */";
+        assert_eq!(comments("c", code), vec!["/*\n * found\n */"]);
+    }
+
+    #[test]
+    fn test_line_comment_groups() {
+        let code = "int a;\n  // first\n  // second\nint b; // trailing\n// alone\n\n// x\n// y\n";
+        assert_eq!(
+            comments("c", code),
+            vec!["  // first\n  // second", "// x\n// y"]
+        );
+        // doc comments are not grouped with plain ones
+        let code = "/// doc\n/// more\n// plain\n// more\nfn f() {}";
+        assert_eq!(
+            comments("rust", code),
+            vec!["/// doc\n/// more", "// plain\n// more"]
+        );
+    }
+
+    #[test]
+    fn test_cpp_raw_string() {
+        let code = "auto s = R\"x(\n/* not a comment\n)\" */\n)x\";\n/*\n * real\n */";
+        assert_eq!(comments("cpp", code), vec!["/*\n * real\n */"]);
+        let code = "int n = 1'000;\n/*\n * real\n */";
+        assert_eq!(comments("cpp", code), vec!["/*\n * real\n */"]);
+    }
+
+    #[test]
+    fn test_rust() {
+        let code = "fn f<'a>(s: &'a str) -> char {\n    let r = r##\"/* \"# \"##;\n    /* outer /* inner\n */ still */\n    '\"'\n}";
+        assert_eq!(
+            comments("rust", code),
+            vec!["    /* outer /* inner\n */ still */"]
+        );
+        let code = "let c = '\\'';\n// a\n// b\nlet s = \"//\n// not\";";
+        assert_eq!(comments("rust", code), vec!["// a\n// b"]);
+    }
+
+    #[test]
+    fn test_go() {
+        let code = "s := `/*\n`\n/*\n real\n*/\nr := '\\''\n// a\n// b";
+        assert_eq!(comments("go", code), vec!["/*\n real\n*/", "// a\n// b"]);
+    }
+
+    #[test]
+    fn test_python() {
+        let code = "s = \"\"\"\n# not a comment\n# either\n\"\"\"\n# one\n# two\nx = '#'\n";
+        assert_eq!(comments("python", code), vec!["# one\n# two"]);
+    }
+
+    #[test]
+    fn test_sql() {
+        let code = "select 'it''s /*', $$\n-- no\n-- no\n$$, E'\\'--';\n/* a /* nested\n */ comment */\n-- one\n-- two\n";
+        assert_eq!(
+            comments("sql", code),
+            vec!["/* a /* nested\n */ comment */", "-- one\n-- two"]
+        );
+        let code = "create function f() returns int as $body$\n/*\n no\n */\n$body$;";
+        assert!(comments("sql", code).is_empty());
+    }
+
+    #[test]
+    fn test_scan_in_pieces() {
+        let code = "s = \"/*\"\n/* a\n b */\n// c\n// d\nx = 1; /* e\n f */\n";
+        for lang in language::LANGUAGES {
+            let expected = scan_all(lang.name(), code);
+            for chunk in 1..4 {
+                let mut scanner = CommentScanner::new(lang);
+                let mut buffer = Vec::new();
+                let mut base = 0;
+                let mut found = Vec::new();
+                for piece in code.as_bytes().chunks(chunk) {
+                    buffer.extend_from_slice(piece);
+                    while let Some((start, end, kind)) = scanner.scan(&buffer, false) {
+                        let comment = String::from_utf8(buffer[start..end].to_vec()).unwrap();
+                        found.push((comment, kind));
+                    }
+                    let keep = scanner.keep_from();
+                    buffer.drain(..keep);
+                    scanner.shift(keep);
+                    base += keep;
+                }
+                while let Some((start, end, kind)) = scanner.scan(&buffer, true) {
+                    let comment = String::from_utf8(buffer[start..end].to_vec()).unwrap();
+                    found.push((comment, kind));
+                }
+                assert!(base > 0);
+                assert_eq!(found, expected, "{} in pieces of {chunk}", lang.name());
+            }
+        }
+    }
+}
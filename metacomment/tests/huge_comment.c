@@ -0,0 +1,640 @@
+/*-------------------------------------------------------------------------
+ *
+ * huge_comment.c
+ *	  Regression fixture for block comments larger than the read buffer.
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ * the aggregate transition state is kept in a per-group memory context and
+ * is reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ * reset whenever a new group starts so that we never leak memory across
+ * groups while spilling tuples to disk when hash tables exceed work_mem the
+ * aggregate transition state is kept in a per-group memory context and is
+ *
+ *-------------------------------------------------------------------------
+ */
+#include "postgres.h"
+
+/*
+ * A small comment right after the huge one.
+ */
+static int	counter = 0;	/* inline comment */
+
+static int	value_0 = 0 / 2;
+static int	value_1 = 1 / 2;
+static int	value_2 = 2 / 2;
+static int	value_3 = 3 / 2;
+static int	value_4 = 4 / 2;
+static int	value_5 = 5 / 2;
+static int	value_6 = 6 / 2;
+static int	value_7 = 7 / 2;
+static int	value_8 = 8 / 2;
+static int	value_9 = 9 / 2;
+static int	value_10 = 10 / 2;
+static int	value_11 = 11 / 2;
+static int	value_12 = 12 / 2;
+static int	value_13 = 13 / 2;
+static int	value_14 = 14 / 2;
+static int	value_15 = 15 / 2;
+static int	value_16 = 16 / 2;
+static int	value_17 = 17 / 2;
+static int	value_18 = 18 / 2;
+static int	value_19 = 19 / 2;
+static int	value_20 = 20 / 2;
+static int	value_21 = 21 / 2;
+static int	value_22 = 22 / 2;
+static int	value_23 = 23 / 2;
+static int	value_24 = 24 / 2;
+static int	value_25 = 25 / 2;
+static int	value_26 = 26 / 2;
+static int	value_27 = 27 / 2;
+static int	value_28 = 28 / 2;
+static int	value_29 = 29 / 2;
+static int	value_30 = 30 / 2;
+static int	value_31 = 31 / 2;
+static int	value_32 = 32 / 2;
+static int	value_33 = 33 / 2;
+static int	value_34 = 34 / 2;
+static int	value_35 = 35 / 2;
+static int	value_36 = 36 / 2;
+static int	value_37 = 37 / 2;
+static int	value_38 = 38 / 2;
+static int	value_39 = 39 / 2;
+static int	value_40 = 40 / 2;
+static int	value_41 = 41 / 2;
+static int	value_42 = 42 / 2;
+static int	value_43 = 43 / 2;
+static int	value_44 = 44 / 2;
+static int	value_45 = 45 / 2;
+static int	value_46 = 46 / 2;
+static int	value_47 = 47 / 2;
+static int	value_48 = 48 / 2;
+static int	value_49 = 49 / 2;
+static int	value_50 = 50 / 2;
+static int	value_51 = 51 / 2;
+static int	value_52 = 52 / 2;
+static int	value_53 = 53 / 2;
+static int	value_54 = 54 / 2;
+static int	value_55 = 55 / 2;
+static int	value_56 = 56 / 2;
+static int	value_57 = 57 / 2;
+static int	value_58 = 58 / 2;
+static int	value_59 = 59 / 2;
+static int	value_60 = 60 / 2;
+static int	value_61 = 61 / 2;
+static int	value_62 = 62 / 2;
+static int	value_63 = 63 / 2;
+static int	value_64 = 64 / 2;
+static int	value_65 = 65 / 2;
+static int	value_66 = 66 / 2;
+static int	value_67 = 67 / 2;
+static int	value_68 = 68 / 2;
+static int	value_69 = 69 / 2;
+static int	value_70 = 70 / 2;
+static int	value_71 = 71 / 2;
+static int	value_72 = 72 / 2;
+static int	value_73 = 73 / 2;
+static int	value_74 = 74 / 2;
+static int	value_75 = 75 / 2;
+static int	value_76 = 76 / 2;
+static int	value_77 = 77 / 2;
+static int	value_78 = 78 / 2;
+static int	value_79 = 79 / 2;
+static int	value_80 = 80 / 2;
+static int	value_81 = 81 / 2;
+static int	value_82 = 82 / 2;
+static int	value_83 = 83 / 2;
+static int	value_84 = 84 / 2;
+static int	value_85 = 85 / 2;
+static int	value_86 = 86 / 2;
+static int	value_87 = 87 / 2;
+static int	value_88 = 88 / 2;
+static int	value_89 = 89 / 2;
+static int	value_90 = 90 / 2;
+static int	value_91 = 91 / 2;
+static int	value_92 = 92 / 2;
+static int	value_93 = 93 / 2;
+static int	value_94 = 94 / 2;
+static int	value_95 = 95 / 2;
+static int	value_96 = 96 / 2;
+static int	value_97 = 97 / 2;
+static int	value_98 = 98 / 2;
+static int	value_99 = 99 / 2;
+static int	value_100 = 100 / 2;
+static int	value_101 = 101 / 2;
+static int	value_102 = 102 / 2;
+static int	value_103 = 103 / 2;
+static int	value_104 = 104 / 2;
+static int	value_105 = 105 / 2;
+static int	value_106 = 106 / 2;
+static int	value_107 = 107 / 2;
+static int	value_108 = 108 / 2;
+static int	value_109 = 109 / 2;
+static int	value_110 = 110 / 2;
+static int	value_111 = 111 / 2;
+static int	value_112 = 112 / 2;
+static int	value_113 = 113 / 2;
+static int	value_114 = 114 / 2;
+static int	value_115 = 115 / 2;
+static int	value_116 = 116 / 2;
+static int	value_117 = 117 / 2;
+static int	value_118 = 118 / 2;
+static int	value_119 = 119 / 2;
+static int	value_120 = 120 / 2;
+static int	value_121 = 121 / 2;
+static int	value_122 = 122 / 2;
+static int	value_123 = 123 / 2;
+static int	value_124 = 124 / 2;
+static int	value_125 = 125 / 2;
+static int	value_126 = 126 / 2;
+static int	value_127 = 127 / 2;
+static int	value_128 = 128 / 2;
+static int	value_129 = 129 / 2;
+static int	value_130 = 130 / 2;
+static int	value_131 = 131 / 2;
+static int	value_132 = 132 / 2;
+static int	value_133 = 133 / 2;
+static int	value_134 = 134 / 2;
+static int	value_135 = 135 / 2;
+static int	value_136 = 136 / 2;
+static int	value_137 = 137 / 2;
+static int	value_138 = 138 / 2;
+static int	value_139 = 139 / 2;
+static int	value_140 = 140 / 2;
+static int	value_141 = 141 / 2;
+static int	value_142 = 142 / 2;
+static int	value_143 = 143 / 2;
+static int	value_144 = 144 / 2;
+static int	value_145 = 145 / 2;
+static int	value_146 = 146 / 2;
+static int	value_147 = 147 / 2;
+static int	value_148 = 148 / 2;
+static int	value_149 = 149 / 2;
+static int	value_150 = 150 / 2;
+static int	value_151 = 151 / 2;
+static int	value_152 = 152 / 2;
+static int	value_153 = 153 / 2;
+static int	value_154 = 154 / 2;
+static int	value_155 = 155 / 2;
+static int	value_156 = 156 / 2;
+static int	value_157 = 157 / 2;
+static int	value_158 = 158 / 2;
+static int	value_159 = 159 / 2;
+static int	value_160 = 160 / 2;
+static int	value_161 = 161 / 2;
+static int	value_162 = 162 / 2;
+static int	value_163 = 163 / 2;
+static int	value_164 = 164 / 2;
+static int	value_165 = 165 / 2;
+static int	value_166 = 166 / 2;
+static int	value_167 = 167 / 2;
+static int	value_168 = 168 / 2;
+static int	value_169 = 169 / 2;
+static int	value_170 = 170 / 2;
+static int	value_171 = 171 / 2;
+static int	value_172 = 172 / 2;
+static int	value_173 = 173 / 2;
+static int	value_174 = 174 / 2;
+static int	value_175 = 175 / 2;
+static int	value_176 = 176 / 2;
+static int	value_177 = 177 / 2;
+static int	value_178 = 178 / 2;
+static int	value_179 = 179 / 2;
+static int	value_180 = 180 / 2;
+static int	value_181 = 181 / 2;
+static int	value_182 = 182 / 2;
+static int	value_183 = 183 / 2;
+static int	value_184 = 184 / 2;
+static int	value_185 = 185 / 2;
+static int	value_186 = 186 / 2;
+static int	value_187 = 187 / 2;
+static int	value_188 = 188 / 2;
+static int	value_189 = 189 / 2;
+static int	value_190 = 190 / 2;
+static int	value_191 = 191 / 2;
+static int	value_192 = 192 / 2;
+static int	value_193 = 193 / 2;
+static int	value_194 = 194 / 2;
+static int	value_195 = 195 / 2;
+static int	value_196 = 196 / 2;
+static int	value_197 = 197 / 2;
+static int	value_198 = 198 / 2;
+static int	value_199 = 199 / 2;
+static int	value_200 = 200 / 2;
+static int	value_201 = 201 / 2;
+static int	value_202 = 202 / 2;
+static int	value_203 = 203 / 2;
+static int	value_204 = 204 / 2;
+static int	value_205 = 205 / 2;
+static int	value_206 = 206 / 2;
+static int	value_207 = 207 / 2;
+static int	value_208 = 208 / 2;
+static int	value_209 = 209 / 2;
+static int	value_210 = 210 / 2;
+static int	value_211 = 211 / 2;
+static int	value_212 = 212 / 2;
+static int	value_213 = 213 / 2;
+static int	value_214 = 214 / 2;
+static int	value_215 = 215 / 2;
+static int	value_216 = 216 / 2;
+static int	value_217 = 217 / 2;
+static int	value_218 = 218 / 2;
+static int	value_219 = 219 / 2;
+static int	value_220 = 220 / 2;
+static int	value_221 = 221 / 2;
+static int	value_222 = 222 / 2;
+static int	value_223 = 223 / 2;
+static int	value_224 = 224 / 2;
+static int	value_225 = 225 / 2;
+static int	value_226 = 226 / 2;
+static int	value_227 = 227 / 2;
+static int	value_228 = 228 / 2;
+static int	value_229 = 229 / 2;
+static int	value_230 = 230 / 2;
+static int	value_231 = 231 / 2;
+static int	value_232 = 232 / 2;
+static int	value_233 = 233 / 2;
+static int	value_234 = 234 / 2;
+static int	value_235 = 235 / 2;
+static int	value_236 = 236 / 2;
+static int	value_237 = 237 / 2;
+static int	value_238 = 238 / 2;
+static int	value_239 = 239 / 2;
+static int	value_240 = 240 / 2;
+static int	value_241 = 241 / 2;
+static int	value_242 = 242 / 2;
+static int	value_243 = 243 / 2;
+static int	value_244 = 244 / 2;
+static int	value_245 = 245 / 2;
+static int	value_246 = 246 / 2;
+static int	value_247 = 247 / 2;
+static int	value_248 = 248 / 2;
+static int	value_249 = 249 / 2;
+static int	value_250 = 250 / 2;
+static int	value_251 = 251 / 2;
+static int	value_252 = 252 / 2;
+static int	value_253 = 253 / 2;
+static int	value_254 = 254 / 2;
+static int	value_255 = 255 / 2;
+static int	value_256 = 256 / 2;
+static int	value_257 = 257 / 2;
+static int	value_258 = 258 / 2;
+static int	value_259 = 259 / 2;
+
+/*
+ * This comment comes after more than two buffers of code.
+ */
+void
+advance_aggregates(int nsets)
+{
+	/*
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 *
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 *
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 * is reset whenever a new group starts so that we never leak memory across
+	 * groups while spilling tuples to disk when hash tables exceed work_mem
+	 * the aggregate transition state is kept in a per-group memory context and
+	 *
+	 */
+	counter += nsets;
+}
@@ -0,0 +1,10 @@
+/*
+ * 文件头注释
+ */
+
+static int 计数 = 0; /* 计数器 */
+
+	/*
+	 * 函数说明：返回 “值”
+	 */
+int 值(void) { return 计数; }